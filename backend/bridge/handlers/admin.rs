@@ -2,21 +2,36 @@ use axum::{
     Json,
     extract::{Extension, Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use sea_orm::DatabaseConnection;
 use uuid::Uuid;
 
 use crate::{
-    bridge::types::{
-        admin::{AdminUser, *},
-        auth::AuthUser,
-        logging::LoggingInfo,
+    bridge::{
+        middleware::permission::RequirePermission,
+        types::{
+            admin::{AdminUser, *},
+            auth::{AuthUser, MfaChallengeResponse, MfaVerifyRequest},
+            logging::LoggingInfo,
+        },
     },
     check_single_permission,
-    control::services::admin_service::AdminService,
-    domain::permissions::Permission::{AdminDelete, AdminRead, AdminWrite},
-    infrastructure::app_error::{AppError, ErrorResponse, MessageResponse},
+    control::services::{
+        admin_service::AdminService, login_throttle_service::LoginThrottleService,
+    },
+    domain::{
+        auth::LoginAttempt,
+        permissions::{
+            Permission::AdminRead,
+            PermissionSet,
+            markers::{AdminDatabase, AdminHealth, AdminLogs, AdminUsers},
+        },
+    },
+    infrastructure::{
+        app_error::{AppError, ErrorResponse, MessageResponse, ThrottledError},
+        session_cookies::SESSION_COOKIES,
+    },
 };
 
 /// Admin login endpoint
@@ -26,29 +41,103 @@ use crate::{
     request_body = AdminLoginRequest,
     responses(
         (status = 200, description = "Admin login successful", body = AdminLoginResponse),
+        (status = 202, description = "Password accepted, MFA code required", body = MfaChallengeResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - invalid credentials", body = ErrorResponse),
         (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 429, description = "Too many failed attempts for this account or client, see the Retry-After header", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Admin login",
-    description = "Authenticates an admin user and returns a JWT token. Requires admin:read permission.",
+    description = "Authenticates an admin user and returns a JWT token. Requires admin:read permission. Admins with MFA enabled, or whose role requires it, get an MFA challenge to complete at /mfa/verify instead.",
     tag = ADMIN_TAG
 )]
 pub async fn admin_login_handler(
     State(db): State<DatabaseConnection>,
     Extension(logging_info): Extension<LoggingInfo>,
     Json(payload): Json<AdminLoginRequest>,
-) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&payload.email, &AdminRead, &db);
-    let response = AdminService::authenticate_admin(
+) -> Result<Response, ThrottledError> {
+    let attempt = LoginAttempt::new(
+        Some(&payload.email),
+        logging_info.path,
+        logging_info.ip_address.clone(),
+        logging_info.user_agent.clone(),
+    );
+
+    let result = LoginThrottleService::protect(&db, &attempt, async {
+        check_single_permission!(&payload.email, &AdminRead, &db);
+        AdminService::authenticate_admin(
+            &db,
+            payload,
+            logging_info.user_agent,
+            logging_info.ip_address,
+        )
+        .await
+    })
+    .await?;
+
+    Ok(match result {
+        AdminLoginResult::Authenticated(body) => {
+            let mut response = (StatusCode::OK, Json(&body)).into_response();
+            SESSION_COOKIES.set_session(response.headers_mut(), &body.token, &body.refresh_token);
+            response
+        }
+        AdminLoginResult::MfaRequired(challenge) => {
+            (StatusCode::ACCEPTED, Json(challenge)).into_response()
+        }
+    })
+}
+
+/// Admin MFA verification endpoint
+#[utoipa::path(
+    post,
+    path = "/mfa/verify",
+    request_body = MfaVerifyRequest,
+    responses(
+        (status = 200, description = "Admin login successful", body = AdminLoginResponse),
+        (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - invalid challenge or code", body = ErrorResponse, examples(
+            ("invalid_challenge" = (value = json!({"message": "Invalid or expired MFA challenge"}))),
+            ("invalid_code" = (value = json!({"message": "Invalid MFA code"})))
+        )),
+        (status = 429, description = "Too many failed attempts from this client, see the Retry-After header", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Complete admin MFA login",
+    description = "Exchanges the challenge token from /login and a TOTP or recovery code for a session. When the challenge included a setup secret, this also enables MFA and returns the recovery codes.",
+    tag = ADMIN_TAG
+)]
+pub async fn admin_mfa_verify_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    Json(payload): Json<MfaVerifyRequest>,
+) -> Result<impl IntoResponse, ThrottledError> {
+    let attempt = LoginAttempt::new(
+        None,
+        logging_info.path,
+        logging_info.ip_address.clone(),
+        logging_info.user_agent.clone(),
+    );
+
+    let response = LoginThrottleService::protect(
         &db,
-        payload,
-        logging_info.user_agent,
-        logging_info.ip_address,
+        &attempt,
+        AdminService::complete_admin_mfa(
+            &db,
+            payload,
+            logging_info.user_agent,
+            logging_info.ip_address,
+        ),
     )
     .await?;
-    Ok((StatusCode::OK, Json(response)))
+
+    let mut http_response = (StatusCode::OK, Json(&response)).into_response();
+    SESSION_COOKIES.set_session(
+        http_response.headers_mut(),
+        &response.token,
+        &response.refresh_token,
+    );
+    Ok(http_response)
 }
 
 /// Admin logout endpoint
@@ -72,26 +161,33 @@ pub async fn admin_logout_handler(
     request: axum::extract::Request,
 ) -> Result<impl IntoResponse, AppError> {
     // Extract token from Authorization header
-    let token = crate::control::services::token_service::TokenService::extract_token_from_header(&request)?;
-    
+    let token =
+        crate::control::services::token_service::TokenService::extract_token_from_header(&request)?;
+
     // Validate token and extract claims to get session_id
-    let claims = crate::control::services::token_service::TokenService::validate_token_claims(&token)?;
-    
+    let claims =
+        crate::control::services::token_service::TokenService::validate_token_claims(&token)?;
+
     // Parse session ID
     let session_id = uuid::Uuid::parse_str(&claims.session_id).map_err(|_| AppError {
         message: "Invalid session ID in token".to_string(),
         status_code: StatusCode::UNAUTHORIZED,
     })?;
-    
+
     // Invalidate the session
-    crate::control::services::session_service::SessionService::invalidate_session(&db, session_id).await?;
-    
-    Ok((
+    crate::control::services::session_service::SessionService::invalidate_session(&db, session_id)
+        .await?;
+
+    let mut response = (
         StatusCode::OK,
         Json(MessageResponse {
             message: "Admin logged out successfully".to_string(),
         }),
-    ))
+    )
+        .into_response();
+    SESSION_COOKIES.clear_session(response.headers_mut());
+
+    Ok(response)
 }
 
 /// Get audit logs endpoint
@@ -102,22 +198,21 @@ pub async fn admin_logout_handler(
     responses(
         (status = 200, description = "Audit logs retrieved successfully", body = PaginatedResponse<AuditLogResponse>),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:logs permission", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Get audit logs",
-    description = "Retrieves paginated audit logs with optional filtering. Requires admin:read permission.",
+    description = "Retrieves paginated audit logs with optional filtering. Requires admin:logs permission.",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:logs"])
     )
 )]
 pub async fn get_audit_logs_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminLogs>,
     Query(params): Query<LogsQueryParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let response = AdminService::get_audit_logs(&db, params).await?;
     Ok((StatusCode::OK, Json(response)))
 }
@@ -130,22 +225,21 @@ pub async fn get_audit_logs_handler(
     responses(
         (status = 200, description = "Users retrieved successfully", body = PaginatedResponse<UserResponse>),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Get users",
     description = "Retrieves paginated users with optional filtering",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn get_users_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
     Query(params): Query<UsersQueryParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let response = AdminService::get_users(&db, params).await?;
     Ok((StatusCode::OK, Json(response)))
 }
@@ -160,7 +254,7 @@ pub async fn get_users_handler(
     responses(
         (status = 200, description = "User retrieved successfully", body = UserResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -168,15 +262,14 @@ pub async fn get_users_handler(
     description = "Retrieves a specific user by ID",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn get_user_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
     Path(user_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let user_id = Uuid::parse_str(&user_id).map_err(|_| AppError {
         message: "Invalid user ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
@@ -195,7 +288,7 @@ pub async fn get_user_handler(
         (status = 201, description = "User created successfully", body = UserResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 409, description = "Conflict - user already exists", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -203,19 +296,131 @@ pub async fn get_user_handler(
     description = "Creates a new user account",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn create_user_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
+    Extension(caller_permissions): Extension<PermissionSet>,
     Json(payload): Json<CreateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminWrite, &db);
-    let response = AdminService::create_user(&db, payload).await?;
+    let response = AdminService::create_user(&db, payload, &caller_permissions).await?;
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+/// Email duplicates endpoint
+#[utoipa::path(
+    get,
+    path = "/users/duplicates",
+    responses(
+        (status = 200, description = "Accounts without a normalized email address, grouped by the address they clash on", body = Vec<EmailDuplicateResponse>),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "List email duplicates",
+    description = "Lists accounts whose email addresses only differ in case or domain spelling, and accounts with addresses that don't parse. Only the first account of each group is found by its normalized address, the others sign in with their exact address until they are merged or removed",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn get_email_duplicates_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+) -> Result<impl IntoResponse, AppError> {
+    let response = AdminService::get_email_duplicates(&db).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Import users endpoint
+#[utoipa::path(
+    post,
+    path = "/users/import",
+    request_body = ImportUsersRequest,
+    responses(
+        (status = 200, description = "Import finished, entries that couldn't be imported are listed with the reason", body = ImportUsersResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Import users",
+    description = "Creates users migrated from another system with their existing Argon2 or bcrypt password hashes. Imported hashes are replaced with current Argon2id hashes when each user first signs in",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn import_users_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Extension(admin_user): Extension<AdminUser>,
+    Extension(caller_permissions): Extension<PermissionSet>,
+    Json(payload): Json<ImportUsersRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let response =
+        AdminService::import_users(&db, payload, &admin_user, &caller_permissions).await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Impersonate user endpoint
+#[utoipa::path(
+    post,
+    path = "/users/{id}/impersonate",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    request_body = ImpersonateUserRequest,
+    responses(
+        (status = 200, description = "Impersonation session started", body = ImpersonationResponse),
+        (status = 400, description = "Bad request - missing reason or impersonating yourself", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission, admins can't be impersonated", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Impersonate user",
+    description = "Starts a session as another user for support purposes. The session ends after IMPERSONATION_TTL_MINUTES, is flagged in the user's profile, can't change account settings, and everything done with it is logged with both identities",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn impersonate_user_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Extension(admin_user): Extension<AdminUser>,
+    Extension(auth_user): Extension<AuthUser>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<ImpersonateUserRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    // Only an admin's own session may start an impersonation, not an API key or another impersonation
+    if auth_user.session_id.is_none() || auth_user.impersonator_id.is_some() {
+        return Err(AppError {
+            message: "Impersonation has to be started from an admin session".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let response = AdminService::impersonate_user(
+        &db,
+        user_uuid,
+        payload,
+        &admin_user,
+        logging_info.user_agent,
+        logging_info.ip_address,
+    )
+    .await?;
+    Ok((StatusCode::OK, Json(response)))
+}
+
 /// Update user endpoint
 #[utoipa::path(
     put,
@@ -228,7 +433,7 @@ pub async fn create_user_handler(
         (status = 200, description = "User updated successfully", body = UserResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse),
         (status = 409, description = "Conflict - email already taken", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -237,22 +442,22 @@ pub async fn create_user_handler(
     description = "Updates an existing user account",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn update_user_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
     Path(user_id): Path<String>,
+    Extension(caller_permissions): Extension<PermissionSet>,
     Json(payload): Json<UpdateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminWrite, &db);
     let user_id = Uuid::parse_str(&user_id).map_err(|_| AppError {
         message: "Invalid user ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
     })?;
 
-    let response = AdminService::update_user(&db, user_id, payload).await?;
+    let response = AdminService::update_user(&db, user_id, payload, &caller_permissions).await?;
     Ok((StatusCode::OK, Json(response)))
 }
 
@@ -267,7 +472,7 @@ pub async fn update_user_handler(
         (status = 200, description = "User deleted successfully", body = MessageResponse),
         (status = 400, description = "Bad request - cannot delete own account", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -275,16 +480,15 @@ pub async fn update_user_handler(
     description = "Deletes a user account",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn delete_user_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
     Path(user_id): Path<String>,
     request: axum::extract::Request,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminDelete, &db);
     let user_id = Uuid::parse_str(&user_id).map_err(|_| AppError {
         message: "Invalid user ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
@@ -312,21 +516,20 @@ pub async fn delete_user_handler(
     responses(
         (status = 200, description = "Database tables retrieved successfully", body = Vec<DatabaseTableResponse>),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:database permission", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Get database tables",
     description = "Retrieves a list of all database tables with record counts",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:database"])
     )
 )]
 pub async fn get_database_tables_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminDatabase>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let response = AdminService::get_database_tables(&db).await?;
     Ok((StatusCode::OK, Json(response)))
 }
@@ -342,23 +545,23 @@ pub async fn get_database_tables_handler(
     responses(
         (status = 200, description = "Table records retrieved successfully", body = TableRecordResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:database permission", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded, see the Retry-After and RateLimit-* headers", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Get table records",
     description = "Retrieves paginated records from a specific database table",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:database"])
     )
 )]
 pub async fn get_table_records_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminDatabase>,
     Path(table_name): Path<String>,
     Query(params): Query<TableRecordsQueryParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let response = AdminService::get_table_records(&db, table_name, params).await?;
     Ok((StatusCode::OK, Json(response)))
 }
@@ -370,20 +573,20 @@ pub async fn get_table_records_handler(
     responses(
         (status = 200, description = "System health check successful", body = HealthResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse)
+        (status = 403, description = "Forbidden - requires the admin:health permission", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded, see the Retry-After and RateLimit-* headers", body = ErrorResponse)
     ),
     summary = "System health check",
     description = "Returns system health status",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:health"])
     )
 )]
 pub async fn health_handler(
     State(db): State<DatabaseConnection>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminHealth>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
     let response = AdminService::get_health_status(&db).await;
     Ok((StatusCode::OK, Json(response)))
 }
@@ -398,7 +601,7 @@ pub async fn health_handler(
     responses(
         (status = 200, description = "User sessions retrieved successfully", body = Vec<SessionResponse>),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -406,22 +609,21 @@ pub async fn health_handler(
     description = "Retrieves all active sessions for a specific user",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn get_user_sessions_handler(
     State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
     Path(user_id): Path<String>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminRead, &db);
-
     let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
         message: "Invalid user ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
     })?;
 
-    let sessions = AdminService::get_user_sessions(&db, user_uuid).await?;
+    let sessions = AdminService::get_user_sessions(&db, user_uuid, auth_user.session_id).await?;
     Ok((StatusCode::OK, Json(sessions)))
 }
 
@@ -435,7 +637,7 @@ pub async fn get_user_sessions_handler(
     responses(
         (status = 200, description = "Session invalidated successfully", body = SessionInvalidationResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "Session not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -443,16 +645,14 @@ pub async fn get_user_sessions_handler(
     description = "Invalidates a specific user session (remote logout)",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn invalidate_session_handler(
     State(db): State<DatabaseConnection>,
     Path(session_id): Path<String>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminDelete, &db);
-
     let session_uuid = Uuid::parse_str(&session_id).map_err(|_| AppError {
         message: "Invalid session ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
@@ -478,7 +678,7 @@ pub async fn invalidate_session_handler(
     responses(
         (status = 200, description = "All user sessions invalidated successfully", body = SessionInvalidationResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -486,16 +686,14 @@ pub async fn invalidate_session_handler(
     description = "Invalidates all sessions for a specific user (force logout from all devices)",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:users"])
     )
 )]
 pub async fn invalidate_all_user_sessions_handler(
     State(db): State<DatabaseConnection>,
     Path(user_id): Path<String>,
-    Extension(admin_user): Extension<AdminUser>,
+    _: RequirePermission<AdminUsers>,
 ) -> Result<impl IntoResponse, AppError> {
-    check_single_permission!(&admin_user.email, &AdminDelete, &db);
-
     let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
         message: "Invalid user ID format".to_string(),
         status_code: StatusCode::BAD_REQUEST,
@@ -510,3 +708,318 @@ pub async fn invalidate_all_user_sessions_handler(
 
     Ok((StatusCode::OK, Json(response)))
 }
+
+/// Unlock user login endpoint
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/unlock",
+    params(
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Login lockout cleared", body = MessageResponse),
+        (status = 400, description = "Invalid user ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Unlock user login",
+    description = "Clears failed login attempts and any lockout on a user's account. Lockouts of client IPs expire on their own.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn unlock_user_login_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path(user_id): Path<String>,
+    Extension(admin_user): Extension<AdminUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let unlocked = AdminService::unlock_user_login(&db, user_uuid, &admin_user).await?;
+
+    let message = if unlocked {
+        "Login lockout cleared"
+    } else {
+        "User has no failed login attempts"
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(MessageResponse {
+            message: message.to_string(),
+        }),
+    ))
+}
+
+/// Get user roles endpoint
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}/roles",
+    params(
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Roles assigned to the user, primary role first", body = Vec<RoleResponse>),
+        (status = 400, description = "Invalid user ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Get user roles",
+    description = "Lists every role assigned to a user. The first one is the primary role stored on the user.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn get_user_roles_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let roles = AdminService::get_user_roles(&db, user_uuid).await?;
+    Ok((StatusCode::OK, Json(roles)))
+}
+
+/// Assign user role endpoint
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/roles/{role_id}",
+    params(
+        ("user_id" = String, Path, description = "User ID"),
+        ("role_id" = i32, Path, description = "Role ID")
+    ),
+    responses(
+        (status = 200, description = "User already has the role", body = MessageResponse),
+        (status = 201, description = "Role assigned", body = MessageResponse),
+        (status = 400, description = "Invalid user ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission and every permission of the role", body = ErrorResponse),
+        (status = 404, description = "User or role not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Assign user role",
+    description = "Gives a user an additional role. Permissions of all assigned roles are merged. A user without a primary role gets this one as primary.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn assign_user_role_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path((user_id, role_id)): Path<(String, i32)>,
+    Extension(admin_user): Extension<AdminUser>,
+    Extension(caller_permissions): Extension<PermissionSet>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let assigned =
+        AdminService::assign_user_role(&db, user_uuid, role_id, &admin_user, &caller_permissions)
+            .await?;
+
+    let (status, message) = if assigned {
+        (StatusCode::CREATED, "Role assigned")
+    } else {
+        (StatusCode::OK, "User already has this role")
+    };
+
+    Ok((
+        status,
+        Json(MessageResponse {
+            message: message.to_string(),
+        }),
+    ))
+}
+
+/// Revoke user role endpoint
+#[utoipa::path(
+    delete,
+    path = "/users/{user_id}/roles/{role_id}",
+    params(
+        ("user_id" = String, Path, description = "User ID"),
+        ("role_id" = i32, Path, description = "Role ID")
+    ),
+    responses(
+        (status = 200, description = "Role revoked", body = MessageResponse),
+        (status = 400, description = "Invalid user ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 404, description = "User not found or role not assigned", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Revoke user role",
+    description = "Takes a role away from a user. Revoking the primary role promotes the user's next role, if any.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn revoke_user_role_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path((user_id, role_id)): Path<(String, i32)>,
+    Extension(admin_user): Extension<AdminUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    AdminService::revoke_user_role(&db, user_uuid, role_id, &admin_user).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(MessageResponse {
+            message: "Role revoked".to_string(),
+        }),
+    ))
+}
+
+/// Get user permission grants endpoint
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}/permissions",
+    params(
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Permissions granted to or denied from the user directly", body = Vec<UserPermissionResponse>),
+        (status = 400, description = "Invalid user ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Get user permission grants",
+    description = "Lists the permissions granted to or denied from a user on top of their roles. Expired records are listed until the purge job removes them but no longer apply.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn get_user_permissions_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let records = AdminService::get_user_permission_overrides(&db, user_uuid).await?;
+    Ok((StatusCode::OK, Json(records)))
+}
+
+/// Set user permission grant endpoint
+#[utoipa::path(
+    post,
+    path = "/users/{user_id}/permissions",
+    params(
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    request_body = CreateUserPermissionRequest,
+    responses(
+        (status = 201, description = "Permission granted or denied", body = UserPermissionResponse),
+        (status = 400, description = "Invalid user ID, permission or expiry", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission and the permission itself", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Grant or deny a user permission",
+    description = "Grants or denies one permission for a single user, optionally until `expires_at`. A deny takes precedence over roles and grants. Replaces an earlier record for the same permission.",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn set_user_permission_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path(user_id): Path<String>,
+    Extension(admin_user): Extension<AdminUser>,
+    Extension(caller_permissions): Extension<PermissionSet>,
+    Json(payload): Json<CreateUserPermissionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|_| AppError {
+        message: "Invalid user ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let record = AdminService::set_user_permission_override(
+        &db,
+        user_uuid,
+        payload,
+        &admin_user,
+        &caller_permissions,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+/// Remove user permission grant endpoint
+#[utoipa::path(
+    delete,
+    path = "/users/{user_id}/permissions/{grant_id}",
+    params(
+        ("user_id" = String, Path, description = "User ID"),
+        ("grant_id" = String, Path, description = "Permission grant or deny ID")
+    ),
+    responses(
+        (status = 200, description = "Permission grant removed", body = MessageResponse),
+        (status = 400, description = "Invalid ID format", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:users permission", body = ErrorResponse),
+        (status = 404, description = "Permission grant not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Remove user permission grant",
+    description = "Removes a grant or deny so the user's permissions come from their roles again",
+    tag = ADMIN_TAG,
+    security(
+        ("jwt_token" = ["admin:users"])
+    )
+)]
+pub async fn remove_user_permission_handler(
+    State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminUsers>,
+    Path((user_id, grant_id)): Path<(String, String)>,
+    Extension(admin_user): Extension<AdminUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let (user_uuid, grant_uuid) = match (Uuid::parse_str(&user_id), Uuid::parse_str(&grant_id)) {
+        (Ok(user_uuid), Ok(grant_uuid)) => (user_uuid, grant_uuid),
+        _ => {
+            return Err(AppError {
+                message: "Invalid ID format".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+    };
+
+    AdminService::remove_user_permission_override(&db, user_uuid, grant_uuid, &admin_user).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(MessageResponse {
+            message: "Permission grant removed".to_string(),
+        }),
+    ))
+}
@@ -1,21 +1,39 @@
 use axum::{
     Extension, Json,
-    extract::{Request, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
 };
 use sea_orm::DatabaseConnection;
 
 use crate::bridge::types::{
+    admin::{SessionInvalidationResponse, SessionResponse},
     auth::{
-        AUTH_TAG, AuthUser, LoginRequest, LoginResponse, ProfileResponse, RegisterRequest,
-        RegisterResponse,
+        AUTH_TAG, ApiKeyAuth, ApiKeyResponse, AuthUser, ChangeEmailRequest, ChangePasswordRequest,
+        ConfirmEmailChangeRequest, CreateApiKeyRequest, CreateApiKeyResponse, DeleteAccountRequest,
+        DeleteAccountResponse, ForgotPasswordRequest, ImpersonationInfo, JwksResponse,
+        LoginRequest, LoginResponse, MfaChallengeResponse, MfaCodeRequest,
+        MfaRecoveryCodesResponse, MfaSetupResponse, MfaStatusResponse, MfaVerifyRequest,
+        OidcAuthorizeResponse, OidcCallbackRequest, OidcProviderResponse, ProfileResponse,
+        RefreshRequest, RegisterRequest, RegisterResponse, ResetPasswordRequest,
+        VerifyEmailRequest,
     },
     logging::LoggingInfo,
 };
-use crate::control::services::{auth_service::AuthService, session_service::SessionService, token_service::TokenService, user_service::UserService};
-use crate::domain::user::*;
-use crate::infrastructure::app_error::{AppError, ErrorResponse, MessageResponse};
+use crate::control::services::{
+    account_service::AccountService, api_key_service::ApiKeyService, auth_service::AuthService,
+    login_throttle_service::LoginThrottleService, mfa_service::MfaService,
+    oidc_service::OidcService, session_service::SessionService, token_service::TokenService,
+    user_service::UserService, verification_service::VerificationService,
+};
+use crate::domain::{
+    auth::{LoginAttempt, LoginResult, MfaScope},
+    user::*,
+};
+use crate::infrastructure::app_error::{AppError, ErrorResponse, MessageResponse, ThrottledError};
+use crate::infrastructure::jwt_keys::JwtKeyManager;
+use crate::infrastructure::oidc::OidcManager;
+use crate::infrastructure::session_cookies::{REFRESH_COOKIE, SESSION_COOKIES};
 
 /// Registers a new user
 #[utoipa::path(
@@ -30,13 +48,14 @@ use crate::infrastructure::app_error::{AppError, ErrorResponse, MessageResponse}
         (status = 409, description = "Conflict - user already exists", body = ErrorResponse, examples(
             ("user_exists" = (value = json!({"message": "User already exists"})))
         )),
+        (status = 429, description = "Rate limit exceeded, see the Retry-After and RateLimit-* headers", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse, examples(
             ("hash_error" = (value = json!({"message": "Failed to hash password"}))),
             ("database_error" = (value = json!({"message": "Failed to create user"})))
         ))
     ),
     summary = "Register a new user",
-    description = "Creates a new user account with email and password. Password is securely hashed using Argon2.",
+    description = "Creates a new user account with email and password. Password is securely hashed using Argon2. A verification link is mailed to the address.",
     tag = AUTH_TAG
 )]
 pub async fn register_handler(
@@ -49,6 +68,14 @@ pub async fn register_handler(
     // Delegate to user service, errors bubble up correctl
     let user = UserService::create_user(&db, registration).await?;
 
+    // The account exists at this point, a failed mail shouldn't fail the registration
+    if let Err(e) = VerificationService::send_verification_email(&db, &user).await {
+        eprintln!(
+            "Failed to queue verification email for {}: {}",
+            user.id, e.message
+        );
+    }
+
     Ok((
         StatusCode::CREATED,
         Json(RegisterResponse {
@@ -67,40 +94,258 @@ pub async fn register_handler(
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 202, description = "Password accepted, MFA code required", body = MfaChallengeResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse, examples(
             ("empty_fields" = (value = json!({"message": "Email and password are required"}))),
         )),
         (status = 401, description = "Unauthorized - invalid credentials", body = ErrorResponse, examples(
             ("invalid_credentials" = (value = json!({"message": "Invalid credentials"})))
         )),
+        (status = 403, description = "Forbidden - email not verified (when REQUIRE_EMAIL_VERIFICATION is enabled), or password older than PASSWORD_MAX_AGE_DAYS", body = ErrorResponse, examples(
+            ("not_verified" = (value = json!({"message": "Email address not verified"}))),
+            ("password_expired" = (value = json!({"message": "Your password has expired, a link to choose a new one was sent to your email"})))
+        )),
+        (status = 429, description = "Too many failed attempts for this account or client, see the Retry-After header", body = ErrorResponse, examples(
+            ("throttled" = (value = json!({"message": "Too many failed login attempts, try again later"})))
+        )),
         (status = 500, description = "Internal server error", body = ErrorResponse, examples(
             ("hash_error" = (value = json!({"message": "Invalid password hash"}))),
             ("token_error" = (value = json!({"message": "Failed to generate token"})))
         ))
     ),
     summary = "Login user",
-    description = "Authenticates a user with email and password, returns a JWT token on success.",
+    description = "Authenticates a user with email and password, returns a short-lived JWT access token and a refresh token on success. With SESSION_COOKIES enabled the tokens are also set as HttpOnly cookies, along with a CSRF cookie whose value state-changing requests must repeat in the X-CSRF-Token header. Users with MFA enabled, or whose role requires it, get an MFA challenge to complete at /mfa/verify instead. Repeated failures slow down and eventually lock the account and the client IP.",
     tag = AUTH_TAG
 )]
 pub async fn login_handler(
     State(db): State<DatabaseConnection>,
     Extension(logging_info): Extension<LoggingInfo>,
     Json(payload): Json<LoginRequest>,
-) -> Result<impl IntoResponse, AppError> {
+) -> Result<Response, ThrottledError> {
+    let attempt = LoginAttempt::new(
+        Some(&payload.email),
+        logging_info.path,
+        logging_info.ip_address.clone(),
+        logging_info.user_agent.clone(),
+    );
+
     // Convert request to user domain model
     let login = UserLogin::new(payload.email, payload.password);
 
-    // Delegate to user service with session tracking
-    let auth_token = AuthService::authenticate_user(
+    // Delegate to user service with session tracking, throttled per account and IP
+    let result = LoginThrottleService::protect(
         &db,
-        login,
-        logging_info.user_agent,
-        logging_info.ip_address,
+        &attempt,
+        AuthService::authenticate_user(
+            &db,
+            login,
+            logging_info.user_agent,
+            logging_info.ip_address,
+        ),
+    )
+    .await?;
+
+    Ok(match result {
+        LoginResult::Authenticated(auth_token) => session_response(LoginResponse {
+            expires_in: auth_token.expires_in(),
+            token: auth_token.token,
+            refresh_token: auth_token.refresh_token,
+            recovery_codes: None,
+        }),
+        LoginResult::MfaRequired(challenge) => (
+            StatusCode::ACCEPTED,
+            Json(MfaChallengeResponse::from(challenge)),
+        )
+            .into_response(),
+    })
+}
+
+/// Completes a login that requires MFA
+#[utoipa::path(
+    post,
+    path = "/mfa/verify",
+    request_body = MfaVerifyRequest,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 400, description = "Bad request - validation errors", body = ErrorResponse, examples(
+            ("empty_fields" = (value = json!({"message": "MFA token and code are required"}))),
+        )),
+        (status = 401, description = "Unauthorized - invalid challenge or code", body = ErrorResponse, examples(
+            ("invalid_challenge" = (value = json!({"message": "Invalid or expired MFA challenge"}))),
+            ("invalid_code" = (value = json!({"message": "Invalid MFA code"})))
+        )),
+        (status = 429, description = "Too many failed attempts from this client, see the Retry-After header", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Complete MFA login",
+    description = "Exchanges the challenge token from /login and a TOTP or recovery code for a session. When the challenge included a setup secret, this also enables MFA and returns the recovery codes.",
+    tag = AUTH_TAG
+)]
+pub async fn mfa_verify_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    Json(payload): Json<MfaVerifyRequest>,
+) -> Result<impl IntoResponse, ThrottledError> {
+    // The challenge token names no account, so codes are throttled per IP
+    let attempt = LoginAttempt::new(
+        None,
+        logging_info.path,
+        logging_info.ip_address.clone(),
+        logging_info.user_agent.clone(),
+    );
+
+    let mfa_login = LoginThrottleService::protect(
+        &db,
+        &attempt,
+        AuthService::complete_mfa_login(
+            &db,
+            &payload.mfa_token,
+            &payload.code,
+            MfaScope::User,
+            logging_info.user_agent,
+            logging_info.ip_address,
+        ),
     )
     .await?;
 
-    Ok(Json(LoginResponse {
+    Ok(session_response(LoginResponse {
+        expires_in: mfa_login.auth_token.expires_in(),
+        token: mfa_login.auth_token.token,
+        refresh_token: mfa_login.auth_token.refresh_token,
+        recovery_codes: mfa_login.recovery_codes,
+    }))
+}
+
+/// Exchanges a refresh token for a new token pair
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Tokens refreshed successfully", body = LoginResponse),
+        (status = 400, description = "Bad request - validation errors", body = ErrorResponse, examples(
+            ("empty_token" = (value = json!({"message": "Refresh token is required"}))),
+        )),
+        (status = 401, description = "Unauthorized - invalid, expired or reused refresh token", body = ErrorResponse, examples(
+            ("invalid_token" = (value = json!({"message": "Invalid refresh token"}))),
+            ("token_reuse" = (value = json!({"message": "Refresh token reuse detected, session revoked"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Refresh access token",
+    description = "Rotates the refresh token and returns a new access token. Each refresh token can only be used once; presenting an already used token revokes the whole session. With session cookies enabled the refresh token can come from the refresh cookie, the request then needs the X-CSRF-Token header.",
+    tag = AUTH_TAG
+)]
+pub async fn refresh_handler(
+    State(db): State<DatabaseConnection>,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    // Cookie sessions keep the refresh token out of reach of scripts, so the body may omit it
+    let refresh_token = if payload.refresh_token.is_empty() {
+        SESSION_COOKIES
+            .read(&headers, REFRESH_COOKIE)
+            .unwrap_or_default()
+    } else {
+        payload.refresh_token
+    };
+
+    let auth_token = AuthService::refresh_session(&db, &refresh_token).await?;
+
+    Ok(session_response(LoginResponse {
+        expires_in: auth_token.expires_in(),
         token: auth_token.token,
+        refresh_token: auth_token.refresh_token,
+        recovery_codes: None,
+    }))
+}
+
+/// Confirms a user's email address
+#[utoipa::path(
+    post,
+    path = "/verify-email",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email verified successfully", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "Email verified successfully"})))
+        )),
+        (status = 400, description = "Bad request - invalid, expired or already used token", body = ErrorResponse, examples(
+            ("invalid_token" = (value = json!({"message": "Invalid or expired token"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Verify email address",
+    description = "Redeems the single-use token from the verification email sent on registration.",
+    tag = AUTH_TAG
+)]
+pub async fn verify_email_handler(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    VerificationService::verify_email(&db, &payload.token).await?;
+
+    Ok(Json(MessageResponse {
+        message: "Email verified successfully".to_string(),
+    }))
+}
+
+/// Starts a password reset
+#[utoipa::path(
+    post,
+    path = "/forgot-password",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Reset email queued if the account exists", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "If an account exists for this email, a password reset link has been sent"})))
+        )),
+        (status = 400, description = "Bad request - validation errors", body = ErrorResponse, examples(
+            ("invalid_email" = (value = json!({"message": "Invalid email format"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Request password reset",
+    description = "Mails a single-use password reset link. The response is the same whether or not the account exists.",
+    tag = AUTH_TAG
+)]
+pub async fn forgot_password_handler(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    VerificationService::request_password_reset(&db, &payload.email).await?;
+
+    Ok(Json(MessageResponse {
+        message: "If an account exists for this email, a password reset link has been sent"
+            .to_string(),
+    }))
+}
+
+/// Completes a password reset
+#[utoipa::path(
+    post,
+    path = "/reset-password",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset successfully", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "Password reset successfully"})))
+        )),
+        (status = 400, description = "Bad request - invalid token or password", body = ErrorResponse, examples(
+            ("invalid_token" = (value = json!({"message": "Invalid or expired token"}))),
+            ("weak_password" = (value = json!({"message": "Password must be at least 8 characters"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Reset password",
+    description = "Redeems the token from the password reset email and sets a new password. All existing sessions of the user are signed out.",
+    tag = AUTH_TAG
+)]
+pub async fn reset_password_handler(
+    State(db): State<DatabaseConnection>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    VerificationService::reset_password(&db, &payload.token, &payload.password).await?;
+
+    Ok(Json(MessageResponse {
+        message: "Password reset successfully".to_string(),
     }))
 }
 
@@ -128,22 +373,26 @@ pub async fn logout_handler(
 ) -> Result<impl IntoResponse, AppError> {
     // Extract token from Authorization header
     let token = TokenService::extract_token_from_header(&request)?;
-    
+
     // Validate token and extract claims to get session_id
     let claims = TokenService::validate_token_claims(&token)?;
-    
+
     // Parse session ID
     let session_id = uuid::Uuid::parse_str(&claims.session_id).map_err(|_| AppError {
         message: "Invalid session ID in token".to_string(),
         status_code: StatusCode::UNAUTHORIZED,
     })?;
-    
+
     // Invalidate the session
     SessionService::invalidate_session(&db, session_id).await?;
-    
-    Ok(Json(MessageResponse {
+
+    let mut response = Json(MessageResponse {
         message: "Logged out successfully".to_string(),
-    }))
+    })
+    .into_response();
+    SESSION_COOKIES.clear_session(response.headers_mut());
+
+    Ok(response)
 }
 
 /// Gets the current user's profile information
@@ -185,9 +434,803 @@ pub async fn profile_handler(
             status_code: StatusCode::NOT_FOUND,
         })?;
 
+    let impersonated_by = match (auth_user.impersonator_id, auth_user.session_id) {
+        (Some(admin_id), Some(session_id)) => {
+            let admin = UserService::find_user_by_id(&db, admin_id).await?;
+            let session = SessionService::find_session(&db, &session_id.to_string()).await?;
+            session.map(|session| ImpersonationInfo {
+                admin_id: admin_id.to_string(),
+                admin_email: admin.map(|admin| admin.email).unwrap_or_default(),
+                expires_at: session.expires_at.to_utc(),
+            })
+        }
+        _ => None,
+    };
+
     Ok(Json(ProfileResponse {
         id: user.id.to_string(),
         email: user.email,
         created_at: user.created_at,
+        pending_email: AccountService::pending_email(&db, user.id).await?,
+        impersonated_by,
+    }))
+}
+
+/// Starts changing the current user's email address
+#[utoipa::path(
+    post,
+    path = "/profile/email",
+    request_body = ChangeEmailRequest,
+    responses(
+        (status = 200, description = "Confirmation link mailed to the new address", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "Open the link sent to your new email address to confirm the change"})))
+        )),
+        (status = 400, description = "Bad request - invalid email address", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - wrong current password, or called with an API key", body = ErrorResponse, examples(
+            ("wrong_password" = (value = json!({"message": "Current password is incorrect"})))
+        )),
+        (status = 409, description = "Conflict - email already taken", body = ErrorResponse),
+        (status = 429, description = "Too many wrong current passwords, see the Retry-After header", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Change email address",
+    description = "Mails a confirmation link to the new address and a notice to the current one. The email address only changes once the link is opened at /confirm-email-change, until then it's shown as `pending_email` in the profile.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn change_email_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, ThrottledError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+
+    AccountService::request_email_change(
+        &db,
+        auth_user.user_id,
+        &payload.current_password,
+        &payload.new_email,
+        logging_info.ip_address,
+        logging_info.user_agent,
+    )
+    .await?;
+
+    Ok(Json(MessageResponse {
+        message: "Open the link sent to your new email address to confirm the change".to_string(),
+    }))
+}
+
+/// Completes an email change
+#[utoipa::path(
+    post,
+    path = "/confirm-email-change",
+    request_body = ConfirmEmailChangeRequest,
+    responses(
+        (status = 200, description = "Email address changed", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "Email address changed successfully"})))
+        )),
+        (status = 400, description = "Bad request - invalid, expired or already used token", body = ErrorResponse, examples(
+            ("invalid_token" = (value = json!({"message": "Invalid or expired token"})))
+        )),
+        (status = 409, description = "Conflict - the address was taken in the meantime", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Confirm email change",
+    description = "Redeems the token mailed to the new address and makes it the account's verified email address.",
+    tag = AUTH_TAG
+)]
+pub async fn confirm_email_change_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    Json(payload): Json<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    AccountService::confirm_email_change(
+        &db,
+        &payload.token,
+        logging_info.ip_address,
+        logging_info.user_agent,
+    )
+    .await?;
+
+    Ok(Json(MessageResponse {
+        message: "Email address changed successfully".to_string(),
+    }))
+}
+
+/// Changes the current user's password
+#[utoipa::path(
+    post,
+    path = "/profile/password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed, other sessions signed out", body = SessionInvalidationResponse),
+        (status = 400, description = "Bad request - weak or unchanged password", body = ErrorResponse, examples(
+            ("weak_password" = (value = json!({"message": "Password must be at least 8 characters"})))
+        )),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - wrong current password, or called with an API key", body = ErrorResponse, examples(
+            ("wrong_password" = (value = json!({"message": "Current password is incorrect"})))
+        )),
+        (status = 429, description = "Too many wrong current passwords, see the Retry-After header", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Change password",
+    description = "Sets a new password after checking the current one. Every other session of the user is signed out, the session making the request stays signed in.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn change_password_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, ThrottledError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+
+    let count = AccountService::change_password(
+        &db,
+        auth_user.user_id,
+        auth_user.session_id,
+        &payload.current_password,
+        &payload.new_password,
+        logging_info.ip_address,
+        logging_info.user_agent,
+    )
+    .await?;
+
+    Ok(Json(SessionInvalidationResponse {
+        message: "Password changed successfully".to_string(),
+        invalidated_count: Some(count),
+    }))
+}
+
+/// Deletes the current user's account
+#[utoipa::path(
+    delete,
+    path = "/profile",
+    request_body = DeleteAccountRequest,
+    responses(
+        (status = 200, description = "Account scheduled for deletion", body = DeleteAccountResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - wrong current password, or called with an API key", body = ErrorResponse, examples(
+            ("wrong_password" = (value = json!({"message": "Current password is incorrect"})))
+        )),
+        (status = 429, description = "Too many wrong current passwords, see the Retry-After header", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Delete account",
+    description = "Schedules the account for deletion after ACCOUNT_DELETION_GRACE_DAYS (30 by default). Every session is signed out and every API key revoked right away. Signing in again before the deletion date keeps the account.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn delete_account_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> Result<impl IntoResponse, ThrottledError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+
+    let deletion_scheduled_at = AccountService::schedule_deletion(
+        &db,
+        auth_user.user_id,
+        &payload.current_password,
+        logging_info.ip_address,
+        logging_info.user_agent,
+    )
+    .await?;
+
+    let mut response = Json(DeleteAccountResponse {
+        message: "Account scheduled for deletion".to_string(),
+        deletion_scheduled_at,
+    })
+    .into_response();
+    SESSION_COOKIES.clear_session(response.headers_mut());
+
+    Ok(response)
+}
+
+/// Publishes the public keys used to sign access tokens
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "JSON Web Key Set", body = JwksResponse)
+    ),
+    summary = "Get JSON Web Key Set",
+    description = "Returns the public keys tokens may be signed with, including retired keys that are still accepted. Empty when tokens are signed with a shared secret.",
+    tag = AUTH_TAG,
+    security(())
+)]
+pub async fn jwks_handler() -> impl IntoResponse {
+    Json(JwksResponse {
+        keys: JwtKeyManager::public_keys(),
+    })
+}
+
+/// Gets the current user's MFA status
+#[utoipa::path(
+    get,
+    path = "/mfa",
+    responses(
+        (status = 200, description = "MFA status retrieved successfully", body = MfaStatusResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Get MFA status",
+    description = "Returns whether MFA is enabled for the account and whether the user's role requires it.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn mfa_status_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = find_current_user(&db, &auth_user).await?;
+
+    Ok(Json(MfaStatusResponse {
+        enabled: MfaService::is_enabled(&db, user.id).await?,
+        required: MfaService::roles_require_mfa(&db, user.id).await?,
+    }))
+}
+
+/// Starts MFA enrollment
+#[utoipa::path(
+    post,
+    path = "/mfa/setup",
+    responses(
+        (status = 200, description = "MFA secret generated", body = MfaSetupResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 409, description = "Conflict - MFA already enabled", body = ErrorResponse, examples(
+            ("already_enabled" = (value = json!({"message": "MFA is already enabled"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Set up MFA",
+    description = "Generates a TOTP secret and provisioning URI for an authenticator app. MFA is enabled once a code is confirmed at /mfa/enable.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn mfa_setup_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let user = find_current_user(&db, &auth_user).await?;
+    let setup = MfaService::begin_enrollment(&db, &user).await?;
+
+    Ok(Json(MfaSetupResponse::from(setup)))
+}
+
+/// Enables MFA
+#[utoipa::path(
+    post,
+    path = "/mfa/enable",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "MFA enabled, recovery codes returned once", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "Bad request - enrollment not started", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - invalid code", body = ErrorResponse, examples(
+            ("invalid_code" = (value = json!({"message": "Invalid MFA code"})))
+        )),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 409, description = "Conflict - MFA already enabled", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Enable MFA",
+    description = "Confirms the secret from /mfa/setup with a TOTP code and enables MFA. Returns one-time recovery codes.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn mfa_enable_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let recovery_codes =
+        MfaService::confirm_enrollment(&db, auth_user.user_id, &payload.code).await?;
+
+    Ok(Json(MfaRecoveryCodesResponse { recovery_codes }))
+}
+
+/// Disables MFA
+#[utoipa::path(
+    post,
+    path = "/mfa/disable",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "MFA disabled", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "MFA disabled successfully"})))
+        )),
+        (status = 401, description = "Unauthorized - invalid code", body = ErrorResponse),
+        (status = 403, description = "Forbidden - MFA required for role, or called with an API key", body = ErrorResponse, examples(
+            ("required" = (value = json!({"message": "MFA is required for your role"})))
+        )),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Disable MFA",
+    description = "Turns off MFA after checking a TOTP or recovery code. Not allowed when the user's role requires MFA.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn mfa_disable_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let user = find_current_user(&db, &auth_user).await?;
+    MfaService::disable(&db, &user, &payload.code).await?;
+
+    Ok(Json(MessageResponse {
+        message: "MFA disabled successfully".to_string(),
     }))
 }
+
+/// Regenerates MFA recovery codes
+#[utoipa::path(
+    post,
+    path = "/mfa/recovery-codes",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "New recovery codes, the old ones stop working", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "Bad request - MFA not enabled", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - invalid code", body = ErrorResponse),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Regenerate recovery codes",
+    description = "Replaces all recovery codes after checking a TOTP or recovery code.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn mfa_recovery_codes_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<MfaCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let recovery_codes =
+        MfaService::regenerate_recovery_codes(&db, auth_user.user_id, &payload.code).await?;
+
+    Ok(Json(MfaRecoveryCodesResponse { recovery_codes }))
+}
+
+/// Lists the current user's API keys
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    responses(
+        (status = 200, description = "Active API keys, newest first", body = Vec<ApiKeyResponse>),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "List API keys",
+    description = "Lists the user's API keys that haven't been revoked. Only the key prefix is returned, never the key itself.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn list_api_keys_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let api_keys = ApiKeyService::list(&db, auth_user.user_id).await?;
+
+    Ok(Json(
+        api_keys
+            .into_iter()
+            .map(ApiKeyResponse::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Creates an API key
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created, the key is only returned this once", body = CreateApiKeyResponse),
+        (status = 400, description = "Bad request - invalid name, scopes or expiry", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - API keys can't create other API keys", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Create API key",
+    description = "Creates a personal API key for scripts and CI. Send it as `X-Api-Key: <key>` or `Authorization: ApiKey <key>`. Requests made with it get the user's permissions limited to the key's scopes.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn create_api_key_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    // A leaked key must not be able to mint longer lived or wider keys
+    if api_key_auth.is_some() {
+        return Err(AppError {
+            message: "API keys can't create other API keys".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+
+    let (api_key, key) = ApiKeyService::create(
+        &db,
+        auth_user.user_id,
+        &payload.name,
+        &payload.scopes,
+        payload.expires_at,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse {
+            key,
+            api_key: ApiKeyResponse::from(api_key),
+        }),
+    ))
+}
+
+/// Revokes an API key
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{key_id}",
+    params(
+        ("key_id" = String, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key revoked", body = MessageResponse, examples(
+            ("success" = (value = json!({"message": "API key revoked"})))
+        )),
+        (status = 400, description = "Bad request - invalid key ID", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 404, description = "Not found - no such active key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Revoke API key",
+    description = "Revokes one of the user's API keys, it stops working immediately.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn revoke_api_key_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Path(key_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let key_id = uuid::Uuid::parse_str(&key_id).map_err(|_| AppError {
+        message: "Invalid API key ID".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    ApiKeyService::revoke(&db, auth_user.user_id, key_id).await?;
+
+    Ok(Json(MessageResponse {
+        message: "API key revoked".to_string(),
+    }))
+}
+
+/// Lists the configured identity providers
+#[utoipa::path(
+    get,
+    path = "/oidc/providers",
+    responses(
+        (status = 200, description = "Identity providers users can sign in with", body = Vec<OidcProviderResponse>)
+    ),
+    summary = "List identity providers",
+    description = "Lists the external OpenID Connect providers configured in rext.toml or the OIDC_* environment variables.",
+    tag = AUTH_TAG
+)]
+pub async fn oidc_providers_handler() -> impl IntoResponse {
+    Json(
+        OidcManager::providers()
+            .iter()
+            .map(|provider| OidcProviderResponse {
+                name: provider.name.clone(),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Starts a login through an identity provider
+#[utoipa::path(
+    get,
+    path = "/oidc/{provider}/authorize",
+    params(
+        ("provider" = String, Path, description = "Identity provider name")
+    ),
+    responses(
+        (status = 200, description = "Authorization URL to redirect the browser to", body = OidcAuthorizeResponse),
+        (status = 404, description = "Not found - unknown provider", body = ErrorResponse, examples(
+            ("unknown_provider" = (value = json!({"message": "Unknown identity provider"})))
+        )),
+        (status = 429, description = "Rate limit exceeded, see the Retry-After and RateLimit-* headers", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 502, description = "Bad gateway - the provider's discovery document couldn't be loaded", body = ErrorResponse, examples(
+            ("provider_unavailable" = (value = json!({"message": "Identity provider unavailable"})))
+        ))
+    ),
+    summary = "Start identity provider login",
+    description = "Starts an authorization code flow with PKCE. Redirect the browser to the returned URL, the provider sends it back to the configured redirect URI with a code and the state. The state is valid for 10 minutes.",
+    tag = AUTH_TAG
+)]
+pub async fn oidc_authorize_handler(
+    State(db): State<DatabaseConnection>,
+    Path(provider): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let (authorization_url, state) = OidcService::begin_login(&db, &provider).await?;
+
+    Ok(Json(OidcAuthorizeResponse {
+        authorization_url,
+        state,
+    }))
+}
+
+/// Completes a login through an identity provider
+#[utoipa::path(
+    post,
+    path = "/oidc/{provider}/callback",
+    params(
+        ("provider" = String, Path, description = "Identity provider name")
+    ),
+    request_body = OidcCallbackRequest,
+    responses(
+        (status = 200, description = "Login successful", body = LoginResponse),
+        (status = 202, description = "Provider login accepted, MFA code required", body = MfaChallengeResponse),
+        (status = 400, description = "Bad request - missing fields or unknown state", body = ErrorResponse, examples(
+            ("invalid_state" = (value = json!({"message": "Invalid or expired login state"})))
+        )),
+        (status = 401, description = "Unauthorized - the provider rejected the code or sent an invalid ID token", body = ErrorResponse, examples(
+            ("rejected" = (value = json!({"message": "Identity provider rejected the login"}))),
+            ("invalid_token" = (value = json!({"message": "Invalid ID token"})))
+        )),
+        (status = 404, description = "Not found - unknown provider", body = ErrorResponse),
+        (status = 409, description = "Conflict - an account with an unverified matching email exists", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded, see the Retry-After and RateLimit-* headers", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 502, description = "Bad gateway - the provider couldn't be reached", body = ErrorResponse)
+    ),
+    summary = "Complete identity provider login",
+    description = "Exchanges the code from the provider's redirect for a session, like /login. The first login links the provider account to the user with the same verified email, or creates a new user. Roles mapped from the provider's claims are assigned or revoked on every login.",
+    tag = AUTH_TAG
+)]
+pub async fn oidc_callback_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(logging_info): Extension<LoggingInfo>,
+    Path(provider): Path<String>,
+    Json(payload): Json<OidcCallbackRequest>,
+) -> Result<Response, AppError> {
+    let result = OidcService::complete_login(
+        &db,
+        &provider,
+        &payload.code,
+        &payload.state,
+        logging_info.user_agent,
+        logging_info.ip_address,
+    )
+    .await?;
+
+    Ok(match result {
+        LoginResult::Authenticated(auth_token) => session_response(LoginResponse {
+            expires_in: auth_token.expires_in(),
+            token: auth_token.token,
+            refresh_token: auth_token.refresh_token,
+            recovery_codes: None,
+        }),
+        LoginResult::MfaRequired(challenge) => (
+            StatusCode::ACCEPTED,
+            Json(MfaChallengeResponse::from(challenge)),
+        )
+            .into_response(),
+    })
+}
+
+/// Lists the current user's sessions
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    responses(
+        (status = 200, description = "Active sessions, most recently used first", body = Vec<SessionResponse>),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "List sessions",
+    description = "Lists the devices the user is signed in on. `is_current` marks the session making the request, `device_info` names the browser and operating system.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn list_sessions_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = SessionService::get_user_sessions(&db, auth_user.user_id).await?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionResponse::from_session(session, auth_user.session_id))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Signs out one of the current user's sessions
+#[utoipa::path(
+    delete,
+    path = "/sessions/{session_id}",
+    params(
+        ("session_id" = String, Path, description = "Session ID from the session list")
+    ),
+    responses(
+        (status = 200, description = "Session signed out", body = SessionInvalidationResponse),
+        (status = 400, description = "Bad request - invalid session ID", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 404, description = "Not found - no such active session", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Sign out a session",
+    description = "Signs out one of the user's devices. Passing the current session's ID logs out this device.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn revoke_session_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let session_id = uuid::Uuid::parse_str(&session_id).map_err(|_| AppError {
+        message: "Invalid session ID format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    })?;
+
+    let session =
+        SessionService::invalidate_user_session(&db, auth_user.user_id, session_id).await?;
+
+    let mut response = Json(SessionInvalidationResponse {
+        message: "Session invalidated successfully".to_string(),
+        invalidated_count: Some(1),
+    })
+    .into_response();
+    if auth_user
+        .session_id
+        .is_some_and(|current| session.session_token == current.to_string())
+    {
+        SESSION_COOKIES.clear_session(response.headers_mut());
+    }
+
+    Ok(response)
+}
+
+/// Signs out all of the current user's other sessions
+#[utoipa::path(
+    delete,
+    path = "/sessions",
+    responses(
+        (status = 200, description = "Other sessions signed out", body = SessionInvalidationResponse),
+        (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - called with an API key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    summary = "Sign out everywhere else",
+    description = "Signs out every device except the one making the request.",
+    tag = AUTH_TAG,
+    security(
+        ("jwt_token" = [])
+    )
+)]
+pub async fn revoke_other_sessions_handler(
+    State(db): State<DatabaseConnection>,
+    Extension(auth_user): Extension<AuthUser>,
+    api_key_auth: Option<Extension<ApiKeyAuth>>,
+) -> Result<impl IntoResponse, AppError> {
+    reject_impersonation(&auth_user)?;
+    reject_api_key(api_key_auth.is_some())?;
+    let current_session_id = auth_user.session_id.ok_or(AppError {
+        message: "Only a signed in session can sign out the others".to_string(),
+        status_code: StatusCode::FORBIDDEN,
+    })?;
+
+    let count =
+        SessionService::invalidate_other_user_sessions(&db, auth_user.user_id, current_session_id)
+            .await?;
+
+    Ok(Json(SessionInvalidationResponse {
+        message: "Other sessions invalidated successfully".to_string(),
+        invalidated_count: Some(count),
+    }))
+}
+
+/// Account changes need the user's own session, a leaked API key must not take over the account
+fn reject_api_key(is_api_key: bool) -> Result<(), AppError> {
+    if is_api_key {
+        return Err(AppError {
+            message: "API keys can't change account settings".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+    Ok(())
+}
+
+/// Admins signed in as a user can look around but not change how the user signs in
+fn reject_impersonation(auth_user: &AuthUser) -> Result<(), AppError> {
+    if auth_user.impersonator_id.is_some() {
+        return Err(AppError {
+            message: "Not allowed while impersonating a user".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+    Ok(())
+}
+
+/// Serializes a new token pair, also setting the session cookies when they are enabled
+fn session_response(body: LoginResponse) -> Response {
+    let mut response = Json(&body).into_response();
+    SESSION_COOKIES.set_session(response.headers_mut(), &body.token, &body.refresh_token);
+    response
+}
+
+/// Loads the authenticated user
+async fn find_current_user(
+    db: &DatabaseConnection,
+    auth_user: &AuthUser,
+) -> Result<User, AppError> {
+    UserService::find_user_by_id(db, auth_user.user_id)
+        .await?
+        .ok_or(AppError {
+            message: "User not found".to_string(),
+            status_code: StatusCode::NOT_FOUND,
+        })
+}
@@ -1,5 +1,5 @@
 use axum::{
-    Json,
+    Extension, Json,
     extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
@@ -7,8 +7,9 @@ use axum::{
 use sea_orm::DatabaseConnection;
 
 use crate::{
-    bridge::types::admin::*,
+    bridge::{middleware::permission::RequirePermission, types::admin::*},
     control::services::admin_service::AdminService,
+    domain::permissions::{PermissionSet, markers::AdminRoles},
     infrastructure::app_error::{AppError, ErrorResponse, MessageResponse},
 };
 
@@ -20,18 +21,19 @@ use crate::{
     responses(
         (status = 200, description = "Roles retrieved successfully", body = PaginatedResponse<RoleResponse>),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Get roles",
     description = "Retrieves paginated roles with optional filtering",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn get_roles_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
     Query(params): Query<RolesQueryParams>,
 ) -> Result<impl IntoResponse, AppError> {
     let response = AdminService::get_roles(&db, params).await?;
@@ -48,7 +50,7 @@ pub async fn get_roles_handler(
     responses(
         (status = 200, description = "Role retrieved successfully", body = RoleResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission", body = ErrorResponse),
         (status = 404, description = "Role not found", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -56,11 +58,12 @@ pub async fn get_roles_handler(
     description = "Retrieves a specific role by its ID",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn get_role_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
     Path(role_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
     let response = AdminService::get_role(&db, role_id).await?;
@@ -76,7 +79,7 @@ pub async fn get_role_handler(
         (status = 201, description = "Role created successfully", body = RoleResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission, or the role would hold a permission the caller lacks", body = ErrorResponse),
         (status = 409, description = "Conflict - role name already exists", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -84,14 +87,16 @@ pub async fn get_role_handler(
     description = "Creates a new role with specified permissions",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn create_role_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
+    Extension(caller_permissions): Extension<PermissionSet>,
     Json(payload): Json<CreateRoleRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let response = AdminService::create_role(&db, payload).await?;
+    let response = AdminService::create_role(&db, payload, &caller_permissions).await?;
     Ok((StatusCode::CREATED, Json(response)))
 }
 
@@ -107,7 +112,7 @@ pub async fn create_role_handler(
         (status = 200, description = "Role updated successfully", body = RoleResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission, or the role would gain a permission the caller lacks", body = ErrorResponse),
         (status = 404, description = "Role not found", body = ErrorResponse),
         (status = 409, description = "Conflict - role name already exists", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -116,15 +121,17 @@ pub async fn create_role_handler(
     description = "Updates an existing role with new permissions",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn update_role_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
+    Extension(caller_permissions): Extension<PermissionSet>,
     Path(role_id): Path<i32>,
     Json(payload): Json<UpdateRoleRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let response = AdminService::update_role(&db, role_id, payload).await?;
+    let response = AdminService::update_role(&db, role_id, payload, &caller_permissions).await?;
     Ok((StatusCode::OK, Json(response)))
 }
 
@@ -138,7 +145,7 @@ pub async fn update_role_handler(
     responses(
         (status = 200, description = "Role deleted successfully", body = MessageResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission", body = ErrorResponse),
         (status = 404, description = "Role not found", body = ErrorResponse),
         (status = 409, description = "Conflict - role is in use by users", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
@@ -147,11 +154,12 @@ pub async fn update_role_handler(
     description = "Deletes a role if it's not assigned to any users",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn delete_role_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
     Path(role_id): Path<i32>,
 ) -> Result<impl IntoResponse, AppError> {
     AdminService::delete_role(&db, role_id).await?;
@@ -172,20 +180,139 @@ pub async fn delete_role_handler(
         (status = 200, description = "Permission check completed", body = PermissionCheckResponse),
         (status = 400, description = "Bad request - validation errors", body = ErrorResponse),
         (status = 401, description = "Unauthorized - authentication required", body = ErrorResponse),
-        (status = 403, description = "Forbidden - admin privileges required", body = ErrorResponse),
+        (status = 403, description = "Forbidden - requires the admin:roles permission", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     summary = "Check permission",
     description = "Checks if a user has a specific permission based on their role",
     tag = ADMIN_TAG,
     security(
-        ("jwt_token" = [])
+        ("jwt_token" = ["admin:roles"])
     )
 )]
 pub async fn check_permission_handler(
     State(db): State<DatabaseConnection>,
+    _: RequirePermission<AdminRoles>,
     Json(payload): Json<PermissionCheckRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let response = AdminService::check_permission(&db, payload).await?;
     Ok((StatusCode::OK, Json(response)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        extract::Request,
+        http::Method,
+        middleware::{self, Next},
+        routing::{post, put},
+    };
+    use sea_orm::{ConnectionTrait, DbBackend, Schema, Set, prelude::*};
+    use tower::ServiceExt;
+
+    use crate::{bridge::types::auth::AuthUser, entity::models::roles};
+
+    /// Roles are another way to hand out permissions, they can't hold more than their editor
+    #[tokio::test]
+    async fn roles_cant_exceed_the_editor() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        let table = Schema::new(DbBackend::Sqlite).create_table_from_entity(roles::Entity);
+        db.execute(db.get_database_backend().build(&table))
+            .await
+            .unwrap();
+        for (name, permissions) in [
+            ("super_admin", r#"["*"]"#),
+            ("support", r#"["admin:read"]"#),
+        ] {
+            roles::ActiveModel {
+                name: Set(name.to_string()),
+                permissions: Set(permissions.to_string()),
+                mfa_required: Set(false),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        // Stands in for admin_middleware and a caller holding admin:roles and admin:read
+        let as_role_admin = |mut request: Request, next: Next| async move {
+            request.extensions_mut().insert(AuthUser {
+                user_id: uuid::Uuid::new_v4(),
+                session_id: None,
+                impersonator_id: None,
+            });
+            request
+                .extensions_mut()
+                .insert(PermissionSet::from_strings(vec![
+                    "admin:roles".to_string(),
+                    "admin:read".to_string(),
+                ]));
+            next.run(request).await
+        };
+        let app = Router::new()
+            .route("/roles", post(create_role_handler))
+            .route("/roles/{id}", put(update_role_handler))
+            .layer(middleware::from_fn(as_role_admin))
+            .with_state(db);
+
+        let cases = [
+            (
+                Method::POST,
+                "/roles",
+                r#"{"name": "a", "permissions": ["*"]}"#,
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                Method::POST,
+                "/roles",
+                r#"{"name": "b", "permissions": ["admin:read"], "parent_role_id": 1}"#,
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                Method::PUT,
+                "/roles/2",
+                r#"{"permissions": ["admin:read", "*"]}"#,
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                Method::PUT,
+                "/roles/2",
+                r#"{"parent_role_id": 1}"#,
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                Method::POST,
+                "/roles",
+                r#"{"name": "c", "permissions": ["admin:read"]}"#,
+                StatusCode::CREATED,
+            ),
+            (
+                Method::PUT,
+                "/roles/2",
+                r#"{"description": "Read only"}"#,
+                StatusCode::OK,
+            ),
+            // Permissions a role already holds can stay, even above the editor
+            (
+                Method::PUT,
+                "/roles/1",
+                r#"{"permissions": ["*"], "description": "All"}"#,
+                StatusCode::OK,
+            ),
+        ];
+        for (method, path, body, status) in cases {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(path)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), status, "{} {} {}", method, path, body);
+        }
+    }
+}
@@ -1,99 +1,351 @@
 use axum::{
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
     },
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use futures_util::{SinkExt, StreamExt};
 use sea_orm::DatabaseConnection;
+use serde::Deserialize;
 use serde_json;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
 use uuid::Uuid;
 
-use crate::infrastructure::websocket::{WEBSOCKET_MANAGER, WebSocketMessage};
+use crate::{
+    control::services::{
+        permission_service::PermissionService, session_service::SessionService,
+        token_service::TokenService,
+    },
+    domain::permissions::Permission,
+    infrastructure::{
+        app_error::AppError,
+        websocket::{
+            ConnectionOwner, SubscriptionFilter, Topic, WEBSOCKET_MANAGER, WebSocketCommand,
+            WebSocketMessage,
+        },
+    },
+};
+
+/// Subprotocol browsers offer next to the token, `new WebSocket(url, ["bearer", token])`,
+/// since they can't set an Authorization header on the upgrade request
+const AUTH_SUBPROTOCOL: &str = "bearer";
+
+/// How long a connection opened without a token may take to send its Auth message
+const AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Deserialize)]
+pub struct WebSocketQuery {
+    /// Access token, prefer the subprotocol since URLs tend to end up in proxy logs
+    pub token: Option<String>,
+}
 
 /// WebSocket handler for real-time monitoring
+///
+/// The access token is taken from the `token` query parameter or the `bearer` subprotocol.
+/// Without either the connection is upgraded and its first message has to be
+/// `{"type": "Auth", "token": "..."}`. Either way the user needs admin:logs or admin:metrics.
+///
+/// Connections start without subscriptions and pick topics with
+/// `{"type": "Subscribe", "topic": "AuditLog", "filter": {"path_prefix": "/api/v1/auth"}}`
+/// and `{"type": "Unsubscribe", "topic": "AuditLog"}`. AuditLog and SystemLog need admin:logs,
+/// PerformanceMetrics needs admin:metrics.
+///
+/// Every message carries a `seq` number. After reconnecting, a client subscribes with
+/// `"since": <highest seq it saw>` to have the messages it missed replayed, or gets a Gap
+/// message for those no longer buffered.
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
-    State(_db): State<DatabaseConnection>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket))
+    State(db): State<DatabaseConnection>,
+    Query(query): Query<WebSocketQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let ws = ws.protocols([AUTH_SUBPROTOCOL]);
+
+    match query.token.or_else(|| subprotocol_token(&headers)) {
+        Some(token) => match authorize(&db, &token).await {
+            Ok(owner) => ws.on_upgrade(move |socket| handle_socket(socket, db, owner)),
+            Err(e) => e.into_response(),
+        },
+        None => ws.on_upgrade(move |socket| handshake(socket, db)),
+    }
+}
+
+/// Token offered as the subprotocol after `bearer`
+fn subprotocol_token(headers: &HeaderMap) -> Option<String> {
+    let protocols = headers.get(header::SEC_WEBSOCKET_PROTOCOL)?.to_str().ok()?;
+    let mut protocols = protocols.split(',').map(str::trim);
+    protocols.find(|protocol| *protocol == AUTH_SUBPROTOCOL)?;
+    protocols
+        .next()
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
+
+/// Checks the token, its session and that the user may watch the monitoring feed
+async fn authorize(db: &DatabaseConnection, token: &str) -> Result<ConnectionOwner, AppError> {
+    let (user_id, session_id, _) =
+        TokenService::extract_and_validate_token_with_session(db, token).await?;
+
+    if !PermissionService::has_any_permission(
+        db,
+        user_id,
+        &[Permission::AdminLogs, Permission::AdminMetrics],
+    )
+    .await?
+    {
+        return Err(AppError {
+            message: "Missing permission: admin:logs or admin:metrics".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+
+    let session = SessionService::find_session(db, &session_id.to_string())
+        .await?
+        .ok_or(AppError {
+            message: "Invalid session".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        })?;
+
+    Ok(ConnectionOwner {
+        user_id,
+        session_id,
+        expires_at: session.expires_at.to_utc(),
+    })
+}
+
+/// Waits for the Auth message of a connection opened without a token
+async fn handshake(mut socket: WebSocket, db: DatabaseConnection) {
+    let token = match tokio::time::timeout(AUTH_TIMEOUT, socket.recv()).await {
+        Ok(Some(Ok(Message::Text(text)))) => match serde_json::from_str(&text) {
+            Ok(WebSocketCommand::Auth { token }) => Some(token),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let result = match token {
+        Some(token) => authorize(&db, &token).await,
+        None => Err(AppError {
+            message: "Expected an Auth message".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        }),
+    };
+
+    match result {
+        Ok(owner) => handle_socket(socket, db, owner).await,
+        Err(e) => {
+            let status = WEBSOCKET_MANAGER
+                .sequence(WebSocketMessage::ConnectionStatus {
+                    status: "unauthorized".to_string(),
+                    message: e.message.clone(),
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                })
+                .await;
+            if let Ok(status_json) = serde_json::to_string(&status) {
+                let _ = socket.send(Message::Text(status_json.into())).await;
+            }
+            let _ = socket.send(close_message(&e.message)).await;
+        }
+    }
+}
+
+/// Close frame telling the client why the server ends the connection
+fn close_message(reason: &str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: close_code::POLICY,
+        reason: reason.into(),
+    }))
+}
+
+/// Runs a command sent by an authenticated connection and returns the reply,
+/// None when the command already replied
+async fn handle_command(
+    db: &DatabaseConnection,
+    connection_id: &str,
+    owner: &ConnectionOwner,
+    command: WebSocketCommand,
+) -> Option<WebSocketMessage> {
+    let result = match command {
+        WebSocketCommand::Auth { .. } => Err(AppError {
+            message: "Already authenticated".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        }),
+        WebSocketCommand::Subscribe {
+            topic,
+            filter,
+            since,
+        } => subscribe(db, connection_id, owner, topic, filter, since)
+            .await
+            .map(|_| None),
+        WebSocketCommand::Unsubscribe { topic } => {
+            WEBSOCKET_MANAGER.unsubscribe(connection_id, topic).await;
+            Ok(Some(WebSocketMessage::Unsubscribed { topic }))
+        }
+        WebSocketCommand::Ping => Ok(Some(WebSocketMessage::Pong)),
+    };
+
+    result.unwrap_or_else(|e| Some(WebSocketMessage::Error { message: e.message }))
+}
+
+/// Subscribes the connection after checking the user may see the topic,
+/// the manager confirms it and replays what was missed since `since`
+async fn subscribe(
+    db: &DatabaseConnection,
+    connection_id: &str,
+    owner: &ConnectionOwner,
+    topic: Topic,
+    filter: SubscriptionFilter,
+    since: Option<u64>,
+) -> Result<(), AppError> {
+    let permission = topic_permission(topic);
+    if !PermissionService::has_permission(db, owner.user_id, &permission).await? {
+        return Err(AppError {
+            message: format!("Missing permission: {}", permission.to_string()),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+    filter.validate(topic)?;
+
+    WEBSOCKET_MANAGER
+        .subscribe(connection_id, topic, filter, since)
+        .await;
+    Ok(())
+}
+
+/// Permission a topic requires, checked on every Subscribe
+fn topic_permission(topic: Topic) -> Permission {
+    match topic {
+        Topic::AuditLog | Topic::SystemLog => Permission::AdminLogs,
+        Topic::PerformanceMetrics => Permission::AdminMetrics,
+    }
 }
 
 /// Handle individual WebSocket connection
-async fn handle_socket(socket: WebSocket) {
+async fn handle_socket(socket: WebSocket, db: DatabaseConnection, owner: ConnectionOwner) {
     let connection_id = Uuid::new_v4().to_string();
 
-    // Broadcast connection event
-    crate::infrastructure::websocket::broadcast_system_log(
-        "info".to_string(),
-        format!("WebSocket connection established: {}", connection_id),
-        "websocket".to_string(),
-    )
-    .await;
+    tracing::info!(
+        target: "websocket",
+        connection_id = %connection_id,
+        user_id = %owner.user_id,
+        "WebSocket connection established"
+    );
 
     // Send connection status
-    let status_message = WebSocketMessage::ConnectionStatus {
-        status: "connected".to_string(),
-        message: "WebSocket connection established".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-    };
+    let status_message = WEBSOCKET_MANAGER
+        .sequence(WebSocketMessage::ConnectionStatus {
+            status: "connected".to_string(),
+            message: "WebSocket connection established".to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        })
+        .await;
 
     // Split the socket into sender and receiver
     let (mut sender, mut receiver) = socket.split();
 
-    // Subscribe to broadcast channel
-    let mut broadcast_rx = WEBSOCKET_MANAGER.subscribe();
+    // Register the connection and subscribe to broadcast channel
+    let (mut broadcast_rx, closed) = WEBSOCKET_MANAGER
+        .add_connection(connection_id.clone(), owner)
+        .await;
 
     // Send initial connection status
     if let Ok(message_json) = serde_json::to_string(&status_message) {
         let _ = sender.send(Message::Text(message_json.into())).await;
     }
 
-    // Create a channel for sending messages from ping/pong task to sender task
-    let (tx, mut rx) = mpsc::channel::<String>(100);
+    // Create a channel for sending messages from the other tasks to the sender task
+    let (tx, mut rx) = mpsc::channel::<Message>(100);
 
     // Clone values for the broadcast task
     let connection_id_broadcast = connection_id.clone();
 
     // Spawn task to forward broadcast messages to this client
     let tx_broadcast = tx.clone();
-    let broadcast_task = tokio::spawn(async move {
-        while let Ok(message) = broadcast_rx.recv().await {
-            if let Ok(message_json) = serde_json::to_string(&message) {
-                if let Err(e) = tx_broadcast.send(message_json).await {
+    let mut broadcast_task = tokio::spawn(async move {
+        // Highest sequence number sent to the client
+        let mut last_seq = 0;
+        // After catching up on a lag, the last replayed message, the channel may still hold copies
+        let mut replayed_up_to: Option<u64> = None;
+
+        loop {
+            let messages = match broadcast_rx.recv().await {
+                Ok(message) => {
+                    if message.message.topic().is_none() {
+                        // Replies are queued after any copy of a replayed message
+                        replayed_up_to = None;
+                    } else if replayed_up_to.is_some_and(|seq| message.seq <= seq) {
+                        continue;
+                    }
+                    vec![message]
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(
+                        "Connection {} missed {} messages, replaying them",
+                        connection_id_broadcast,
+                        missed
+                    );
+                    let replay = WEBSOCKET_MANAGER
+                        .replay(&connection_id_broadcast, last_seq)
+                        .await;
+                    replayed_up_to = replay
+                        .iter()
+                        .filter(|message| message.message.topic().is_some())
+                        .map(|message| message.seq)
+                        .max();
+                    replay
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            for message in messages {
+                last_seq = last_seq.max(message.seq);
+                if let Ok(message_json) = serde_json::to_string(&message)
+                    && let Err(e) = tx_broadcast.send(Message::Text(message_json.into())).await
+                {
                     tracing::warn!(
                         "Failed to send message to client {}: {}",
                         connection_id_broadcast,
                         e
                     );
-                    break;
+                    return;
                 }
             }
         }
     });
 
-    // Clone values for the ping/pong task
-    let connection_id_ping = connection_id.clone();
+    // Clone values for the command task
+    let connection_id_commands = connection_id.clone();
 
     // Handle incoming messages from client
-    let ping_pong_task = tokio::spawn(async move {
+    let mut command_task = tokio::spawn(async move {
         while let Some(Ok(message)) = receiver.next().await {
             match message {
                 Message::Text(text) => {
-                    // Handle text messages (could be commands or ping)
-                    if text == "ping" {
-                        let pong = WebSocketMessage::Pong;
-                        if let Ok(pong_json) = serde_json::to_string(&pong) {
-                            let _ = tx.send(pong_json).await;
+                    // The plain "ping" predates the JSON commands
+                    let reply = if text == "ping" {
+                        Some(WebSocketMessage::Pong)
+                    } else {
+                        match serde_json::from_str::<WebSocketCommand>(&text) {
+                            Ok(command) => {
+                                handle_command(&db, &connection_id_commands, &owner, command).await
+                            }
+                            Err(e) => Some(WebSocketMessage::Error {
+                                message: format!("Invalid command: {}", e),
+                            }),
                         }
+                    };
+                    if let Some(reply) = reply {
+                        WEBSOCKET_MANAGER
+                            .send_to_connection(&connection_id_commands, reply)
+                            .await;
                     }
                 }
                 Message::Close(_) => {
                     tracing::info!(
                         "WebSocket connection {} closed by client",
-                        connection_id_ping
+                        connection_id_commands
                     );
                     break;
                 }
@@ -104,39 +356,56 @@ async fn handle_socket(socket: WebSocket) {
         }
     });
 
-    // Main sender task that handles both broadcast and ping/pong messages
-    let sender_task = tokio::spawn(async move {
-        while let Some(message_json) = rx.recv().await {
-            if let Err(e) = sender.send(Message::Text(message_json.into())).await {
+    // Main sender task that writes the connection's messages and the close frame
+    let mut sender_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = sender.send(message).await {
                 tracing::warn!("Failed to send message to WebSocket: {}", e);
                 break;
             }
         }
     });
 
-    // Wait for any task to complete
-    tokio::select! {
-        _ = broadcast_task => {
+    let session_expiry = (owner.expires_at - chrono::Utc::now())
+        .to_std()
+        .unwrap_or_default();
+
+    // Wait for any task to complete, or for the session to end
+    let close_reason = tokio::select! {
+        _ = &mut broadcast_task => {
             tracing::info!("Broadcast task ended for connection {}", connection_id);
+            None
         }
-        _ = ping_pong_task => {
-            tracing::info!("Ping/pong task ended for connection {}", connection_id);
+        _ = &mut command_task => {
+            tracing::info!("Command task ended for connection {}", connection_id);
+            None
         }
-        _ = sender_task => {
+        _ = &mut sender_task => {
             tracing::info!("Sender task ended for connection {}", connection_id);
+            None
         }
+        _ = closed.notified() => Some("Session has been invalidated"),
+        _ = tokio::time::sleep(session_expiry) => Some("Session expired"),
+    };
+
+    broadcast_task.abort();
+    command_task.abort();
+    if !sender_task.is_finished() {
+        if let Some(reason) = close_reason {
+            tracing::info!("Closing WebSocket connection {}: {}", connection_id, reason);
+            let _ = tx.send(close_message(reason)).await;
+        }
+        // The sender task ends once the queued messages are flushed
+        drop(tx);
+        let _ = tokio::time::timeout(std::time::Duration::from_secs(5), sender_task).await;
     }
 
     // Clean up connection
     WEBSOCKET_MANAGER.remove_connection(&connection_id).await;
 
-    // Broadcast disconnection event
-    crate::infrastructure::websocket::broadcast_system_log(
-        "info".to_string(),
-        format!("WebSocket connection closed: {}", connection_id),
-        "websocket".to_string(),
-    )
-    .await;
-
-    tracing::info!("WebSocket connection {} cleaned up", connection_id);
+    tracing::info!(
+        target: "websocket",
+        connection_id = %connection_id,
+        "WebSocket connection closed"
+    );
 }
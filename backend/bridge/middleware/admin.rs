@@ -8,14 +8,12 @@ use sea_orm::DatabaseConnection;
 use tracing::{info, warn};
 
 use crate::{
-    bridge::types::{admin::AdminUser, auth::AuthUser},
-    control::services::{
-        session_service::SessionService, token_service::TokenService, user_service::UserService,
-    },
+    bridge::{middleware::auth::authenticate, types::admin::AdminUser},
+    control::services::user_service::UserService,
     infrastructure::{app_error::AppError, logging::LoggingManager},
 };
 
-/// Admin middleware that handles JWT extraction and validation; no permission checking here, all done at the handler
+/// Admin middleware that authenticates a JWT or API key; permissions are checked per handler with `RequirePermission`
 pub async fn admin_middleware(
     State(db): State<DatabaseConnection>,
     mut request: Request,
@@ -23,18 +21,9 @@ pub async fn admin_middleware(
 ) -> Result<Response, AppError> {
     let request_id = LoggingManager::generate_request_id();
 
-    // Extract token from Authorization header
-    let token = TokenService::extract_token_from_header(&request)?;
-
-    // Extract and validate token with session validation
-    let (user_id, session_id) =
-        TokenService::extract_and_validate_token_with_session(&db, &token).await?;
-
-    // Update session activity (fire and forget)
-    let db_clone = db.clone();
-    tokio::spawn(async move {
-        let _ = SessionService::update_session_activity(&db_clone, session_id).await;
-    });
+    // API key or JWT with session validation
+    let auth_user = authenticate(&db, &mut request).await?;
+    let user_id = auth_user.user_id;
 
     let user = UserService::find_user_by_id(&db, user_id)
         .await?
@@ -50,15 +39,18 @@ pub async fn admin_middleware(
         "Admin access granted"
     );
 
-    // Add both AuthUser and AdminUser to request extensions for downstream handlers
-    request.extensions_mut().insert(AuthUser { user_id });
-
+    // AuthUser is already set, add AdminUser for downstream handlers
     request.extensions_mut().insert(AdminUser {
         user_id,
         email: user.email.clone(),
     });
 
-    Ok(next.run(request).await)
+    let mut response = next.run(request).await;
+
+    // Lets the logging middleware, which runs before authentication, attribute the request
+    response.extensions_mut().insert(auth_user);
+
+    Ok(response)
 }
 
 /// Log admin access attempts (for security monitoring)
@@ -2,23 +2,65 @@ use axum::{extract::Request, extract::State, middleware::Next, response::Respons
 use sea_orm::DatabaseConnection;
 
 use crate::{
-    bridge::types::auth::AuthUser,
-    control::services::{session_service::SessionService, token_service::TokenService},
+    bridge::types::auth::{ApiKeyAuth, AuthUser},
+    control::services::{
+        api_key_service::ApiKeyService, session_service::SessionService,
+        token_service::TokenService,
+    },
     infrastructure::app_error::AppError,
 };
 
-// JWT middleware with session validation
+// JWT or API key middleware with session validation
 pub async fn auth_middleware(
     State(db): State<DatabaseConnection>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let token = TokenService::extract_token_from_header(&request)?;
+    let auth_user = authenticate(&db, &mut request).await?;
+
+    let mut response = next.run(request).await;
+
+    // Lets the logging middleware, which runs before authentication, attribute the request
+    response.extensions_mut().insert(auth_user);
+
+    Ok(response)
+}
+
+/// Authenticates the request with an API key when one is sent, otherwise with a JWT and its session
+///
+/// Inserts `AuthUser`, and `ApiKeyAuth` for API keys, into the request extensions.
+pub async fn authenticate(
+    db: &DatabaseConnection,
+    request: &mut Request,
+) -> Result<AuthUser, AppError> {
+    if let Some(key) = TokenService::extract_api_key_from_header(request) {
+        let api_key = ApiKeyService::authenticate(db, &key).await?;
+
+        // Record usage (fire and forget)
+        let db_clone = db.clone();
+        let key_id = api_key.id;
+        tokio::spawn(async move {
+            let _ = ApiKeyService::touch(&db_clone, key_id).await;
+        });
+
+        request.extensions_mut().insert(ApiKeyAuth {
+            key_id: api_key.id,
+            scopes: ApiKeyService::scopes(&api_key),
+        });
+        let auth_user = AuthUser {
+            user_id: api_key.user_id,
+            session_id: None,
+            impersonator_id: None,
+        };
+        request.extensions_mut().insert(auth_user.clone());
+        return Ok(auth_user);
+    }
+
+    let token = TokenService::extract_token_from_header(request)?;
 
     // Extract and validate token with session validation
-    let (user_id, session_id) =
-        TokenService::extract_and_validate_token_with_session(&db, &token).await?;
-    // let user_id = TokenService::extract_and_validate_token(&request)?;
+    let (user_id, session_id, impersonator_id) =
+        TokenService::extract_and_validate_token_with_session(db, &token).await?;
 
     // Update session activity (fire and forget)
     let db_clone = db.clone();
@@ -27,7 +69,12 @@ pub async fn auth_middleware(
     });
 
     // Add user to request extensions
-    request.extensions_mut().insert(AuthUser { user_id });
+    let auth_user = AuthUser {
+        user_id,
+        session_id: Some(session_id),
+        impersonator_id,
+    };
+    request.extensions_mut().insert(auth_user.clone());
 
-    Ok(next.run(request).await)
+    Ok(auth_user)
 }
@@ -0,0 +1,24 @@
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+
+use crate::infrastructure::{app_error::AppError, session_cookies::SESSION_COOKIES};
+
+/// Double submit CSRF check for requests authenticated by the session cookie
+///
+/// Browsers attach cookies to cross-site requests, so state-changing requests that rely on the
+/// cookie must also send the CSRF cookie's value in the X-CSRF-Token header. Another site can't
+/// read the cookie to do that. Requests with an Authorization or API key header are left alone.
+pub async fn csrf_middleware(request: Request, next: Next) -> Result<Response, AppError> {
+    let safe_method = request.method().is_safe();
+
+    if !safe_method
+        && SESSION_COOKIES.authenticates_with_cookie(request.headers())
+        && !SESSION_COOKIES.csrf_token_valid(request.headers())
+    {
+        return Err(AppError {
+            message: "Invalid CSRF token".to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        });
+    }
+
+    Ok(next.run(request).await)
+}
@@ -1,19 +1,22 @@
 use axum::{
     body::Body,
-    extract::{Request, State},
+    extract::{ConnectInfo, MatchedPath, Request, State},
     http::StatusCode,
     middleware::Next,
     response::Response,
 };
 use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
 use serde_json::Value;
-use std::time::Instant;
-use tracing::{error, info};
+use std::{net::SocketAddr, time::Instant};
+use tracing::{error, info, warn};
 
 use crate::{
     bridge::types::{auth::AuthUser, logging::LoggingInfo},
     entity::models::audit_logs,
-    infrastructure::{logging::LoggingManager, websocket::broadcast_audit_log},
+    infrastructure::{
+        logging::LoggingManager, request_metrics::REQUEST_METRICS,
+        trusted_proxies::TRUSTED_PROXIES, websocket::broadcast_audit_log,
+    },
 };
 
 const MAX_BODY_LOG_BYTES: usize = 4096; // 4KB
@@ -25,6 +28,10 @@ const SENSITIVE_FIELDS: &[&str] = &[
     "pwd",
     "secret",
     "token",
+    "refresh_token",
+    "mfa_token",
+    "code",
+    "recovery_codes",
     "key",
     "auth",
     "authorization",
@@ -144,55 +151,73 @@ pub async fn extract_request_response(
     Ok((res, copy_req_sanitized, copy_res_sanitized))
 }
 
-/// Request logging middleware for auditing all API requests
-pub async fn request_logging_middleware(
-    State(db): State<DatabaseConnection>,
-    mut request: Request,
-    next: Next,
-) -> Result<Response, StatusCode> {
-    let start = Instant::now();
-    let request_id = LoggingManager::generate_request_id();
-
-    // Extract request info
-    let method = request.method().to_string();
-    let path = request.uri().path().to_string();
-
+/// Paths left out of the audit log
+fn is_unlogged(path: &str) -> bool {
     // if path is /api-docs/openapi.json, don't log
     if path == "/api-docs/openapi.json" {
-        return Ok(next.run(request).await);
+        return true;
     }
 
     // Don't log the logs endpoint to prevent recursive logging
     if path == "/api/v1/admin/logs" {
-        return Ok(next.run(request).await);
+        return true;
     }
 
     // Don't log database inspection endpoints as they can return large amounts of data
     if path.starts_with("/api/v1/admin/database") {
-        return Ok(next.run(request).await);
+        return true;
     }
 
     // Don't log users endpoint as it can return large amounts of user data
     if path.starts_with("/api/v1/admin/users") {
-        return Ok(next.run(request).await);
+        return true;
     }
 
     // Don't log WebSocket endpoint to prevent recursive logging
     if path == "/api/v1/admin/ws" {
-        return Ok(next.run(request).await);
+        return true;
     }
 
-    let ip_address = request
+    false
+}
+
+/// Client IP address, the connection's peer unless that is a proxy listed in TRUSTED_PROXIES
+pub fn client_ip(request: &Request) -> Option<String> {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let forwarded_for = request
         .headers()
-        .get("x-forwarded-for")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string())
-        .or_else(|| {
-            request
-                .extensions()
-                .get::<std::net::SocketAddr>()
-                .map(|addr| addr.ip().to_string())
-        });
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok());
+
+    TRUSTED_PROXIES
+        .client_ip(peer, forwarded_for)
+        .map(|ip| ip.to_string())
+}
+
+/// Request logging middleware for auditing all API requests
+pub async fn request_logging_middleware(
+    State(db): State<DatabaseConnection>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let start = Instant::now();
+    let request_id = LoggingManager::generate_request_id();
+
+    // Extract request info
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    // Metrics are kept per route template, unmatched paths would grow them without bound
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let ip_address = client_ip(&request);
     let user_agent = request
         .headers()
         .get("user-agent")
@@ -219,18 +244,32 @@ pub async fn request_logging_middleware(
 
     // Insert into request extensions for downstream handlers, must be done
     // before we extract the request and response bodies, otherwise the
-    // request will have finished already.
+    // request will have finished already. Requests that aren't logged below
+    // still get it, handlers use it to audit who did what.
     request.extensions_mut().insert(logging_info);
 
+    // Requests that aren't logged are still counted in the metrics
+    if is_unlogged(&path) {
+        let response = next.run(request).await;
+        REQUEST_METRICS.record(&method, &route, response.status(), start.elapsed());
+        return Ok(response);
+    }
+
     // Capture request and response bodies (runs the next handler so we get the response)
     let (response, request_body, response_body) = extract_request_response(request, next).await.map_err(|(status, message)| {
         error!(request_id = %request_id, error = %message, "Failed to extract request and response bodies");
         status
     })?;
 
+    // Authentication runs after this middleware, it hands the user back on the response
+    let auth_user = response.extensions().get::<AuthUser>();
+    let user_id = user_id.or_else(|| auth_user.map(|u| u.user_id));
+    let actor_id = auth_user.and_then(|u| u.impersonator_id);
+
     let duration = start.elapsed();
     let response_time_ms = duration.as_millis() as i32;
     let status_code = response.status().as_u16() as i32;
+    REQUEST_METRICS.record(&method, &route, response.status(), duration);
 
     // Error message if status is error
     let error_message = if status_code >= 400 {
@@ -260,10 +299,6 @@ pub async fn request_logging_middleware(
     let error_message_for_ws = error_message_clone.clone();
     let user_id_for_ws = user_id_clone.clone();
 
-    // Clone values for system log broadcasting
-    let method_for_logs = method_clone.clone();
-    let path_for_logs = path_clone.clone();
-
     // Insert audit log asynchronously (don't block response)
     let audit_log = audit_logs::ActiveModel {
         id: Set(uuid::Uuid::new_v4()),
@@ -278,19 +313,12 @@ pub async fn request_logging_middleware(
         request_body: Set(request_body),
         response_body: Set(response_body),
         error_message: Set(error_message_clone.clone()),
+        actor_id: Set(actor_id),
     };
     let db_clone = db.clone();
     tokio::spawn(async move {
         if let Err(e) = audit_log.insert(&db_clone).await {
             error!(request_id = %request_id_clone, error = ?e, "Failed to insert audit log");
-
-            // Broadcast error log
-            crate::infrastructure::websocket::broadcast_system_log(
-                "error".to_string(),
-                format!("Failed to insert audit log: {}", e),
-                "audit_logging".to_string(),
-            )
-            .await;
         } else {
             info!(request_id = %request_id_clone, "Audit log inserted");
 
@@ -308,47 +336,28 @@ pub async fn request_logging_middleware(
                 error_message_for_ws,
             )
             .await;
-
-            // Broadcast info log for successful requests (but not too frequently)
-            if status_code >= 200 && status_code < 300 {
-                crate::infrastructure::websocket::broadcast_system_log(
-                    "info".to_string(),
-                    format!(
-                        "Request completed: {} {} ({}ms)",
-                        method_for_logs, path_for_logs, response_time_ms
-                    ),
-                    "request_logging".to_string(),
-                )
-                .await;
-            } else if status_code >= 400 {
-                // Broadcast warning for client errors
-                crate::infrastructure::websocket::broadcast_system_log(
-                    "warn".to_string(),
-                    format!(
-                        "Client error: {} {} - {}",
-                        method_for_logs, path_for_logs, status_code
-                    ),
-                    "request_logging".to_string(),
-                )
-                .await;
-            } else if status_code >= 500 {
-                // Broadcast error for server errors
-                crate::infrastructure::websocket::broadcast_system_log(
-                    "error".to_string(),
-                    format!(
-                        "Server error: {} {} - {}",
-                        method_for_logs, path_for_logs, status_code
-                    ),
-                    "request_logging".to_string(),
-                )
-                .await;
-            }
         }
     });
 
     // log to tracing with admin label if the path starts with /api/v1/admin
     let is_admin_request = path_clone.starts_with("/api/v1/admin");
-    if let Some(ref err) = error_message_clone {
+    if status_code < 500
+        && let Some(ref err) = error_message_clone
+    {
+        warn!(
+            request_id = %request_id,
+            status_code,
+            user_id = ?user_id_clone,
+            path = %path_clone,
+            method = %method_clone,
+            ip_address = ?ip_address_clone,
+            user_agent = ?user_agent_clone,
+            response_time_ms,
+            error = %err,
+            admin_request = %is_admin_request,
+            "Request error"
+        );
+    } else if let Some(ref err) = error_message_clone {
         error!(
             request_id = %request_id,
             status_code,
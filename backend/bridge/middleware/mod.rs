@@ -1,3 +1,6 @@
 pub mod admin;
 pub mod auth;
+pub mod csrf;
 pub mod logging;
+pub mod permission;
+pub mod rate_limit;
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{StatusCode, request::Parts},
+};
+use sea_orm::DatabaseConnection;
+use std::marker::PhantomData;
+use tracing::warn;
+
+use crate::{
+    bridge::types::auth::{ApiKeyAuth, AuthUser},
+    control::services::permission_service::PermissionService,
+    domain::permissions::{PermissionMarker, PermissionSet},
+    infrastructure::app_error::AppError,
+};
+
+/// Extractor that rejects the request unless the caller holds permission `P`
+///
+/// Must run behind `auth_middleware` or `admin_middleware`. The caller's permission set is
+/// resolved once and cached in the request extensions, so several extractors on one handler
+/// don't query the database again. Handlers can take it as `Extension<PermissionSet>` after
+/// this extractor, e.g. to check that what they hand out doesn't exceed it.
+///
+/// ```rust,no_run
+/// pub async fn get_users_handler(
+///     _: RequirePermission<markers::AdminUsers>,
+///     State(db): State<DatabaseConnection>,
+/// ) -> Result<impl IntoResponse, AppError> { ... }
+/// ```
+pub struct RequirePermission<P: PermissionMarker>(PhantomData<P>);
+
+impl<P, S> FromRequestParts<S> for RequirePermission<P>
+where
+    P: PermissionMarker,
+    DatabaseConnection: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let permissions = caller_permissions(parts, state).await?;
+        let required = P::permission();
+
+        if !permissions.contains(&required) {
+            let user_id = parts.extensions.get::<AuthUser>().map(|u| u.user_id);
+            let api_key_id = parts.extensions.get::<ApiKeyAuth>().map(|k| k.key_id);
+            warn!(
+                user_id = ?user_id,
+                api_key_id = ?api_key_id,
+                path = %parts.uri.path(),
+                permission = %required.to_string(),
+                "Permission denied"
+            );
+            return Err(AppError {
+                message: format!("Missing permission: {}", required.to_string()),
+                status_code: StatusCode::FORBIDDEN,
+            });
+        }
+
+        Ok(Self(PhantomData))
+    }
+}
+
+/// The authenticated caller's permissions, resolved on first use
+async fn caller_permissions<S>(parts: &mut Parts, state: &S) -> Result<PermissionSet, AppError>
+where
+    DatabaseConnection: FromRef<S>,
+    S: Send + Sync,
+{
+    if let Some(permissions) = parts.extensions.get::<PermissionSet>() {
+        return Ok(permissions.clone());
+    }
+
+    let auth_user = parts
+        .extensions
+        .get::<AuthUser>()
+        .cloned()
+        .ok_or(AppError {
+            message: "Authentication required".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        })?;
+
+    let db = DatabaseConnection::from_ref(state);
+    let mut permissions = PermissionService::get_user_permissions(&db, auth_user.user_id).await?;
+
+    // API keys never get more than their scopes, whatever the user may do
+    if let Some(api_key) = parts.extensions.get::<ApiKeyAuth>() {
+        permissions.restrict_to(&api_key.scopes);
+    }
+    parts.extensions.insert(permissions.clone());
+
+    Ok(permissions)
+}
@@ -2,12 +2,41 @@ use axum::middleware;
 use sea_orm::DatabaseConnection;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::bridge::middleware::admin::admin_middleware;
+use crate::bridge::middleware::{admin::admin_middleware, rate_limit::rate_limit_middleware};
+use crate::infrastructure::rate_limit::{RateLimitKey, RateLimitPolicy, RateLimiter};
 
 pub fn admin_router(db: DatabaseConnection) -> OpenApiRouter {
+    // Expensive endpoints, limited per admin
+    let table_records_limiter = RateLimiter::new(
+        RateLimitPolicy::per_minute("table_records", 30, RateLimitKey::User),
+        db.clone(),
+    );
+    let table_records_routes = OpenApiRouter::new()
+        .routes(routes!(
+            crate::bridge::handlers::admin::get_table_records_handler
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            table_records_limiter,
+            rate_limit_middleware,
+        ));
+
+    let health_limiter = RateLimiter::new(
+        RateLimitPolicy::per_minute("health", 10, RateLimitKey::User),
+        db.clone(),
+    );
+    let health_routes = OpenApiRouter::new()
+        .routes(routes!(crate::bridge::handlers::admin::health_handler))
+        .route_layer(middleware::from_fn_with_state(
+            health_limiter,
+            rate_limit_middleware,
+        ));
+
     // Admin authentication routes (no middleware needed)
     let auth_routes = OpenApiRouter::new()
         .routes(routes!(crate::bridge::handlers::admin::admin_login_handler))
+        .routes(routes!(
+            crate::bridge::handlers::admin::admin_mfa_verify_handler
+        ))
         .routes(routes!(
             crate::bridge::handlers::admin::admin_logout_handler
         ));
@@ -21,9 +50,36 @@ pub fn admin_router(db: DatabaseConnection) -> OpenApiRouter {
         // User management
         .routes(routes!(crate::bridge::handlers::admin::get_users_handler))
         .routes(routes!(crate::bridge::handlers::admin::create_user_handler))
+        .routes(routes!(
+            crate::bridge::handlers::admin::import_users_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::get_email_duplicates_handler
+        ))
         .routes(routes!(crate::bridge::handlers::admin::get_user_handler))
         .routes(routes!(crate::bridge::handlers::admin::update_user_handler))
         .routes(routes!(crate::bridge::handlers::admin::delete_user_handler))
+        .routes(routes!(
+            crate::bridge::handlers::admin::unlock_user_login_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::get_user_roles_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::assign_user_role_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::revoke_user_role_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::get_user_permissions_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::set_user_permission_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::remove_user_permission_handler
+        ))
         // Session management
         .routes(routes!(
             crate::bridge::handlers::admin::get_user_sessions_handler
@@ -34,6 +90,9 @@ pub fn admin_router(db: DatabaseConnection) -> OpenApiRouter {
         .routes(routes!(
             crate::bridge::handlers::admin::invalidate_all_user_sessions_handler
         ))
+        .routes(routes!(
+            crate::bridge::handlers::admin::impersonate_user_handler
+        ))
         // Role management
         .routes(routes!(crate::bridge::handlers::roles::get_roles_handler))
         .routes(routes!(crate::bridge::handlers::roles::create_role_handler))
@@ -47,14 +106,44 @@ pub fn admin_router(db: DatabaseConnection) -> OpenApiRouter {
         .routes(routes!(
             crate::bridge::handlers::admin::get_database_tables_handler
         ))
-        .routes(routes!(
-            crate::bridge::handlers::admin::get_table_records_handler
-        ))
+        .merge(table_records_routes)
         // System health
-        .routes(routes!(crate::bridge::handlers::admin::health_handler))
+        .merge(health_routes)
         // Combined auth and admin middleware
         .route_layer(middleware::from_fn_with_state(db.clone(), admin_middleware));
 
     // Combine auth and protected routes
     auth_routes.merge(protected_routes).with_state(db)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every protected admin operation must document the permission it requires
+    #[tokio::test]
+    async fn protected_routes_declare_a_permission() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        let (_, api) = admin_router(db).split_for_parts();
+        let api = serde_json::to_value(&api).unwrap();
+
+        let public = ["/login", "/mfa/verify", "/logout"];
+        let mut checked = 0;
+        for (path, item) in api["paths"].as_object().unwrap() {
+            if public.contains(&path.as_str()) {
+                continue;
+            }
+            for (method, operation) in item.as_object().unwrap() {
+                let scopes = &operation["security"][0]["jwt_token"];
+                assert!(
+                    scopes.as_array().is_some_and(|scopes| !scopes.is_empty()),
+                    "{} {} has no required permission",
+                    method,
+                    path
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+}
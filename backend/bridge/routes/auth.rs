@@ -2,20 +2,174 @@ use axum::middleware;
 use sea_orm::DatabaseConnection;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
-use crate::bridge::middleware::auth::auth_middleware;
+use crate::bridge::middleware::{auth::auth_middleware, rate_limit::rate_limit_middleware};
+use crate::infrastructure::rate_limit::{RateLimitKey, RateLimitPolicy, RateLimiter};
 
 pub fn auth_router(db: DatabaseConnection) -> OpenApiRouter {
+    // Account creation, limited per client IP
+    let registration_limiter = RateLimiter::new(
+        RateLimitPolicy::per_minute("register", 5, RateLimitKey::Ip),
+        db.clone(),
+    );
+    let registration_routes = OpenApiRouter::new()
+        .routes(routes!(crate::bridge::handlers::auth::register_handler))
+        .route_layer(middleware::from_fn_with_state(
+            registration_limiter,
+            rate_limit_middleware,
+        ));
+
+    // Identity provider logins, limited per client IP
+    let oidc_limiter = RateLimiter::new(
+        RateLimitPolicy::per_minute("oidc", 20, RateLimitKey::Ip),
+        db.clone(),
+    );
+    let oidc_routes = OpenApiRouter::new()
+        .routes(routes!(
+            crate::bridge::handlers::auth::oidc_authorize_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::oidc_callback_handler
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            oidc_limiter,
+            rate_limit_middleware,
+        ));
+
     // Routes that don't need authentication
     let public_routes = OpenApiRouter::new()
-        .routes(routes!(crate::bridge::handlers::auth::register_handler))
+        .merge(registration_routes)
+        .merge(oidc_routes)
+        .routes(routes!(
+            crate::bridge::handlers::auth::oidc_providers_handler
+        ))
         .routes(routes!(crate::bridge::handlers::auth::login_handler))
+        .routes(routes!(crate::bridge::handlers::auth::refresh_handler))
+        .routes(routes!(crate::bridge::handlers::auth::mfa_verify_handler))
+        .routes(routes!(crate::bridge::handlers::auth::verify_email_handler))
+        .routes(routes!(
+            crate::bridge::handlers::auth::forgot_password_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::reset_password_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::confirm_email_change_handler
+        ))
         .routes(routes!(crate::bridge::handlers::auth::logout_handler));
 
     // Routes that need authentication
     let protected_routes = OpenApiRouter::new()
         .routes(routes!(crate::bridge::handlers::auth::profile_handler))
+        .routes(routes!(
+            crate::bridge::handlers::auth::delete_account_handler
+        ))
+        .routes(routes!(crate::bridge::handlers::auth::change_email_handler))
+        .routes(routes!(
+            crate::bridge::handlers::auth::change_password_handler
+        ))
+        .routes(routes!(crate::bridge::handlers::auth::mfa_status_handler))
+        .routes(routes!(crate::bridge::handlers::auth::mfa_setup_handler))
+        .routes(routes!(crate::bridge::handlers::auth::mfa_enable_handler))
+        .routes(routes!(crate::bridge::handlers::auth::mfa_disable_handler))
+        .routes(routes!(
+            crate::bridge::handlers::auth::mfa_recovery_codes_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::list_api_keys_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::create_api_key_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::revoke_api_key_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::list_sessions_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::revoke_other_sessions_handler
+        ))
+        .routes(routes!(
+            crate::bridge::handlers::auth::revoke_session_handler
+        ))
         .route_layer(middleware::from_fn_with_state(db.clone(), auth_middleware));
 
     // Combine both route groups - retains the middleware layers
     public_routes.merge(protected_routes).with_state(db)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        extract::Request,
+        http::{Method, StatusCode},
+        middleware::Next,
+        routing::{delete, post},
+    };
+    use tower::ServiceExt;
+
+    use crate::bridge::{
+        handlers::auth::*,
+        types::auth::{ApiKeyAuth, AuthUser},
+    };
+
+    /// Account security settings must need the user's own session, not one of their API keys
+    #[tokio::test]
+    async fn api_keys_cant_change_mfa_sessions_or_keys() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        // Stands in for auth_middleware accepting an API key
+        let as_api_key = |mut request: Request, next: Next| async move {
+            request.extensions_mut().insert(AuthUser {
+                user_id: uuid::Uuid::new_v4(),
+                session_id: None,
+                impersonator_id: None,
+            });
+            request.extensions_mut().insert(ApiKeyAuth {
+                key_id: uuid::Uuid::new_v4(),
+                scopes: Vec::new(),
+            });
+            next.run(request).await
+        };
+        let app = Router::new()
+            .route("/mfa/setup", post(mfa_setup_handler))
+            .route("/mfa/enable", post(mfa_enable_handler))
+            .route("/mfa/disable", post(mfa_disable_handler))
+            .route("/mfa/recovery-codes", post(mfa_recovery_codes_handler))
+            .route("/sessions", delete(revoke_other_sessions_handler))
+            .route("/sessions/{session_id}", delete(revoke_session_handler))
+            .route("/api-keys/{key_id}", delete(revoke_api_key_handler))
+            .layer(middleware::from_fn(as_api_key))
+            .with_state(db);
+
+        let session_path = format!("/sessions/{}", uuid::Uuid::new_v4());
+        let key_path = format!("/api-keys/{}", uuid::Uuid::new_v4());
+        let routes = [
+            (Method::POST, "/mfa/setup"),
+            (Method::POST, "/mfa/enable"),
+            (Method::POST, "/mfa/disable"),
+            (Method::POST, "/mfa/recovery-codes"),
+            (Method::DELETE, "/sessions"),
+            (Method::DELETE, session_path.as_str()),
+            (Method::DELETE, key_path.as_str()),
+        ];
+        for (method, path) in routes {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(path)
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"code": "123456"}"#))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::FORBIDDEN,
+                "{} {}",
+                method,
+                path
+            );
+        }
+    }
+}
@@ -1,6 +1,15 @@
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::{
+    bridge::types::auth::MfaChallengeResponse,
+    domain::{
+        permissions::PermissionEffect,
+        session::{SessionLimitAction, describe_user_agent},
+    },
+    entity::models::user_sessions,
+};
+
 pub const ADMIN_TAG: &str = "Admin";
 
 // Admin Authentication
@@ -13,8 +22,20 @@ pub struct AdminLoginRequest {
 #[derive(Serialize, ToSchema)]
 pub struct AdminLoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
     pub admin_id: String,
     pub email: String,
+    /// MFA recovery codes, only returned when the login also completed MFA enrollment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+/// Outcome of the admin password check
+pub enum AdminLoginResult {
+    Authenticated(AdminLoginResponse),
+    MfaRequired(MfaChallengeResponse),
 }
 
 // Pagination
@@ -60,6 +81,8 @@ pub struct AuditLogResponse {
     pub request_body: Option<String>,
     pub response_body: Option<String>,
     pub error_message: Option<String>,
+    /// Admin who acted as user_id while impersonating them
+    pub actor_id: Option<String>,
 }
 
 // User Management
@@ -95,6 +118,60 @@ pub struct UserResponse {
     pub role_name: Option<String>,
 }
 
+/// Why an admin signs in as a user, kept in the audit log
+#[derive(Deserialize, ToSchema)]
+pub struct ImpersonateUserRequest {
+    pub reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub refresh_token: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    pub user_id: String,
+    pub email: String,
+    /// When the impersonation session ends, refreshing can't extend it
+    pub impersonation_expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Accounts whose email addresses normalize to the same address
+#[derive(Serialize, ToSchema)]
+pub struct EmailDuplicateResponse {
+    pub email_normalized: String,
+    /// The account holding the address first, the others can only sign in with their exact address
+    pub users: Vec<UserResponse>,
+    /// The stored address doesn't parse, so it can't be normalized at all
+    pub invalid: bool,
+}
+
+/// Users migrated from another system, imported with their existing password hashes
+#[derive(Deserialize, ToSchema)]
+pub struct ImportUsersRequest {
+    pub users: Vec<ImportUserRequest>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ImportUserRequest {
+    pub email: String,
+    /// Argon2 (PHC string) or bcrypt ($2a$, $2b$, $2y$) hash
+    pub password_hash: String,
+    pub role_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportUsersResponse {
+    pub imported: Vec<UserResponse>,
+    pub failed: Vec<ImportUserFailure>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportUserFailure {
+    pub email: String,
+    pub message: String,
+}
+
 // Database Inspection
 #[derive(Serialize, ToSchema)]
 pub struct DatabaseTableResponse {
@@ -195,16 +272,69 @@ pub struct RoleResponse {
     pub name: String,
     pub description: Option<String>,
     pub permissions: Vec<String>,
+    /// Whether members of this role must sign in with MFA
+    pub mfa_required: bool,
+    /// Role this role inherits permissions from
+    pub parent_role_id: Option<i32>,
+    /// Most sessions a member may have at once, the server default applies if empty
+    pub max_sessions: Option<i32>,
+    /// "evict" signs out the oldest session at the limit, "reject" refuses the new login
+    pub session_limit_action: Option<String>,
+    /// Minutes without activity after which a session ends
+    pub idle_timeout_minutes: Option<i32>,
+    /// Hours after sign in after which a session ends
+    pub session_lifetime_hours: Option<i32>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
 
+/// Per-user permission grant or deny
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserPermissionResponse {
+    #[schema(value_type = String)]
+    pub id: uuid::Uuid,
+    pub permission: String,
+    /// "grant" or "deny", a deny wins over roles and grants
+    #[schema(value_type = String, example = "grant")]
+    pub effect: PermissionEffect,
+    /// When the record stops applying, never if empty
+    pub expires_at: Option<String>,
+    pub reason: Option<String>,
+    /// Admin who created the record
+    pub created_by: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// Grant or deny a permission for a single user
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateUserPermissionRequest {
+    #[schema(example = "admin:database")]
+    pub permission: String,
+    #[schema(value_type = String, example = "grant")]
+    pub effect: PermissionEffect,
+    /// RFC 3339 timestamp after which the record no longer applies
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reason: Option<String>,
+}
+
 /// Create role request
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateRoleRequest {
     pub name: String,
     pub description: Option<String>,
     pub permissions: Vec<String>,
+    /// Require members of this role to sign in with MFA (defaults to false)
+    pub mfa_required: Option<bool>,
+    /// Inherit the permissions of this role
+    pub parent_role_id: Option<i32>,
+    /// Limit members to this many sessions at once
+    pub max_sessions: Option<i32>,
+    #[schema(value_type = Option<String>, example = "evict")]
+    pub session_limit_action: Option<SessionLimitAction>,
+    /// End sessions unused for this many minutes
+    pub idle_timeout_minutes: Option<i32>,
+    /// End sessions this many hours after sign in
+    pub session_lifetime_hours: Option<i32>,
 }
 
 /// Update role request
@@ -213,6 +343,33 @@ pub struct UpdateRoleRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub permissions: Option<Vec<String>>,
+    pub mfa_required: Option<bool>,
+    /// New parent role, `null` removes the parent and leaving it out keeps the current one
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<i32>)]
+    pub parent_role_id: Option<Option<i32>>,
+    /// Session settings, `null` falls back to the server default
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<i32>)]
+    pub max_sessions: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<String>, example = "reject")]
+    pub session_limit_action: Option<Option<SessionLimitAction>>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<i32>)]
+    pub idle_timeout_minutes: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<i32>)]
+    pub session_lifetime_hours: Option<Option<i32>>,
+}
+
+/// Tells an explicit `null` apart from a missing field, which serde maps to `None`
+fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 /// Role query parameters
@@ -249,6 +406,37 @@ pub struct SessionResponse {
     pub last_activity: String,
     pub expires_at: String,
     pub is_current: bool, // If this is the current session
+    /// Admin who started the session to impersonate the user
+    pub impersonator_id: Option<String>,
+}
+
+impl SessionResponse {
+    /// Converts a session row, `current_session_id` is the JWT session ID of the caller
+    pub fn from_session(
+        session: user_sessions::Model,
+        current_session_id: Option<uuid::Uuid>,
+    ) -> Self {
+        let is_current =
+            current_session_id.is_some_and(|current| session.session_token == current.to_string());
+
+        Self {
+            id: session.id.to_string(),
+            user_id: session.user_id.to_string(),
+            device_info: describe_user_agent(session.user_agent.as_deref()),
+            ip_address: session.ip_address,
+            created_at: session
+                .created_at
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_default(),
+            last_activity: session
+                .last_activity
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_default(),
+            expires_at: session.expires_at.to_rfc3339(),
+            is_current,
+            impersonator_id: session.impersonator_id.map(|id| id.to_string()),
+        }
+    }
 }
 
 /// Request to invalidate a session
@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::domain::{
+    auth::{MfaChallenge, MfaSetup},
+    permissions::Permission,
+};
+use crate::entity::models::api_keys;
+use crate::infrastructure::jwt_keys::PublicJwk;
+
 pub const AUTH_TAG: &str = "Authentication";
 
 // Request/Response types
@@ -24,9 +31,81 @@ pub struct LoginRequest {
 
 #[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
+    /// Short-lived JWT access token
+    pub token: String,
+    /// Opaque refresh token, exchanged at /refresh for a new token pair
+    pub refresh_token: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    /// MFA recovery codes, only returned when the login also completed MFA enrollment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    /// May be left out when session cookies are enabled, the refresh cookie is used instead
+    #[serde(default)]
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token from the verification email
     pub token: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    /// Email address of the account to reset
+    #[schema(example = "user@example.com")]
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token from the password reset email
+    pub token: String,
+
+    /// The new password
+    #[schema(example = "newpassword123")]
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmEmailChangeRequest {
+    /// Token from the email sent to the new address
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangeEmailRequest {
+    /// Address to change to, it has to be confirmed before it's used
+    #[schema(example = "new@example.com")]
+    pub new_email: String,
+    pub current_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    #[schema(example = "newpassword123")]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    pub current_password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeleteAccountResponse {
+    pub message: String,
+    /// When the account is deleted for good, signing in before then keeps it
+    #[schema(value_type = String, example = "2024-02-19T15:30:00Z")]
+    pub deletion_scheduled_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RegisterResponse {
@@ -53,6 +132,19 @@ pub struct ProfileResponse {
     pub id: String,
     pub email: String,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// New email address waiting for confirmation
+    pub pending_email: Option<String>,
+    /// Set while an admin is signed in as this user
+    pub impersonated_by: Option<ImpersonationInfo>,
+}
+
+/// Admin behind an impersonation session
+#[derive(Serialize, ToSchema)]
+pub struct ImpersonationInfo {
+    pub admin_id: String,
+    pub admin_email: String,
+    /// When the impersonation session ends
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 // JWT token extractor
@@ -60,4 +152,171 @@ pub struct ProfileResponse {
 pub struct AuthUser {
     #[schema(value_type = String)]
     pub user_id: uuid::Uuid,
+    /// Session the JWT belongs to, None for API keys
+    #[schema(value_type = Option<String>)]
+    pub session_id: Option<uuid::Uuid>,
+    /// Admin impersonating the user, from the token's `act` claim
+    #[schema(value_type = Option<String>)]
+    pub impersonator_id: Option<uuid::Uuid>,
+}
+
+/// Set next to `AuthUser` when the request was authenticated with an API key
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    pub key_id: uuid::Uuid,
+    /// Permissions the key is limited to
+    pub scopes: Vec<Permission>,
+}
+
+/// JSON Web Key Set
+#[derive(Serialize, ToSchema)]
+pub struct JwksResponse {
+    pub keys: Vec<PublicJwk>,
+}
+
+/// Returned by login when a second factor is needed
+#[derive(Serialize, ToSchema)]
+pub struct MfaChallengeResponse {
+    /// Always true, distinguishes the challenge from a finished login
+    pub mfa_required: bool,
+    /// Challenge token, exchanged together with a code at /mfa/verify
+    pub mfa_token: String,
+    /// Seconds until the challenge expires
+    pub expires_in: i64,
+    /// Set when the user's role requires MFA and they still have to enroll
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup: Option<MfaSetupResponse>,
+}
+
+impl From<MfaChallenge> for MfaChallengeResponse {
+    fn from(challenge: MfaChallenge) -> Self {
+        Self {
+            mfa_required: true,
+            expires_in: challenge.expires_in(),
+            mfa_token: challenge.token,
+            setup: challenge.setup.map(MfaSetupResponse::from),
+        }
+    }
+}
+
+/// TOTP secret for authenticator apps
+#[derive(Serialize, ToSchema)]
+pub struct MfaSetupResponse {
+    /// Base32 secret for manual entry
+    #[schema(example = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP")]
+    pub secret: String,
+    /// Provisioning URI, render it as a QR code
+    #[schema(
+        example = "otpauth://totp/Rext:user%40example.com?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=Rext&algorithm=SHA1&digits=6&period=30"
+    )]
+    pub otpauth_uri: String,
+}
+
+impl From<MfaSetup> for MfaSetupResponse {
+    fn from(setup: MfaSetup) -> Self {
+        Self {
+            secret: setup.secret,
+            otpauth_uri: setup.otpauth_uri,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MfaVerifyRequest {
+    /// Challenge token returned by login
+    pub mfa_token: String,
+    /// TOTP code or recovery code
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MfaCodeRequest {
+    /// TOTP code, or a recovery code where accepted
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MfaRecoveryCodesResponse {
+    /// One-time recovery codes, store them somewhere safe, they won't be shown again
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MfaStatusResponse {
+    /// Whether MFA is enabled for the account
+    pub enabled: bool,
+    /// Whether the user's role requires MFA
+    pub required: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Label to recognize the key by, e.g. the CI job using it
+    #[schema(example = "nightly-ci")]
+    pub name: String,
+    /// Permissions the key is limited to, on top of what the user holds
+    #[schema(example = json!(["admin:logs"]))]
+    pub scopes: Vec<String>,
+    /// When the key stops working, never if empty
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    /// Start of the key, enough to tell keys apart
+    #[schema(example = "rext_1a2b3c4d")]
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: Option<String>,
+}
+
+impl From<api_keys::Model> for ApiKeyResponse {
+    fn from(api_key: api_keys::Model) -> Self {
+        Self {
+            id: api_key.id.to_string(),
+            name: api_key.name,
+            prefix: api_key.prefix,
+            scopes: serde_json::from_str(&api_key.scopes).unwrap_or_default(),
+            expires_at: api_key.expires_at.map(|dt| dt.to_rfc3339()),
+            last_used_at: api_key.last_used_at.map(|dt| dt.to_rfc3339()),
+            created_at: api_key.created_at.map(|dt| dt.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    /// The full key, send it as `X-Api-Key` or `Authorization: ApiKey <key>`. It won't be shown again
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcProviderResponse {
+    /// Name to use in the authorize and callback paths
+    #[schema(example = "google")]
+    pub name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OidcAuthorizeResponse {
+    /// Where to send the browser to sign in at the provider
+    pub authorization_url: String,
+    /// Echoed back by the provider, pass it to the callback unchanged
+    pub state: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OidcCallbackRequest {
+    /// The `code` query parameter of the provider's redirect
+    pub code: String,
+    /// The `state` query parameter of the provider's redirect
+    pub state: String,
 }
@@ -0,0 +1,438 @@
+//! Account self-service
+//!
+//! Lets users change their own email address and password and delete their account. Each change
+//! needs the current password and is written to the audit log. Deleted accounts are kept for a
+//! grace period, signing in again before it ends keeps the account.
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::prelude::{DateTimeWithTimeZone, Expr};
+use sea_orm::*;
+use std::env;
+use uuid::Uuid;
+
+use crate::control::services::{
+    api_key_service::ApiKeyService, audit_service::AuditService,
+    login_throttle_service::LoginThrottleService, session_service::SessionService,
+    user_service::UserService, verification_service::VerificationService,
+};
+use crate::domain::{audit::AuditEvent, auth::LoginAttempt, user::User, validation::*};
+use crate::entity::models::{prelude::*, users};
+use crate::infrastructure::{
+    app_error::{AppError, ThrottledError},
+    job_queue::{JobQueueManager, Message},
+};
+
+/// Service for changes users make to their own account
+pub struct AccountService;
+
+impl AccountService {
+    /// Starts an email change, the new address has to be confirmed through the mailed link
+    pub async fn request_email_change(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        current_password: &str,
+        new_email: &str,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<(), ThrottledError> {
+        let attempt = LoginAttempt::for_user(
+            user_id,
+            "/api/v1/auth/profile/email",
+            ip_address.clone(),
+            user_agent.clone(),
+        );
+        let user = Self::verified_user(db, user_id, current_password, &attempt).await?;
+
+        let new_email = new_email.trim();
+        let new_email_normalized = validate_signup_email(new_email)?;
+        if normalize_email(&user.email).is_ok_and(|current| current == new_email_normalized) {
+            return Err(AppError {
+                message: "This is already your email address".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            }
+            .into());
+        }
+        Self::ensure_email_available(db, user_id, new_email).await?;
+
+        Users::update_many()
+            .col_expr(users::Column::PendingEmail, Expr::value(new_email))
+            .filter(users::Column::Id.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+
+        VerificationService::send_email_change(db, &user, new_email).await?;
+
+        Self::audit(
+            db,
+            "EMAIL_CHANGE_REQUEST",
+            "/api/v1/auth/profile/email",
+            user_id,
+            ip_address,
+            user_agent,
+            format!(
+                "Email change from {} to {} requested",
+                user.email, new_email
+            ),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Completes an email change with the token mailed to the new address
+    /// The new address counts as verified, since the link proves the user can read its mail
+    pub async fn confirm_email_change(
+        db: &DatabaseConnection,
+        token: &str,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<(), AppError> {
+        let user_id = VerificationService::confirm_email_change(db, token).await?;
+
+        let user = Users::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|_| Self::database_error())?
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+        let new_email = user.pending_email.clone().ok_or(AppError {
+            message: "No email change pending".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        })?;
+
+        // Someone may have registered the address since the change was requested
+        Self::ensure_email_available(db, user_id, &new_email).await?;
+
+        let old_email = user.email.clone();
+        let mut user: users::ActiveModel = user.into();
+        user.email_normalized = Set(Some(normalize_email(&new_email)?));
+        user.email = Set(new_email.clone());
+        user.pending_email = Set(None);
+        user.email_verified_at = Set(Some(Utc::now().fixed_offset()));
+        user.update(db).await.map_err(|_| AppError {
+            message: "Email already taken".to_string(),
+            status_code: StatusCode::CONFLICT,
+        })?;
+
+        Self::audit(
+            db,
+            "EMAIL_CHANGE",
+            "/api/v1/auth/confirm-email-change",
+            user_id,
+            ip_address,
+            user_agent,
+            format!("Email changed from {} to {}", old_email, new_email),
+        )
+        .await
+    }
+
+    /// Replaces the user's password and signs out every other session
+    /// Without a current session, e.g. when called with an API key, all sessions are signed out.
+    /// Returns how many sessions were signed out
+    pub async fn change_password(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        current_session_id: Option<Uuid>,
+        current_password: &str,
+        new_password: &str,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<u64, ThrottledError> {
+        let attempt = LoginAttempt::for_user(
+            user_id,
+            "/api/v1/auth/profile/password",
+            ip_address.clone(),
+            user_agent.clone(),
+        );
+        let user = Self::verified_user(db, user_id, current_password, &attempt).await?;
+
+        if current_password == new_password {
+            return Err(AppError {
+                message: "New password must be different from the current one".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            }
+            .into());
+        }
+        UserService::set_password(db, user_id, new_password).await?;
+
+        let signed_out = match current_session_id {
+            Some(session_id) => {
+                SessionService::invalidate_other_user_sessions(db, user_id, session_id).await?
+            }
+            None => SessionService::invalidate_all_user_sessions(db, user_id).await?,
+        };
+
+        Self::notify(
+            &user,
+            "Your password was changed",
+            "The password of your account was just changed and your other devices were signed out. If you didn't do this, reset your password right away.".to_string(),
+        )
+        .await;
+
+        Self::audit(
+            db,
+            "PASSWORD_CHANGE",
+            "/api/v1/auth/profile/password",
+            user_id,
+            ip_address,
+            user_agent,
+            format!(
+                "Password changed by {}, {} other sessions signed out",
+                user.email, signed_out
+            ),
+        )
+        .await?;
+
+        Ok(signed_out)
+    }
+
+    /// Schedules the user's account for deletion after the grace period
+    /// Signs out every session and revokes all API keys right away.
+    /// Returns when the account will be deleted
+    pub async fn schedule_deletion(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        current_password: &str,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<DateTime<Utc>, ThrottledError> {
+        let attempt = LoginAttempt::for_user(
+            user_id,
+            "/api/v1/auth/profile",
+            ip_address.clone(),
+            user_agent.clone(),
+        );
+        let user = Self::verified_user(db, user_id, current_password, &attempt).await?;
+
+        let delete_at = Utc::now() + Self::deletion_grace_period();
+        Users::update_many()
+            .col_expr(
+                users::Column::DeletionScheduledAt,
+                Expr::value(delete_at.fixed_offset()),
+            )
+            .filter(users::Column::Id.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+
+        SessionService::invalidate_all_user_sessions(db, user_id).await?;
+        ApiKeyService::revoke_all(db, user_id).await?;
+
+        Self::notify(
+            &user,
+            "Your account will be deleted",
+            format!(
+                "Your account is scheduled for deletion on {}. Sign in before then if you want to keep it.",
+                delete_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        )
+        .await;
+
+        Self::audit(
+            db,
+            "ACCOUNT_DELETE_REQUEST",
+            "/api/v1/auth/profile",
+            user_id,
+            ip_address,
+            user_agent,
+            format!(
+                "Account {} scheduled for deletion at {}",
+                user.email,
+                delete_at.to_rfc3339()
+            ),
+        )
+        .await?;
+
+        Ok(delete_at)
+    }
+
+    /// Keeps an account that was scheduled for deletion, called whenever the user signs in
+    pub async fn cancel_deletion(db: &DatabaseConnection, user_id: Uuid) -> Result<(), AppError> {
+        let result = Users::update_many()
+            .col_expr(
+                users::Column::DeletionScheduledAt,
+                Expr::value(Option::<DateTimeWithTimeZone>::None),
+            )
+            .filter(users::Column::Id.eq(user_id))
+            .filter(users::Column::DeletionScheduledAt.is_not_null())
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+
+        if result.rows_affected == 0 {
+            return Ok(());
+        }
+
+        Self::audit(
+            db,
+            "ACCOUNT_DELETE_CANCEL",
+            "/api/v1/auth/login",
+            user_id,
+            None,
+            None,
+            format!(
+                "Scheduled deletion of account {} cancelled by signing in",
+                user_id
+            ),
+        )
+        .await
+    }
+
+    /// Deletes accounts whose grace period has ended, returns how many were deleted
+    pub async fn purge_deleted_accounts(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let due = Users::find()
+            .filter(users::Column::DeletionScheduledAt.lte(Utc::now().fixed_offset()))
+            .all(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+
+        let mut deleted = 0;
+        for user in due {
+            UserService::delete_user(db, user.id).await?;
+            deleted += 1;
+
+            // The audit record outlives the user, so it isn't linked to it
+            AuditService::record(
+                db,
+                AuditEvent {
+                    action: "ACCOUNT_DELETE".to_string(),
+                    path: "/api/v1/auth/profile".to_string(),
+                    status_code: None,
+                    user_id: None,
+                    actor_id: None,
+                    ip_address: None,
+                    user_agent: None,
+                    message: format!(
+                        "Account {} ({}) deleted after its grace period",
+                        user.email, user.id
+                    ),
+                },
+            )
+            .await?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Email address the user asked to change to and hasn't confirmed yet
+    pub async fn pending_email(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Option<String>, AppError> {
+        Ok(Users::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|_| Self::database_error())?
+            .and_then(|user| user.pending_email))
+    }
+
+    /// How long a deleted account is kept, configured with ACCOUNT_DELETION_GRACE_DAYS
+    /// (defaults to 30)
+    pub fn deletion_grace_period() -> Duration {
+        let days = env::var("ACCOUNT_DELETION_GRACE_DAYS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|days| *days >= 0)
+            .unwrap_or(30);
+        Duration::days(days)
+    }
+
+    /// Loads the user and checks their current password
+    /// Checks the current password, wrong guesses are throttled like failed logins
+    async fn verified_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        current_password: &str,
+        attempt: &LoginAttempt,
+    ) -> Result<User, ThrottledError> {
+        LoginThrottleService::protect_password_check(db, attempt, async {
+            let user = UserService::find_user_by_id(db, user_id)
+                .await?
+                .ok_or(AppError {
+                    message: "User not found".to_string(),
+                    status_code: StatusCode::NOT_FOUND,
+                })?;
+
+            if current_password.is_empty()
+                || !UserService::verify_password(db, &user, current_password).await?
+            {
+                return Err(AppError {
+                    message: "Current password is incorrect".to_string(),
+                    status_code: StatusCode::FORBIDDEN,
+                });
+            }
+
+            Ok(user)
+        })
+        .await
+    }
+
+    async fn ensure_email_available(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        email: &str,
+    ) -> Result<(), AppError> {
+        if UserService::find_user_by_email(db, email)
+            .await?
+            .is_some_and(|user| user.id != user_id)
+        {
+            return Err(AppError {
+                message: "Email already taken".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+        Ok(())
+    }
+
+    /// Mails a security notice, a failed mail doesn't undo the change
+    async fn notify(user: &User, subject: &str, text: String) {
+        let message = Message {
+            to: user.email.clone(),
+            subject: subject.to_string(),
+            text,
+        };
+        if let Err(e) = JobQueueManager::enqueue(message).await {
+            tracing::warn!(
+                "Failed to queue account notice for {}: {}",
+                user.id,
+                e.message
+            );
+        }
+    }
+
+    async fn audit(
+        db: &DatabaseConnection,
+        action: &str,
+        path: &str,
+        user_id: Uuid,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        message: String,
+    ) -> Result<(), AppError> {
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: action.to_string(),
+                path: path.to_string(),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(user_id),
+                actor_id: None,
+                ip_address,
+                user_agent,
+                message,
+            },
+        )
+        .await
+    }
+
+    fn database_error() -> AppError {
+        AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -3,24 +3,31 @@ use sea_orm::*;
 use uuid::Uuid;
 
 use crate::{
-    bridge::types::admin::*,
+    bridge::types::{admin::*, auth::MfaVerifyRequest},
     control::services::{
-        database_service::DatabaseMonitorService, session_service::SessionService,
-        system_monitor::SystemMonitorService, user_service::UserService,
+        audit_service::AuditService, auth_service::AuthService,
+        database_service::DatabaseMonitorService, login_throttle_service::LoginThrottleService,
+        mfa_service::MfaService, permission_service::PermissionService,
+        session_service::SessionService, system_monitor::SystemMonitorService,
+        user_service::UserService,
     },
-    domain::validation::*,
-    entity::models::{audit_logs, roles, users},
-    infrastructure::{app_error::AppError, jwt_claims::Claims},
+    domain::{
+        audit::AuditEvent,
+        auth::MfaScope,
+        permissions::{Permission, PermissionEffect, PermissionSet},
+        validation::*,
+    },
+    entity::models::{audit_logs, roles, user_permissions, user_roles, users},
+    infrastructure::app_error::AppError,
 };
 use axum::http::StatusCode;
-use jsonwebtoken::{EncodingKey, Header, encode};
-use std::env;
 
 /// Service for admin-related business operations
 pub struct AdminService;
 
 impl AdminService {
-    /// Authenticates an admin user and returns a JWT token
+    /// Authenticates an admin user and returns an access token and refresh token,
+    /// or an MFA challenge when the admin has MFA enabled or their role requires it
     /// Specifically for "super admin" privileges, defined with the "*" permission
     /// Other "admin" permissions like "admin:read" are handled by the user_can_perform_action function
     ///
@@ -36,7 +43,7 @@ impl AdminService {
         login: AdminLoginRequest,
         user_agent: Option<String>,
         ip_address: Option<String>,
-    ) -> Result<AdminLoginResponse, AppError> {
+    ) -> Result<AdminLoginResult, AppError> {
         // Validate input
         validate_login_input(&login.email, &login.password)?;
 
@@ -49,7 +56,7 @@ impl AdminService {
             })?;
 
         // Verify password
-        let is_valid = UserService::verify_password(&user, &login.password)?;
+        let is_valid = UserService::verify_password(db, &user, &login.password).await?;
         if !is_valid {
             return Err(AppError {
                 message: "Invalid credentials".to_string(),
@@ -57,36 +64,57 @@ impl AdminService {
             });
         }
 
-        // Generate session ID and JWT token
-        let session_id = Uuid::new_v4();
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
-        let encoding_key = EncodingKey::from_secret(jwt_secret.as_ref());
+        AuthService::ensure_password_current(db, &user).await?;
 
-        let claims = Claims {
-            sub: user.id.to_string(),
-            exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
-            session_id: session_id.to_string(),
-        };
+        // Admins with MFA finish signing in through complete_admin_mfa
+        if let Some(challenge) = MfaService::challenge_for(db, &user, MfaScope::Admin).await? {
+            return Ok(AdminLoginResult::MfaRequired(challenge.into()));
+        }
 
-        let token = encode(&Header::default(), &claims, &encoding_key).map_err(|_| AppError {
-            message: "Failed to generate token".to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+        // Create the session and issue access and refresh tokens
+        let auth_token = AuthService::start_session(db, user.id, user_agent, ip_address).await?;
 
-        // Create session record
-        SessionService::create_session(
+        Ok(AdminLoginResult::Authenticated(AdminLoginResponse {
+            expires_in: auth_token.expires_in(),
+            token: auth_token.token,
+            refresh_token: auth_token.refresh_token,
+            admin_id: user.id.to_string(),
+            email: user.email,
+            recovery_codes: None,
+        }))
+    }
+
+    /// Completes an admin login by exchanging the MFA challenge and a code
+    pub async fn complete_admin_mfa(
+        db: &DatabaseConnection,
+        request: MfaVerifyRequest,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<AdminLoginResponse, AppError> {
+        let mfa_login = AuthService::complete_mfa_login(
             db,
-            user.id,
+            &request.mfa_token,
+            &request.code,
+            MfaScope::Admin,
             user_agent,
             ip_address,
-            &session_id.to_string(),
         )
         .await?;
 
+        let user = UserService::find_user_by_id(db, mfa_login.auth_token.user_id)
+            .await?
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
         Ok(AdminLoginResponse {
-            token,
+            expires_in: mfa_login.auth_token.expires_in(),
+            token: mfa_login.auth_token.token,
+            refresh_token: mfa_login.auth_token.refresh_token,
             admin_id: user.id.to_string(),
             email: user.email,
+            recovery_codes: mfa_login.recovery_codes,
         })
     }
 
@@ -159,6 +187,7 @@ impl AdminService {
                 request_body: log.request_body,
                 response_body: log.response_body,
                 error_message: log.error_message,
+                actor_id: log.actor_id.map(|id| id.to_string()),
             })
             .collect();
 
@@ -260,11 +289,16 @@ impl AdminService {
         })
     }
 
-    /// Create a new user using UserService
+    /// Create a new user using UserService, the role can't hold more than the caller
     pub async fn create_user(
         db: &DatabaseConnection,
         request: CreateUserRequest,
+        caller: &PermissionSet,
     ) -> Result<UserResponse, AppError> {
+        if let Some(role_id) = request.role_id {
+            PermissionService::ensure_can_assign_role(db, caller, role_id).await?;
+        }
+
         let user = UserService::create_user_with_role(
             db,
             request.email,
@@ -282,12 +316,188 @@ impl AdminService {
         })
     }
 
-    /// Update a user using UserService
+    /// Starts a time limited session as another user, for support staff reproducing problems.
+    /// Admins can't be impersonated, so impersonation never grants more than the admin already has
+    pub async fn impersonate_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        request: ImpersonateUserRequest,
+        admin_user: &AdminUser,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<ImpersonationResponse, AppError> {
+        let reason = request.reason.trim();
+        if reason.is_empty() {
+            return Err(AppError {
+                message: "A reason for the impersonation is required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+        if user_id == admin_user.user_id {
+            return Err(AppError {
+                message: "You can't impersonate yourself".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let user = UserService::find_user_by_id(db, user_id)
+            .await?
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        let admin_permissions: Vec<Permission> = PermissionService::get_all_permissions()
+            .into_iter()
+            .filter(|permission| matches!(permission.category(), "super" | "admin"))
+            .collect();
+        if PermissionService::has_any_permission(db, user_id, &admin_permissions).await? {
+            return Err(AppError {
+                message: "Admins can't be impersonated".to_string(),
+                status_code: StatusCode::FORBIDDEN,
+            });
+        }
+
+        let (auth_token, ends_at) = AuthService::start_impersonation(
+            db,
+            user_id,
+            admin_user.user_id,
+            user_agent.clone(),
+            ip_address.clone(),
+        )
+        .await?;
+
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "IMPERSONATE".to_string(),
+                path: format!("/api/v1/admin/users/{}/impersonate", user_id),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(user_id),
+                actor_id: Some(admin_user.user_id),
+                ip_address,
+                user_agent,
+                message: format!(
+                    "{} started impersonating {} until {}: {}",
+                    admin_user.email,
+                    user.email,
+                    ends_at.to_rfc3339(),
+                    reason
+                ),
+            },
+        )
+        .await?;
+
+        Ok(ImpersonationResponse {
+            expires_in: auth_token.expires_in(),
+            token: auth_token.token,
+            refresh_token: auth_token.refresh_token,
+            user_id: user.id.to_string(),
+            email: user.email,
+            impersonation_expires_at: ends_at,
+        })
+    }
+
+    /// Lists accounts whose email address couldn't be normalized, so admins can merge or remove them
+    pub async fn get_email_duplicates(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<EmailDuplicateResponse>, AppError> {
+        let groups = UserService::find_email_duplicates(db).await?;
+
+        Ok(groups
+            .into_iter()
+            .map(|(email_normalized, users)| EmailDuplicateResponse {
+                invalid: normalize_email(&email_normalized).is_err(),
+                email_normalized,
+                users: users
+                    .into_iter()
+                    .map(|user| UserResponse {
+                        id: user.id.to_string(),
+                        email: user.email,
+                        created_at: user.created_at.map(|t| t.to_rfc3339()),
+                        role_id: user.role_id,
+                        role_name: None,
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Imports users with existing password hashes, each user is imported on its own so one
+    /// bad entry doesn't stop the rest. Roles can't hold more than the caller
+    pub async fn import_users(
+        db: &DatabaseConnection,
+        request: ImportUsersRequest,
+        admin_user: &AdminUser,
+        caller: &PermissionSet,
+    ) -> Result<ImportUsersResponse, AppError> {
+        let mut imported = Vec::new();
+        let mut failed = Vec::new();
+
+        for entry in request.users {
+            let email = entry.email.trim().to_string();
+            let result = async {
+                if let Some(role_id) = entry.role_id {
+                    PermissionService::ensure_can_assign_role(db, caller, role_id).await?;
+                }
+                UserService::import_user_with_role(
+                    db,
+                    email.clone(),
+                    entry.password_hash,
+                    entry.role_id,
+                )
+                .await
+            }
+            .await;
+            match result {
+                Ok(user) => imported.push(UserResponse {
+                    id: user.id.to_string(),
+                    email: user.email,
+                    created_at: user.created_at.map(|t| t.to_rfc3339()),
+                    role_id: entry.role_id,
+                    role_name: None,
+                }),
+                Err(e) => failed.push(ImportUserFailure {
+                    email,
+                    message: e.message,
+                }),
+            }
+        }
+
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "USER_IMPORT".to_string(),
+                path: "/api/v1/admin/users/import".to_string(),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(admin_user.user_id),
+                actor_id: None,
+                ip_address: None,
+                user_agent: None,
+                message: format!(
+                    "{} users imported by {}, {} failed",
+                    imported.len(),
+                    admin_user.email,
+                    failed.len()
+                ),
+            },
+        )
+        .await?;
+
+        Ok(ImportUsersResponse { imported, failed })
+    }
+
+    /// Update a user using UserService, a new role can't hold more than the caller
     pub async fn update_user(
         db: &DatabaseConnection,
         user_id: Uuid,
         request: UpdateUserRequest,
+        caller: &PermissionSet,
     ) -> Result<UserResponse, AppError> {
+        if let Some(role_id) = request.role_id {
+            PermissionService::ensure_can_assign_role(db, caller, role_id).await?;
+        }
+
         let user = UserService::update_user(
             db,
             user_id,
@@ -622,6 +832,12 @@ impl AdminService {
                     name: role.name,
                     description: role.description,
                     permissions,
+                    mfa_required: role.mfa_required,
+                    parent_role_id: role.parent_role_id,
+                    max_sessions: role.max_sessions,
+                    session_limit_action: role.session_limit_action,
+                    idle_timeout_minutes: role.idle_timeout_minutes,
+                    session_lifetime_hours: role.session_lifetime_hours,
                     created_at: role.created_at.map(|dt| dt.to_rfc3339()),
                     updated_at: role.updated_at.map(|dt| dt.to_rfc3339()),
                 }
@@ -663,15 +879,22 @@ impl AdminService {
             name: role.name,
             description: role.description,
             permissions,
+            mfa_required: role.mfa_required,
+            parent_role_id: role.parent_role_id,
+            max_sessions: role.max_sessions,
+            session_limit_action: role.session_limit_action,
+            idle_timeout_minutes: role.idle_timeout_minutes,
+            session_lifetime_hours: role.session_lifetime_hours,
             created_at: role.created_at.map(|dt| dt.to_rfc3339()),
             updated_at: role.updated_at.map(|dt| dt.to_rfc3339()),
         })
     }
 
-    /// Create a new role
+    /// Create a new role, it can't hold more than the caller
     pub async fn create_role(
         db: &DatabaseConnection,
         request: CreateRoleRequest,
+        caller: &PermissionSet,
     ) -> Result<RoleResponse, AppError> {
         // Check if role name already exists
         let existing_role = roles::Entity::find()
@@ -690,6 +913,22 @@ impl AdminService {
             });
         }
 
+        if let Some(parent_role_id) = request.parent_role_id {
+            PermissionService::validate_parent_role(db, None, parent_role_id).await?;
+        }
+        PermissionService::ensure_can_grant_role(
+            db,
+            caller,
+            &request.permissions,
+            request.parent_role_id,
+        )
+        .await?;
+        Self::validate_session_settings(&[
+            request.max_sessions,
+            request.idle_timeout_minutes,
+            request.session_lifetime_hours,
+        ])?;
+
         // Convert permissions to JSON string
         let permissions_json =
             serde_json::to_string(&request.permissions).map_err(|_| AppError {
@@ -702,6 +941,14 @@ impl AdminService {
             name: Set(request.name),
             description: Set(request.description),
             permissions: Set(permissions_json),
+            mfa_required: Set(request.mfa_required.unwrap_or(false)),
+            parent_role_id: Set(request.parent_role_id),
+            max_sessions: Set(request.max_sessions),
+            session_limit_action: Set(request
+                .session_limit_action
+                .map(|action| action.as_str().to_string())),
+            idle_timeout_minutes: Set(request.idle_timeout_minutes),
+            session_lifetime_hours: Set(request.session_lifetime_hours),
             ..Default::default()
         };
 
@@ -715,16 +962,23 @@ impl AdminService {
             name: role.name,
             description: role.description,
             permissions: request.permissions,
+            mfa_required: role.mfa_required,
+            parent_role_id: role.parent_role_id,
+            max_sessions: role.max_sessions,
+            session_limit_action: role.session_limit_action,
+            idle_timeout_minutes: role.idle_timeout_minutes,
+            session_lifetime_hours: role.session_lifetime_hours,
             created_at: role.created_at.map(|dt| dt.to_rfc3339()),
             updated_at: role.updated_at.map(|dt| dt.to_rfc3339()),
         })
     }
 
-    /// Update an existing role
+    /// Update an existing role, permissions and a parent it gains can't exceed the caller's
     pub async fn update_role(
         db: &DatabaseConnection,
         role_id: i32,
         request: UpdateRoleRequest,
+        caller: &PermissionSet,
     ) -> Result<RoleResponse, AppError> {
         // Get existing role
         let role = roles::Entity::find_by_id(role_id)
@@ -759,6 +1013,28 @@ impl AdminService {
             }
         }
 
+        // What the role already holds stays, so editing a role above the caller's level still works
+        let current_permissions: Vec<String> =
+            serde_json::from_str(&role.permissions).unwrap_or_else(|_| vec![]);
+        let added_permissions: Vec<String> = request
+            .permissions
+            .iter()
+            .flatten()
+            .filter(|permission| !current_permissions.contains(permission))
+            .cloned()
+            .collect();
+        let new_parent_role_id = request
+            .parent_role_id
+            .flatten()
+            .filter(|parent_role_id| role.parent_role_id != Some(*parent_role_id));
+        PermissionService::ensure_can_grant_role(
+            db,
+            caller,
+            &added_permissions,
+            new_parent_role_id,
+        )
+        .await?;
+
         // Prepare update model
         let mut role_model: roles::ActiveModel = role.into();
 
@@ -778,6 +1054,35 @@ impl AdminService {
             role_model.permissions = Set(permissions_json);
         }
 
+        if let Some(mfa_required) = request.mfa_required {
+            role_model.mfa_required = Set(mfa_required);
+        }
+
+        if let Some(parent_role_id) = request.parent_role_id {
+            if let Some(parent_role_id) = parent_role_id {
+                PermissionService::validate_parent_role(db, Some(role_id), parent_role_id).await?;
+            }
+            role_model.parent_role_id = Set(parent_role_id);
+        }
+
+        Self::validate_session_settings(&[
+            request.max_sessions.flatten(),
+            request.idle_timeout_minutes.flatten(),
+            request.session_lifetime_hours.flatten(),
+        ])?;
+        if let Some(max_sessions) = request.max_sessions {
+            role_model.max_sessions = Set(max_sessions);
+        }
+        if let Some(action) = request.session_limit_action {
+            role_model.session_limit_action = Set(action.map(|action| action.as_str().to_string()));
+        }
+        if let Some(idle_timeout_minutes) = request.idle_timeout_minutes {
+            role_model.idle_timeout_minutes = Set(idle_timeout_minutes);
+        }
+        if let Some(session_lifetime_hours) = request.session_lifetime_hours {
+            role_model.session_lifetime_hours = Set(session_lifetime_hours);
+        }
+
         // Update timestamp
         role_model.updated_at = Set(Some(chrono::Utc::now().fixed_offset()));
 
@@ -796,11 +1101,28 @@ impl AdminService {
             name: updated_role.name,
             description: updated_role.description,
             permissions,
+            mfa_required: updated_role.mfa_required,
+            parent_role_id: updated_role.parent_role_id,
+            max_sessions: updated_role.max_sessions,
+            session_limit_action: updated_role.session_limit_action,
+            idle_timeout_minutes: updated_role.idle_timeout_minutes,
+            session_lifetime_hours: updated_role.session_lifetime_hours,
             created_at: updated_role.created_at.map(|dt| dt.to_rfc3339()),
             updated_at: updated_role.updated_at.map(|dt| dt.to_rfc3339()),
         })
     }
 
+    /// Session limits and timeouts have to be positive, leave them empty to use the defaults
+    fn validate_session_settings(settings: &[Option<i32>]) -> Result<(), AppError> {
+        if settings.iter().flatten().any(|value| *value <= 0) {
+            return Err(AppError {
+                message: "Session limits and timeouts must be greater than zero".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+        Ok(())
+    }
+
     /// Delete a role
     pub async fn delete_role(db: &DatabaseConnection, role_id: i32) -> Result<(), AppError> {
         // Check if role exists
@@ -816,7 +1138,7 @@ impl AdminService {
                 status_code: StatusCode::NOT_FOUND,
             })?;
 
-        // Check if role is assigned to any users
+        // Check if role is assigned to any users, as primary or additional role
         let users_with_role = users::Entity::find()
             .filter(users::Column::RoleId.eq(role_id))
             .count(db)
@@ -825,14 +1147,39 @@ impl AdminService {
                 message: format!("Database error: {}", e),
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             })?;
+        let assignments = user_roles::Entity::find()
+            .filter(user_roles::Column::RoleId.eq(role_id))
+            .count(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
 
-        if users_with_role > 0 {
+        if users_with_role + assignments > 0 {
             return Err(AppError {
                 message: "Cannot delete role: it is assigned to users".to_string(),
                 status_code: StatusCode::CONFLICT,
             });
         }
 
+        // Check if other roles inherit from it
+        let child_roles = roles::Entity::find()
+            .filter(roles::Column::ParentRoleId.eq(role_id))
+            .count(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if child_roles > 0 {
+            return Err(AppError {
+                message: "Cannot delete role: other roles inherit from it".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+
         // Delete the role
         roles::Entity::delete_by_id(role_id)
             .exec(db)
@@ -871,25 +1218,14 @@ impl AdminService {
 
         let (_, role_model) = user;
 
-        // Check role-based permissions
-        if let Some(role) = role_model {
-            let permissions: Vec<String> =
-                serde_json::from_str(&role.permissions).unwrap_or_else(|_| vec![]);
-
-            let has_permission =
-                permissions.contains(&"*".to_string()) || permissions.contains(&request.permission);
-
-            return Ok(PermissionCheckResponse {
-                has_permission,
-                user_role: Some(role.name),
-                required_permission: request.permission,
-            });
-        }
+        // All roles, inherited permissions, wildcards and the user's own grants and denies
+        let has_permission = PermissionService::get_user_permissions(db, user_id)
+            .await?
+            .contains(&Permission::from_string(&request.permission));
 
-        // No role assigned
         Ok(PermissionCheckResponse {
-            has_permission: false,
-            user_role: None,
+            has_permission,
+            user_role: role_model.map(|role| role.name),
             required_permission: request.permission,
         })
     }
@@ -914,37 +1250,18 @@ impl AdminService {
     }
 
     /// Get sessions for a specific user
+    /// `current_session_id` marks the calling admin's own session
     pub async fn get_user_sessions(
         db: &DatabaseConnection,
         user_id: Uuid,
+        current_session_id: Option<Uuid>,
     ) -> Result<Vec<SessionResponse>, AppError> {
-        // Get sessions from SessionService
         let sessions = SessionService::get_user_sessions(db, user_id).await?;
 
-        // Convert to response format
-        let session_responses: Vec<SessionResponse> = sessions
+        Ok(sessions
             .into_iter()
-            .map(|session| SessionResponse {
-                id: session.id.to_string(),
-                user_id: session.user_id.to_string(),
-                device_info: session
-                    .user_agent
-                    .unwrap_or_else(|| "Unknown Device".to_string()),
-                ip_address: session.ip_address,
-                created_at: session
-                    .created_at
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_default(),
-                last_activity: session
-                    .last_activity
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_default(),
-                expires_at: session.expires_at.to_rfc3339(),
-                is_current: false, // Will be determined on frontend based on current session
-            })
-            .collect();
-
-        Ok(session_responses)
+            .map(|session| SessionResponse::from_session(session, current_session_id))
+            .collect())
     }
 
     /// Invalidate a specific session
@@ -962,4 +1279,227 @@ impl AdminService {
     ) -> Result<u64, AppError> {
         SessionService::invalidate_all_user_sessions(db, user_id).await
     }
+
+    /// Lifts a login lockout on a user's account, returns whether one was in place
+    pub async fn unlock_user_login(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        admin_user: &AdminUser,
+    ) -> Result<bool, AppError> {
+        let user = UserService::find_user_by_id(db, user_id)
+            .await?
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        let unlocked = LoginThrottleService::unlock(db, user.id, &user.email).await?;
+        if unlocked {
+            AuditService::record(
+                db,
+                AuditEvent {
+                    action: "UNLOCK".to_string(),
+                    path: format!("/api/v1/admin/users/{}/unlock", user.id),
+                    status_code: Some(StatusCode::OK.as_u16() as i32),
+                    user_id: Some(admin_user.user_id),
+                    actor_id: None,
+                    ip_address: None,
+                    user_agent: None,
+                    message: format!(
+                        "Login lockout for {} cleared by {}",
+                        user.email, admin_user.email
+                    ),
+                },
+            )
+            .await?;
+        }
+
+        Ok(unlocked)
+    }
+
+    /// Lists every role assigned to a user, the primary role first
+    pub async fn get_user_roles(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<RoleResponse>, AppError> {
+        let role_ids = UserService::get_role_ids(db, user_id).await?;
+
+        let mut roles = Vec::with_capacity(role_ids.len());
+        for role_id in role_ids {
+            roles.push(Self::get_role(db, role_id).await?);
+        }
+
+        Ok(roles)
+    }
+
+    /// Assigns an additional role to a user, returns false if they already had it.
+    /// The role can't hold more than the caller
+    pub async fn assign_user_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+        admin_user: &AdminUser,
+        caller: &PermissionSet,
+    ) -> Result<bool, AppError> {
+        PermissionService::ensure_can_assign_role(db, caller, role_id).await?;
+
+        let assigned = UserService::assign_role(db, user_id, role_id).await?;
+        if assigned {
+            Self::audit_role_change(db, "ROLE_ASSIGN", user_id, role_id, admin_user).await?;
+        }
+        Ok(assigned)
+    }
+
+    /// Revokes one of a user's roles
+    pub async fn revoke_user_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+        admin_user: &AdminUser,
+    ) -> Result<(), AppError> {
+        UserService::revoke_role(db, user_id, role_id).await?;
+        Self::audit_role_change(db, "ROLE_REVOKE", user_id, role_id, admin_user).await
+    }
+
+    async fn audit_role_change(
+        db: &DatabaseConnection,
+        action: &str,
+        user_id: Uuid,
+        role_id: i32,
+        admin_user: &AdminUser,
+    ) -> Result<(), AppError> {
+        let verb = if action == "ROLE_ASSIGN" {
+            "assigned to"
+        } else {
+            "revoked from"
+        };
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: action.to_string(),
+                path: format!("/api/v1/admin/users/{}/roles/{}", user_id, role_id),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(admin_user.user_id),
+                actor_id: None,
+                ip_address: None,
+                user_agent: None,
+                message: format!(
+                    "Role {} {} user {} by {}",
+                    role_id, verb, user_id, admin_user.email
+                ),
+            },
+        )
+        .await
+    }
+
+    /// Lists a user's own permission grants and denies
+    pub async fn get_user_permission_overrides(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<UserPermissionResponse>, AppError> {
+        if UserService::find_user_by_id(db, user_id).await?.is_none() {
+            return Err(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            });
+        }
+
+        let records = PermissionService::get_user_overrides(db, user_id).await?;
+        Ok(records
+            .into_iter()
+            .map(Self::user_permission_response)
+            .collect())
+    }
+
+    /// Grants or denies a permission for a single user, only one the caller holds
+    pub async fn set_user_permission_override(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        request: CreateUserPermissionRequest,
+        admin_user: &AdminUser,
+        caller: &PermissionSet,
+    ) -> Result<UserPermissionResponse, AppError> {
+        PermissionService::ensure_can_grant(
+            caller,
+            &Permission::from_string(request.permission.trim()),
+        )?;
+
+        let record = PermissionService::set_user_override(
+            db,
+            user_id,
+            &request.permission,
+            request.effect,
+            request.expires_at,
+            request.reason,
+            admin_user.user_id,
+        )
+        .await?;
+
+        let until = record
+            .expires_at
+            .map(|dt| format!(" until {}", dt.to_rfc3339()))
+            .unwrap_or_default();
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "PERMISSION_SET".to_string(),
+                path: format!("/api/v1/admin/users/{}/permissions", user_id),
+                status_code: Some(StatusCode::CREATED.as_u16() as i32),
+                user_id: Some(admin_user.user_id),
+                actor_id: None,
+                ip_address: None,
+                user_agent: None,
+                message: format!(
+                    "Permission {} set to {} for user {}{} by {}",
+                    record.permission, record.effect, user_id, until, admin_user.email
+                ),
+            },
+        )
+        .await?;
+
+        Ok(Self::user_permission_response(record))
+    }
+
+    /// Removes a user's permission grant or deny
+    pub async fn remove_user_permission_override(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        override_id: Uuid,
+        admin_user: &AdminUser,
+    ) -> Result<(), AppError> {
+        let record = PermissionService::remove_user_override(db, user_id, override_id).await?;
+
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "PERMISSION_REMOVE".to_string(),
+                path: format!(
+                    "/api/v1/admin/users/{}/permissions/{}",
+                    user_id, override_id
+                ),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(admin_user.user_id),
+                actor_id: None,
+                ip_address: None,
+                user_agent: None,
+                message: format!(
+                    "Permission {} ({}) removed from user {} by {}",
+                    record.permission, record.effect, user_id, admin_user.email
+                ),
+            },
+        )
+        .await
+    }
+
+    fn user_permission_response(record: user_permissions::Model) -> UserPermissionResponse {
+        UserPermissionResponse {
+            id: record.id,
+            permission: record.permission,
+            effect: PermissionEffect::from_db(&record.effect),
+            expires_at: record.expires_at.map(|dt| dt.to_rfc3339()),
+            reason: record.reason,
+            created_by: record.created_by.map(|id| id.to_string()),
+            created_at: record.created_at.map(|dt| dt.to_rfc3339()),
+        }
+    }
 }
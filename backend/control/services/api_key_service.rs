@@ -0,0 +1,316 @@
+//! API key service
+//!
+//! Personal API keys for scripts and CI. A key looks like `rext_<prefix>_<secret>`, only a hash
+//! of the whole key is stored. The prefix stays readable so users can tell their keys apart.
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use rand_core::RngCore;
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::control::services::{
+    audit_service::AuditService, permission_service::PermissionService, token_service::TokenService,
+};
+use crate::domain::{audit::AuditEvent, permissions::Permission};
+use crate::entity::models::{api_keys, prelude::*};
+use crate::infrastructure::app_error::AppError;
+
+/// Fixed start of every key, makes leaked keys easy to spot in logs and secret scanners
+const KEY_PREFIX: &str = "rext";
+
+/// Longest accepted key name
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Service for personal API keys
+pub struct ApiKeyService;
+
+impl ApiKeyService {
+    /// Creates a key for the user, returns the stored record and the full key
+    /// The full key can't be recovered later, it has to be shown to the user right away
+    pub async fn create(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        name: &str,
+        scopes: &[String],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(api_keys::Model, String), AppError> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(AppError {
+                message: format!("Name must be between 1 and {} characters", MAX_NAME_LENGTH),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let scopes = Self::validate_scopes(scopes)?;
+
+        if let Some(expires_at) = expires_at
+            && expires_at <= Utc::now()
+        {
+            return Err(AppError {
+                message: "expires_at must be in the future".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let (prefix, key) = Self::generate_key();
+
+        let api_key = api_keys::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            name: Set(name.to_string()),
+            prefix: Set(prefix),
+            key_hash: Set(TokenService::hash_opaque_token(&key)),
+            scopes: Set(serde_json::to_string(&scopes).unwrap_or_else(|_| "[]".to_string())),
+            expires_at: Set(expires_at.map(Into::into)),
+            last_used_at: Set(None),
+            revoked_at: Set(None),
+            created_at: Set(Some(Utc::now().into())),
+        }
+        .insert(db)
+        .await
+        .map_err(|_| AppError {
+            message: "Failed to create API key".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+
+        Self::audit(
+            db,
+            "API_KEY_CREATE",
+            &api_key,
+            format!(
+                "API key {} ({}) created with scopes {}",
+                api_key.prefix,
+                api_key.name,
+                scopes.join(", ")
+            ),
+        )
+        .await?;
+
+        Ok((api_key, key))
+    }
+
+    /// Keys of a user that haven't been revoked, newest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<api_keys::Model>, AppError> {
+        ApiKeys::find()
+            .filter(api_keys::Column::UserId.eq(user_id))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .order_by_desc(api_keys::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|_| Self::database_error())
+    }
+
+    /// Revokes one of the user's keys, it stops working immediately
+    pub async fn revoke(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        key_id: Uuid,
+    ) -> Result<api_keys::Model, AppError> {
+        let api_key = ApiKeys::find_by_id(key_id)
+            .filter(api_keys::Column::UserId.eq(user_id))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .one(db)
+            .await
+            .map_err(|_| Self::database_error())?
+            .ok_or(AppError {
+                message: "API key not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        let mut active: api_keys::ActiveModel = api_key.into();
+        active.revoked_at = Set(Some(Utc::now().into()));
+        let api_key = active
+            .update(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+
+        Self::audit(
+            db,
+            "API_KEY_REVOKE",
+            &api_key,
+            format!("API key {} ({}) revoked", api_key.prefix, api_key.name),
+        )
+        .await?;
+
+        Ok(api_key)
+    }
+
+    /// Revokes every key of a user, returns how many were still active
+    pub async fn revoke_all(db: &DatabaseConnection, user_id: Uuid) -> Result<u64, AppError> {
+        let result = ApiKeys::update_many()
+            .col_expr(
+                api_keys::Column::RevokedAt,
+                sea_orm::prelude::Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(
+                    Utc::now(),
+                ))),
+            )
+            .filter(api_keys::Column::UserId.eq(user_id))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+        Ok(result.rows_affected)
+    }
+
+    /// Looks up a key sent by a client, rejecting unknown, revoked and expired keys
+    pub async fn authenticate(
+        db: &DatabaseConnection,
+        key: &str,
+    ) -> Result<api_keys::Model, AppError> {
+        let invalid = || AppError {
+            message: "Invalid API key".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        };
+
+        if !key.starts_with(KEY_PREFIX) {
+            return Err(invalid());
+        }
+
+        let api_key = ApiKeys::find()
+            .filter(api_keys::Column::KeyHash.eq(TokenService::hash_opaque_token(key)))
+            .one(db)
+            .await
+            .map_err(|_| Self::database_error())?
+            .ok_or_else(invalid)?;
+
+        if api_key.revoked_at.is_some() {
+            return Err(invalid());
+        }
+        if api_key
+            .expires_at
+            .is_some_and(|expires_at| expires_at.to_utc() <= Utc::now())
+        {
+            return Err(AppError {
+                message: "API key expired".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
+
+        Ok(api_key)
+    }
+
+    /// Records that a key was just used
+    pub async fn touch(db: &DatabaseConnection, key_id: Uuid) -> Result<(), AppError> {
+        ApiKeys::update_many()
+            .col_expr(
+                api_keys::Column::LastUsedAt,
+                sea_orm::prelude::Expr::value(Some(sea_orm::prelude::DateTimeWithTimeZone::from(
+                    Utc::now(),
+                ))),
+            )
+            .filter(api_keys::Column::Id.eq(key_id))
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+        Ok(())
+    }
+
+    /// Permissions a key is limited to
+    pub fn scopes(api_key: &api_keys::Model) -> Vec<Permission> {
+        serde_json::from_str::<Vec<String>>(&api_key.scopes)
+            .unwrap_or_default()
+            .iter()
+            .map(|scope| Permission::from_string(scope))
+            .collect()
+    }
+
+    fn validate_scopes(scopes: &[String]) -> Result<Vec<String>, AppError> {
+        if scopes.is_empty() {
+            return Err(AppError {
+                message: "At least one scope is required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let mut valid = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            let scope = scope.trim();
+            if !PermissionService::is_valid_permission(scope) {
+                return Err(AppError {
+                    message: format!("Invalid scope: {}", scope),
+                    status_code: StatusCode::BAD_REQUEST,
+                });
+            }
+            if !valid.iter().any(|s: &String| s == scope) {
+                valid.push(scope.to_string());
+            }
+        }
+
+        Ok(valid)
+    }
+
+    /// Returns the displayable prefix and the full key
+    fn generate_key() -> (String, String) {
+        let mut bytes = [0u8; 4];
+        rand_core::OsRng.fill_bytes(&mut bytes);
+        let prefix = format!(
+            "{}_{}",
+            KEY_PREFIX,
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+        let key = format!("{}_{}", prefix, TokenService::generate_opaque_token());
+        (prefix, key)
+    }
+
+    async fn audit(
+        db: &DatabaseConnection,
+        action: &str,
+        api_key: &api_keys::Model,
+        message: String,
+    ) -> Result<(), AppError> {
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: action.to_string(),
+                path: "/api/v1/auth/api-keys".to_string(),
+                status_code: None,
+                user_id: Some(api_key.user_id),
+                actor_id: None,
+                ip_address: None,
+                user_agent: None,
+                message,
+            },
+        )
+        .await
+    }
+
+    fn database_error() -> AppError {
+        AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_start_with_their_prefix() {
+        let (prefix, key) = ApiKeyService::generate_key();
+        assert!(prefix.starts_with("rext_"));
+        assert_eq!(prefix.len(), "rext_".len() + 8);
+        assert!(key.starts_with(&format!("{}_", prefix)));
+        assert_ne!(ApiKeyService::generate_key().1, key);
+    }
+
+    #[test]
+    fn scopes_are_validated_and_deduplicated() {
+        assert!(ApiKeyService::validate_scopes(&[]).is_err());
+        assert!(ApiKeyService::validate_scopes(&["nonsense".to_string()]).is_err());
+        assert_eq!(
+            ApiKeyService::validate_scopes(&["admin:logs".to_string(), " admin:logs ".to_string()])
+                .unwrap(),
+            vec!["admin:logs".to_string()]
+        );
+    }
+}
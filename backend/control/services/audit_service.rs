@@ -0,0 +1,69 @@
+//! Audit service
+//!
+//! Records security events into the audit log and pushes them to the admin WebSocket feed.
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::domain::audit::AuditEvent;
+use crate::entity::models::audit_logs;
+use crate::infrastructure::{app_error::AppError, websocket::broadcast_audit_log};
+
+/// Service for application level audit events
+pub struct AuditService;
+
+impl AuditService {
+    /// Stores an event in the audit log and broadcasts it
+    pub async fn record(db: &DatabaseConnection, event: AuditEvent) -> Result<(), AppError> {
+        let id = Uuid::new_v4();
+        let timestamp = Utc::now();
+
+        audit_logs::ActiveModel {
+            id: Set(id),
+            timestamp: Set(Some(timestamp.into())),
+            method: Set(event.action.clone()),
+            path: Set(event.path.clone()),
+            status_code: Set(event.status_code),
+            response_time_ms: Set(None),
+            user_id: Set(event.user_id),
+            ip_address: Set(event.ip_address.clone()),
+            user_agent: Set(event.user_agent.clone()),
+            request_body: Set(None),
+            response_body: Set(None),
+            error_message: Set(Some(event.message.clone())),
+            actor_id: Set(event.actor_id),
+        }
+        .insert(db)
+        .await
+        .map_err(|_| AppError {
+            message: "Failed to record audit event".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+
+        tracing::warn!(
+            target: "audit",
+            action = %event.action,
+            user_id = ?event.user_id,
+            "{}",
+            event.message
+        );
+
+        broadcast_audit_log(
+            id.to_string(),
+            timestamp.to_rfc3339(),
+            event.action,
+            event.path,
+            event.status_code,
+            None,
+            event.user_id.map(|id| id.to_string()),
+            event.ip_address,
+            event.user_agent,
+            Some(event.message),
+        )
+        .await;
+
+        Ok(())
+    }
+}
@@ -1,26 +1,29 @@
-use jsonwebtoken::{EncodingKey, Header, encode};
 use sea_orm::*;
 use std::env;
-use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-use crate::control::services::{session_service::SessionService, user_service::UserService};
-use crate::domain::{auth::*, user::*, validation::*};
+use crate::control::services::{
+    account_service::AccountService, mfa_service::MfaService, session_service::SessionService,
+    user_service::UserService, verification_service::VerificationService,
+};
+use crate::domain::{auth::*, password::PasswordPolicy, user::*, validation::*};
 use crate::infrastructure::app_error::AppError;
-use crate::infrastructure::jwt_claims::Claims;
+use crate::infrastructure::jwt_claims::{ActorClaims, Claims};
+use crate::infrastructure::jwt_keys::JwtKeyManager;
 use axum::http::StatusCode;
 
 /// Service for authentication-related business operations
 pub struct AuthService;
 
 impl AuthService {
-    /// Authenticates a user and returns a JWT token with session tracking
+    /// Authenticates a user and returns an access token and refresh token with session tracking
+    /// Users with MFA get a challenge instead, completed with `complete_mfa_login`
     pub async fn authenticate_user(
         db: &DatabaseConnection,
         login: UserLogin,
         user_agent: Option<String>,
         ip_address: Option<String>,
-    ) -> Result<AuthToken, AppError> {
+    ) -> Result<LoginResult, AppError> {
         // Validate input
         validate_login_input(&login.email, &login.password)?;
 
@@ -33,7 +36,7 @@ impl AuthService {
             })?;
 
         // Verify password
-        let is_valid = UserService::verify_password(&user, &login.password)?;
+        let is_valid = UserService::verify_password(db, &user, &login.password).await?;
         if !is_valid {
             return Err(AppError {
                 message: "Invalid credentials".to_string(),
@@ -41,60 +44,233 @@ impl AuthService {
             });
         }
 
-        // Update last login timestamp (non-blocking)
+        // Optionally block sign in until the email address is confirmed
+        if Self::email_verification_required() && !user.is_email_verified() {
+            return Err(AppError {
+                message: "Email address not verified".to_string(),
+                status_code: StatusCode::FORBIDDEN,
+            });
+        }
+
+        Self::ensure_password_current(db, &user).await?;
+
+        // The session is only created once the second factor is checked
+        if let Some(challenge) = MfaService::challenge_for(db, &user, MfaScope::User).await? {
+            return Ok(LoginResult::MfaRequired(challenge));
+        }
+
+        Self::record_login(db, user.id);
+
+        let auth_token = Self::start_session(db, user.id, user_agent, ip_address).await?;
+        Ok(LoginResult::Authenticated(auth_token))
+    }
+
+    /// Completes a login by exchanging an MFA challenge token and a TOTP or recovery code
+    pub async fn complete_mfa_login(
+        db: &DatabaseConnection,
+        challenge_token: &str,
+        code: &str,
+        scope: MfaScope,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<MfaLogin, AppError> {
+        if challenge_token.is_empty() || code.is_empty() {
+            return Err(AppError {
+                message: "MFA token and code are required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let (user_id, recovery_codes) =
+            MfaService::complete_challenge(db, challenge_token, code, scope).await?;
+
+        Self::record_login(db, user_id);
+
+        let auth_token = Self::start_session(db, user_id, user_agent, ip_address).await?;
+
+        Ok(MfaLogin {
+            auth_token,
+            recovery_codes,
+        })
+    }
+
+    /// Updates the last login timestamp without blocking the login
+    pub fn record_login(db: &DatabaseConnection, user_id: Uuid) {
         let db_clone = db.clone();
-        let user_id = user.id;
         tokio::spawn(async move {
             let _ = UserService::update_last_login(&db_clone, user_id).await;
         });
+    }
+
+    /// Creates a session for an already authenticated user
+    /// Returns a short-lived access token and the first refresh token of the session.
+    /// Signing in keeps an account that was scheduled for deletion
+    pub async fn start_session(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<AuthToken, AppError> {
+        AccountService::cancel_deletion(db, user_id).await?;
 
         // Generate session ID
         let session_id = Uuid::new_v4();
 
         // Generate JWT token with session ID
-        let token = Self::generate_jwt_token(&user.id, &session_id)?;
+        let (token, expires_at) = Self::generate_jwt_token(&user_id, &session_id, None, None)?;
 
         // Create session record (after successful token generation)
-        SessionService::create_session(
+        let session = SessionService::create_session(
             db,
-            user.id,
+            user_id,
             user_agent,
             ip_address,
             &session_id.to_string(),
         )
         .await?;
 
-        Ok(token)
+        let refresh_token = SessionService::issue_refresh_token(db, &session).await?;
+
+        Ok(AuthToken::new(token, refresh_token, user_id, expires_at))
     }
 
-    /// Generates a JWT token for a user with session tracking
-    fn generate_jwt_token(user_id: &uuid::Uuid, session_id: &Uuid) -> Result<AuthToken, AppError> {
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
-        let encoding_key = EncodingKey::from_secret(jwt_secret.as_ref());
+    /// Starts a session for a user on behalf of an admin, its tokens name the admin in the `act`
+    /// claim. Unlike `start_session` it leaves a scheduled account deletion alone.
+    /// Returns the tokens and when the impersonation ends
+    pub async fn start_impersonation(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        impersonator_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<(AuthToken, chrono::DateTime<chrono::Utc>), AppError> {
+        let session_id = Uuid::new_v4();
 
-        let expiration = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            + 24 * 60 * 60; // 24 hours
+        let session = SessionService::create_impersonation_session(
+            db,
+            user_id,
+            impersonator_id,
+            user_agent,
+            ip_address,
+            &session_id.to_string(),
+        )
+        .await?;
+        let ends_at = session.expires_at.to_utc();
+
+        let (token, expires_at) =
+            Self::generate_jwt_token(&user_id, &session_id, Some(impersonator_id), Some(ends_at))?;
+        let refresh_token = SessionService::issue_refresh_token(db, &session).await?;
+
+        Ok((
+            AuthToken::new(token, refresh_token, user_id, expires_at),
+            ends_at,
+        ))
+    }
+
+    /// Exchanges a refresh token for a new access token and a rotated refresh token
+    pub async fn refresh_session(
+        db: &DatabaseConnection,
+        refresh_token: &str,
+    ) -> Result<AuthToken, AppError> {
+        if refresh_token.is_empty() {
+            return Err(AppError {
+                message: "Refresh token is required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let (session, new_refresh_token) =
+            SessionService::rotate_refresh_token(db, refresh_token).await?;
+
+        // The JWT session ID is stored in the session_token column
+        let session_id = Uuid::parse_str(&session.session_token).map_err(|_| AppError {
+            message: "Invalid session".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        })?;
+
+        let (token, expires_at) = Self::generate_jwt_token(
+            &session.user_id,
+            &session_id,
+            session.impersonator_id,
+            Some(session.expires_at.to_utc()),
+        )?;
+
+        Ok(AuthToken::new(
+            token,
+            new_refresh_token,
+            session.user_id,
+            expires_at,
+        ))
+    }
+
+    /// Refuses a password login once the password is older than PASSWORD_MAX_AGE_DAYS and mails
+    /// the user a link to set a new one
+    pub async fn ensure_password_current(
+        db: &DatabaseConnection,
+        user: &User,
+    ) -> Result<(), AppError> {
+        let policy = PasswordPolicy::from_env();
+        if policy.max_age.is_none() {
+            return Ok(());
+        }
+
+        let Some(changed_at) = UserService::password_changed_at(db, user.id).await? else {
+            return Ok(());
+        };
+        if !policy.is_expired(changed_at, chrono::Utc::now()) {
+            return Ok(());
+        }
+
+        VerificationService::request_password_reset(db, &user.email).await?;
+        Err(AppError {
+            message: "Your password has expired, a link to choose a new one was sent to your email"
+                .to_string(),
+            status_code: StatusCode::FORBIDDEN,
+        })
+    }
+
+    /// Whether users must verify their email before signing in, configured with
+    /// REQUIRE_EMAIL_VERIFICATION (defaults to false)
+    pub fn email_verification_required() -> bool {
+        env::var("REQUIRE_EMAIL_VERIFICATION")
+            .ok()
+            .and_then(|value| value.parse::<bool>().ok())
+            .unwrap_or(false)
+    }
+
+    /// Lifetime of access tokens, configured with ACCESS_TOKEN_TTL_MINUTES (defaults to 15)
+    pub fn access_token_lifetime() -> chrono::Duration {
+        let minutes = env::var("ACCESS_TOKEN_TTL_MINUTES")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(15);
+
+        chrono::Duration::minutes(minutes)
+    }
+
+    /// Generates a JWT access token for a user with session tracking
+    /// `impersonator_id` becomes the `act` claim, and the token never outlives `session_ends_at`.
+    /// Returns the token and its expiration time
+    fn generate_jwt_token(
+        user_id: &uuid::Uuid,
+        session_id: &Uuid,
+        impersonator_id: Option<Uuid>,
+        session_ends_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(String, chrono::DateTime<chrono::Utc>), AppError> {
+        let expires_at = chrono::Utc::now() + Self::access_token_lifetime();
+        let expires_at = session_ends_at.map_or(expires_at, |ends_at| expires_at.min(ends_at));
 
         let claims = Claims {
             sub: user_id.to_string(),
-            exp: expiration as usize,
+            exp: expires_at.timestamp() as usize,
             session_id: session_id.to_string(),
+            act: impersonator_id.map(|id| ActorClaims {
+                sub: id.to_string(),
+            }),
         };
 
-        let token_string =
-            encode(&Header::default(), &claims, &encoding_key).map_err(|_| AppError {
-                message: "Failed to generate token".to_string(),
-                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            })?;
-
-        let expires_at = chrono::DateTime::from_timestamp(expiration as i64, 0)
-            .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::hours(24));
-
-        let auth_token = AuthToken::new(token_string, *user_id, expires_at);
+        let token_string = JwtKeyManager::sign(&claims)?;
 
-        Ok(auth_token)
+        Ok((token_string, expires_at))
     }
 }
@@ -0,0 +1,455 @@
+//! Login throttle service
+//!
+//! Counts failed logins per account and per client IP. Past a threshold every attempt
+//! has to wait an exponentially growing delay, and further failures lock the key for a while.
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::Expr;
+use sea_orm::*;
+use std::future::Future;
+use uuid::Uuid;
+
+use crate::control::services::{audit_service::AuditService, user_service::UserService};
+use crate::domain::{
+    audit::AuditEvent,
+    auth::{LoginAttempt, LoginThrottlePolicy},
+};
+use crate::entity::models::{login_throttles, prelude::*};
+use crate::infrastructure::app_error::{AppError, ThrottledError};
+
+/// Service for brute-force protection on login endpoints
+pub struct LoginThrottleService;
+
+impl LoginThrottleService {
+    /// Runs a login unless the account or client is currently throttled
+    ///
+    /// Unauthorized results count as failures, a successful login clears the account's counter.
+    pub async fn protect<T, F>(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+        login: F,
+    ) -> Result<T, ThrottledError>
+    where
+        F: Future<Output = Result<T, AppError>>,
+    {
+        Self::guard(db, attempt, StatusCode::UNAUTHORIZED, login).await
+    }
+
+    /// Runs a current password check for an account change, throttled like a login
+    ///
+    /// Forbidden results, a wrong password, count as failures against the user and the client.
+    pub async fn protect_password_check<T, F>(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+        check: F,
+    ) -> Result<T, ThrottledError>
+    where
+        F: Future<Output = Result<T, AppError>>,
+    {
+        Self::guard(db, attempt, StatusCode::FORBIDDEN, check).await
+    }
+
+    async fn guard<T, F>(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+        failure: StatusCode,
+        login: F,
+    ) -> Result<T, ThrottledError>
+    where
+        F: Future<Output = Result<T, AppError>>,
+    {
+        Self::check(db, attempt).await?;
+
+        match login.await {
+            Ok(value) => {
+                Self::record_success(db, attempt).await?;
+                Ok(value)
+            }
+            Err(error) if error.status_code == failure => {
+                Self::record_failure(db, attempt).await?;
+                Err(error.into())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Rejects the attempt while one of its keys is locked or backing off
+    pub async fn check(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+    ) -> Result<(), ThrottledError> {
+        let now = Utc::now();
+
+        for (key, policy) in attempt.throttle_keys() {
+            let Some(throttle) = Self::find(db, &key).await? else {
+                continue;
+            };
+
+            if let Some(retry_after) = Self::retry_after(&throttle, &policy, now) {
+                return Err(Self::too_many_requests(retry_after));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears an account lockout, from logins and from current password checks, returns whether
+    /// there was anything to clear
+    pub async fn unlock(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        email: &str,
+    ) -> Result<bool, AppError> {
+        let mut attempt = LoginAttempt::new(Some(email), String::new(), None, None);
+        attempt.user_id = Some(user_id);
+        let mut cleared = false;
+        for (key, _) in attempt.throttle_keys() {
+            cleared |= Self::clear(db, &key).await?;
+        }
+        Ok(cleared)
+    }
+
+    /// Deletes keys whose failures are past the window and whose lockout is over, they no
+    /// longer affect anything and would otherwise pile up for every client IP ever seen
+    pub async fn purge_expired(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let window = LoginThrottlePolicy::account().window;
+        let result = LoginThrottles::delete_many()
+            .filter(login_throttles::Column::LastFailedAt.lte(now - window))
+            .filter(
+                Condition::any()
+                    .add(login_throttles::Column::LockedUntil.is_null())
+                    .add(login_throttles::Column::LockedUntil.lte(now)),
+            )
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+        Ok(result.rows_affected)
+    }
+
+    async fn record_success(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+    ) -> Result<(), AppError> {
+        // The IP counter is kept, one valid account shouldn't reset a client guessing others
+        if let Some(email) = &attempt.email {
+            Self::clear(db, &format!("account:{}", email)).await?;
+        }
+        if let Some(user_id) = attempt.user_id {
+            Self::clear(db, &format!("user:{}", user_id)).await?;
+        }
+        Ok(())
+    }
+
+    async fn record_failure(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+    ) -> Result<(), ThrottledError> {
+        let now = Utc::now();
+        let mut lockout = None;
+
+        for (key, policy) in attempt.throttle_keys() {
+            let txn = db.begin().await.map_err(|_| Self::database_error())?;
+
+            // Write before reading so the transaction takes SQLite's write lock up front and
+            // waits for it, a read first can't be upgraded once another connection has written,
+            // like the session activity update running alongside authenticated requests
+            LoginThrottles::update_many()
+                .col_expr(
+                    login_throttles::Column::FailedCount,
+                    Expr::col(login_throttles::Column::FailedCount).into(),
+                )
+                .filter(login_throttles::Column::Key.eq(key.clone()))
+                .exec(&txn)
+                .await
+                .map_err(|_| Self::database_error())?;
+
+            let existing = LoginThrottles::find_by_id(key.clone())
+                .one(&txn)
+                .await
+                .map_err(|_| Self::database_error())?;
+
+            let failed_count = match &existing {
+                Some(throttle) if Self::is_active(throttle, &policy, now) => {
+                    throttle.failed_count + 1
+                }
+                _ => 1,
+            };
+            let locked_until = policy
+                .should_lock(failed_count)
+                .then(|| now + policy.lockout);
+
+            let throttle = login_throttles::ActiveModel {
+                key: Set(key.clone()),
+                failed_count: Set(failed_count),
+                last_failed_at: Set(now.into()),
+                locked_until: Set(locked_until.map(Into::into)),
+            };
+            let result = match existing {
+                Some(_) => throttle.update(&txn).await.map(|_| ()),
+                None => throttle.insert(&txn).await.map(|_| ()),
+            };
+            result.map_err(|_| Self::database_error())?;
+            txn.commit().await.map_err(|_| Self::database_error())?;
+
+            if let Some(locked_until) = locked_until {
+                Self::audit_lockout(db, attempt, &key, failed_count, locked_until).await?;
+                lockout = lockout.max(Some(policy.lockout));
+            }
+        }
+
+        match lockout {
+            Some(retry_after) => Err(Self::too_many_requests(retry_after)),
+            None => Ok(()),
+        }
+    }
+
+    async fn audit_lockout(
+        db: &DatabaseConnection,
+        attempt: &LoginAttempt,
+        key: &str,
+        failed_count: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let user_id = match key.strip_prefix("account:") {
+            Some(email) => UserService::find_user_by_email(db, email)
+                .await?
+                .map(|user| user.id),
+            None => attempt.user_id.filter(|_| key.starts_with("user:")),
+        };
+
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "LOCKOUT".to_string(),
+                path: attempt.path.clone(),
+                status_code: Some(StatusCode::TOO_MANY_REQUESTS.as_u16() as i32),
+                user_id,
+                actor_id: None,
+                ip_address: attempt.ip_address.clone(),
+                user_agent: attempt.user_agent.clone(),
+                message: format!(
+                    "Login locked for {} after {} failed attempts until {}",
+                    key,
+                    failed_count,
+                    locked_until.to_rfc3339()
+                ),
+            },
+        )
+        .await
+    }
+
+    /// Time left before the key accepts another attempt
+    fn retry_after(
+        throttle: &login_throttles::Model,
+        policy: &LoginThrottlePolicy,
+        now: DateTime<Utc>,
+    ) -> Option<chrono::Duration> {
+        if let Some(locked_until) = throttle.locked_until.map(|dt| dt.to_utc()) {
+            return (locked_until > now).then(|| locked_until - now);
+        }
+        if !Self::is_active(throttle, policy, now) {
+            return None;
+        }
+
+        let allowed_at =
+            throttle.last_failed_at.to_utc() + policy.backoff(throttle.failed_count)?;
+        (allowed_at > now).then(|| allowed_at - now)
+    }
+
+    /// Whether earlier failures still count, expired lockouts and old failures start over
+    fn is_active(
+        throttle: &login_throttles::Model,
+        policy: &LoginThrottlePolicy,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match throttle.locked_until {
+            Some(locked_until) => locked_until.to_utc() > now,
+            None => throttle.last_failed_at.to_utc() + policy.window > now,
+        }
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        key: &str,
+    ) -> Result<Option<login_throttles::Model>, AppError> {
+        LoginThrottles::find_by_id(key.to_string())
+            .one(db)
+            .await
+            .map_err(|_| Self::database_error())
+    }
+
+    async fn clear(db: &DatabaseConnection, key: &str) -> Result<bool, AppError> {
+        let result = LoginThrottles::delete_by_id(key.to_string())
+            .exec(db)
+            .await
+            .map_err(|_| Self::database_error())?;
+        Ok(result.rows_affected > 0)
+    }
+
+    fn too_many_requests(retry_after: chrono::Duration) -> ThrottledError {
+        // Round up so clients never retry a moment too early
+        let millis = retry_after.num_milliseconds().max(0) as u64;
+        ThrottledError::TooManyRequests {
+            message: "Too many failed login attempts, try again later".to_string(),
+            retry_after_secs: millis.div_ceil(1000).max(1),
+        }
+    }
+
+    fn database_error() -> AppError {
+        AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LoginThrottlePolicy {
+        LoginThrottlePolicy {
+            backoff_after: 3,
+            lockout_after: 10,
+            base_delay: chrono::Duration::seconds(1),
+            max_delay: chrono::Duration::seconds(300),
+            lockout: chrono::Duration::minutes(15),
+            window: chrono::Duration::minutes(15),
+        }
+    }
+
+    fn throttle(failed_count: i32, seconds_ago: i64) -> login_throttles::Model {
+        login_throttles::Model {
+            key: "account:user@example.com".to_string(),
+            failed_count,
+            last_failed_at: (Utc::now() - chrono::Duration::seconds(seconds_ago)).into(),
+            locked_until: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn purge_keeps_only_keys_that_still_count() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let table =
+            Schema::new(DbBackend::Sqlite).create_table_from_entity(login_throttles::Entity);
+        db.execute(db.get_database_backend().build(&table))
+            .await
+            .unwrap();
+
+        let hour = 3600;
+        let rows = [
+            ("ip:stale", throttle(2, hour), None),
+            ("ip:recent", throttle(2, 60), None),
+            (
+                "ip:locked",
+                throttle(50, hour),
+                Some(chrono::Duration::minutes(5)),
+            ),
+            (
+                "ip:unlocked",
+                throttle(50, hour),
+                Some(chrono::Duration::minutes(-5)),
+            ),
+        ];
+        for (key, mut model, locked_for) in rows {
+            model.key = key.to_string();
+            model.locked_until = locked_for.map(|d| (Utc::now() + d).into());
+            model.into_active_model().insert(&db).await.unwrap();
+        }
+
+        assert_eq!(LoginThrottleService::purge_expired(&db).await.unwrap(), 2);
+        let mut kept: Vec<String> = LoginThrottles::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|model| model.key)
+            .collect();
+        kept.sort();
+        assert_eq!(kept, vec!["ip:locked", "ip:recent"]);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = policy();
+        assert_eq!(policy.backoff(2), None);
+        assert_eq!(policy.backoff(3), Some(chrono::Duration::seconds(1)));
+        assert_eq!(policy.backoff(5), Some(chrono::Duration::seconds(4)));
+        assert_eq!(policy.backoff(12), Some(chrono::Duration::seconds(300)));
+        assert_eq!(
+            policy.backoff(i32::MAX),
+            Some(chrono::Duration::seconds(300))
+        );
+    }
+
+    #[test]
+    fn retry_after_respects_backoff_lockout_and_window() {
+        let policy = policy();
+        let now = Utc::now();
+
+        assert!(LoginThrottleService::retry_after(&throttle(2, 0), &policy, now).is_none());
+        assert!(LoginThrottleService::retry_after(&throttle(5, 1), &policy, now).is_some());
+        assert!(LoginThrottleService::retry_after(&throttle(5, 10), &policy, now).is_none());
+
+        let mut locked = throttle(10, 0);
+        locked.locked_until = Some((now + chrono::Duration::minutes(5)).into());
+        assert!(LoginThrottleService::retry_after(&locked, &policy, now).is_some());
+
+        // Expired lockouts and stale failures no longer count
+        locked.locked_until = Some((now - chrono::Duration::seconds(1)).into());
+        assert!(LoginThrottleService::retry_after(&locked, &policy, now).is_none());
+        assert!(!LoginThrottleService::is_active(
+            &throttle(9, 3600),
+            &policy,
+            now
+        ));
+    }
+
+    #[test]
+    fn forged_forwarded_for_does_not_change_the_ip_key() {
+        use crate::bridge::middleware::logging::client_ip;
+        use axum::extract::{ConnectInfo, Request};
+        use std::net::SocketAddr;
+
+        let ip_key = |forwarded_for: Option<&str>| {
+            let mut request = Request::builder().uri("/api/v1/auth/login");
+            if let Some(forwarded_for) = forwarded_for {
+                request = request.header("x-forwarded-for", forwarded_for);
+            }
+            let mut request = request.body(axum::body::Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(
+                "203.0.113.7:4242".parse::<SocketAddr>().unwrap(),
+            ));
+
+            let attempt = LoginAttempt::new(None, String::new(), client_ip(&request), None);
+            attempt.throttle_keys().pop().map(|(key, _)| key)
+        };
+
+        // No proxy is trusted here, so a client can't rotate its key by sending the header
+        assert_eq!(ip_key(None), Some("ip:203.0.113.7".to_string()));
+        assert_eq!(ip_key(Some("1.2.3.4")), ip_key(None));
+        assert_eq!(ip_key(Some("1.2.3.4, 5.6.7.8")), ip_key(None));
+    }
+
+    #[test]
+    fn password_checks_count_against_the_user_and_client() {
+        let user_id = Uuid::new_v4();
+        let attempt = LoginAttempt::for_user(
+            user_id,
+            "/api/v1/auth/profile/password",
+            Some("203.0.113.7".to_string()),
+            None,
+        );
+        let keys: Vec<String> = attempt
+            .throttle_keys()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![format!("user:{}", user_id), "ip:203.0.113.7".to_string()]
+        );
+    }
+}
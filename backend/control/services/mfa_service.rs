@@ -0,0 +1,539 @@
+//! MFA service
+//!
+//! TOTP (RFC 6238) enrollment, code verification and one-time recovery codes.
+//! Secrets use the common authenticator app defaults: SHA-1, 6 digits, 30 second steps.
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sea_orm::prelude::Expr;
+use sea_orm::*;
+use sha1::Sha1;
+use std::env;
+use uuid::Uuid;
+
+use crate::control::services::{token_service::TokenService, user_service::UserService};
+use crate::domain::{
+    auth::{MfaChallenge, MfaScope, MfaSetup},
+    user::User,
+};
+use crate::entity::models::{prelude::*, *};
+use crate::infrastructure::{
+    app_error::AppError, jwt_claims::MfaChallengeClaims, jwt_keys::JwtKeyManager,
+};
+
+/// Seconds per TOTP time step
+const TOTP_STEP_SECONDS: u64 = 30;
+
+/// Digits in a TOTP code
+const TOTP_DIGITS: u32 = 6;
+
+/// Accepted clock drift, in time steps before and after the current one
+const TOTP_SKEW_STEPS: u64 = 1;
+
+/// Recovery codes generated on enrollment
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Value of the `purpose` claim of challenge tokens
+const MFA_CHALLENGE_PURPOSE: &str = "mfa_challenge";
+
+/// Service for TOTP multi-factor authentication
+pub struct MfaService;
+
+impl MfaService {
+    /// Returns the challenge a user has to complete after entering their password, if any
+    /// Users whose role requires MFA but who haven't enrolled get a fresh secret to set up
+    pub async fn challenge_for(
+        db: &DatabaseConnection,
+        user: &User,
+        scope: MfaScope,
+    ) -> Result<Option<MfaChallenge>, AppError> {
+        if Self::is_enabled(db, user.id).await? {
+            return Self::issue_challenge(user.id, scope, None).map(Some);
+        }
+
+        if Self::roles_require_mfa(db, user.id).await? {
+            let setup = Self::begin_enrollment(db, user).await?;
+            return Self::issue_challenge(user.id, scope, Some(setup)).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// Validates a challenge token and the code that goes with it
+    /// Returns the user ID and, when the challenge also completed enrollment, the recovery codes
+    pub async fn complete_challenge(
+        db: &DatabaseConnection,
+        challenge_token: &str,
+        code: &str,
+        scope: MfaScope,
+    ) -> Result<(Uuid, Option<Vec<String>>), AppError> {
+        let user_id = Self::validate_challenge(challenge_token, scope)?;
+
+        if Self::is_enabled(db, user_id).await? {
+            Self::verify_code(db, user_id, code).await?;
+            Ok((user_id, None))
+        } else {
+            let recovery_codes = Self::confirm_enrollment(db, user_id, code).await?;
+            Ok((user_id, Some(recovery_codes)))
+        }
+    }
+
+    /// Whether the user has completed MFA enrollment
+    pub async fn is_enabled(db: &DatabaseConnection, user_id: Uuid) -> Result<bool, AppError> {
+        Ok(Self::find_mfa(db, user_id)
+            .await?
+            .is_some_and(|mfa| mfa.enabled_at.is_some()))
+    }
+
+    /// Whether any of the user's roles requires its members to use MFA
+    pub async fn roles_require_mfa(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<bool, AppError> {
+        let role_ids = UserService::get_role_ids(db, user_id).await?;
+        if role_ids.is_empty() {
+            return Ok(false);
+        }
+
+        let required = Roles::find()
+            .filter(roles::Column::Id.is_in(role_ids))
+            .filter(roles::Column::MfaRequired.eq(true))
+            .count(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(required > 0)
+    }
+
+    /// Starts enrollment by storing a new, not yet enabled secret
+    pub async fn begin_enrollment(
+        db: &DatabaseConnection,
+        user: &User,
+    ) -> Result<MfaSetup, AppError> {
+        if Self::is_enabled(db, user.id).await? {
+            return Err(AppError {
+                message: "MFA is already enabled".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+
+        let secret = Self::generate_secret();
+
+        // Replace any pending enrollment
+        UserMfa::delete_by_id(user.id)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let mfa_model = user_mfa::ActiveModel {
+            user_id: Set(user.id),
+            secret: Set(secret.clone()),
+            enabled_at: Set(None),
+            last_used_step: Set(None),
+            created_at: Set(Some(Utc::now().fixed_offset())),
+        };
+
+        UserMfa::insert(mfa_model)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to start MFA enrollment: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(MfaSetup {
+            otpauth_uri: Self::provisioning_uri(&secret, &user.email),
+            secret,
+        })
+    }
+
+    /// Enables MFA once the user proves their authenticator works
+    /// Returns the recovery codes, they are only ever shown this once
+    pub async fn confirm_enrollment(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let mfa = Self::find_mfa(db, user_id).await?.ok_or(AppError {
+            message: "MFA enrollment has not been started".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        })?;
+
+        if mfa.enabled_at.is_some() {
+            return Err(AppError {
+                message: "MFA is already enabled".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+
+        let step = Self::match_totp(&mfa.secret, code, None)?.ok_or_else(Self::invalid_code)?;
+
+        let now = Utc::now().fixed_offset();
+        let mut mfa_model: user_mfa::ActiveModel = mfa.into();
+        mfa_model.enabled_at = Set(Some(now));
+        mfa_model.last_used_step = Set(Some(step as i64));
+        mfa_model.update(db).await.map_err(|e| AppError {
+            message: format!("Failed to enable MFA: {}", e),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+
+        Self::replace_recovery_codes(db, user_id).await
+    }
+
+    /// Turns MFA off after checking a current code
+    /// Not allowed while the user's role requires MFA
+    pub async fn disable(db: &DatabaseConnection, user: &User, code: &str) -> Result<(), AppError> {
+        if Self::roles_require_mfa(db, user.id).await? {
+            return Err(AppError {
+                message: "MFA is required for your role".to_string(),
+                status_code: StatusCode::FORBIDDEN,
+            });
+        }
+
+        Self::verify_code(db, user.id, code).await?;
+
+        MfaRecoveryCodes::delete_many()
+            .filter(mfa_recovery_codes::Column::UserId.eq(user.id))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        UserMfa::delete_by_id(user.id)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to disable MFA: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(())
+    }
+
+    /// Replaces the recovery codes after checking a current code
+    pub async fn regenerate_recovery_codes(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, AppError> {
+        Self::verify_code(db, user_id, code).await?;
+        Self::replace_recovery_codes(db, user_id).await
+    }
+
+    /// Checks a TOTP or recovery code for a user with MFA enabled
+    /// Each TOTP code and each recovery code is accepted only once
+    pub async fn verify_code(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<(), AppError> {
+        let mfa = Self::find_mfa(db, user_id)
+            .await?
+            .filter(|mfa| mfa.enabled_at.is_some())
+            .ok_or(AppError {
+                message: "MFA is not enabled".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            })?;
+
+        let last_used_step = mfa.last_used_step.map(|step| step as u64);
+        if let Some(step) = Self::match_totp(&mfa.secret, code, last_used_step)? {
+            // Guard on the previous step so a code can't be used twice concurrently
+            let previous = match mfa.last_used_step {
+                Some(previous) => user_mfa::Column::LastUsedStep.eq(previous),
+                None => user_mfa::Column::LastUsedStep.is_null(),
+            };
+
+            let result = UserMfa::update_many()
+                .col_expr(user_mfa::Column::LastUsedStep, Expr::value(step as i64))
+                .filter(user_mfa::Column::UserId.eq(user_id))
+                .filter(previous)
+                .exec(db)
+                .await
+                .map_err(|e| AppError {
+                    message: format!("Database error: {}", e),
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                })?;
+
+            if result.rows_affected == 1 {
+                return Ok(());
+            }
+
+            return Err(Self::invalid_code());
+        }
+
+        Self::use_recovery_code(db, user_id, code).await
+    }
+
+    /// Signs a short-lived challenge token for the second login step
+    fn issue_challenge(
+        user_id: Uuid,
+        scope: MfaScope,
+        setup: Option<MfaSetup>,
+    ) -> Result<MfaChallenge, AppError> {
+        let minutes = env::var("MFA_CHALLENGE_TTL_MINUTES")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(5);
+        let expires_at = Utc::now() + chrono::Duration::minutes(minutes);
+
+        let claims = MfaChallengeClaims {
+            sub: user_id.to_string(),
+            exp: expires_at.timestamp() as usize,
+            purpose: MFA_CHALLENGE_PURPOSE.to_string(),
+            scope: scope.as_str().to_string(),
+        };
+
+        Ok(MfaChallenge {
+            token: JwtKeyManager::sign(&claims)?,
+            expires_at,
+            setup,
+        })
+    }
+
+    /// Validates a challenge token issued for the given login flow
+    fn validate_challenge(token: &str, scope: MfaScope) -> Result<Uuid, AppError> {
+        let invalid_challenge = || AppError {
+            message: "Invalid or expired MFA challenge".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        };
+
+        let claims: MfaChallengeClaims =
+            JwtKeyManager::verify(token).map_err(|_| invalid_challenge())?;
+
+        if claims.purpose != MFA_CHALLENGE_PURPOSE || claims.scope != scope.as_str() {
+            return Err(invalid_challenge());
+        }
+
+        Uuid::parse_str(&claims.sub).map_err(|_| invalid_challenge())
+    }
+
+    async fn find_mfa(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Option<user_mfa::Model>, AppError> {
+        UserMfa::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })
+    }
+
+    /// Generates new recovery codes, invalidating the old ones
+    async fn replace_recovery_codes(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, AppError> {
+        MfaRecoveryCodes::delete_many()
+            .filter(mfa_recovery_codes::Column::UserId.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let now = Utc::now().fixed_offset();
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| Self::generate_recovery_code())
+            .collect();
+
+        let models = codes.iter().map(|code| mfa_recovery_codes::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            code_hash: Set(TokenService::hash_opaque_token(&Self::normalize_code(code))),
+            created_at: Set(Some(now)),
+            used_at: Set(None),
+        });
+
+        MfaRecoveryCodes::insert_many(models)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to store recovery codes: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(codes)
+    }
+
+    /// Marks a matching, unused recovery code as used
+    async fn use_recovery_code(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        code: &str,
+    ) -> Result<(), AppError> {
+        let code_hash = TokenService::hash_opaque_token(&Self::normalize_code(code));
+
+        let result = MfaRecoveryCodes::update_many()
+            .col_expr(
+                mfa_recovery_codes::Column::UsedAt,
+                Expr::value(Utc::now().fixed_offset()),
+            )
+            .filter(mfa_recovery_codes::Column::UserId.eq(user_id))
+            .filter(mfa_recovery_codes::Column::CodeHash.eq(code_hash))
+            .filter(mfa_recovery_codes::Column::UsedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if result.rows_affected == 0 {
+            return Err(Self::invalid_code());
+        }
+
+        Ok(())
+    }
+
+    /// Finds the time step a code is valid for, within the allowed skew
+    /// Steps at or before `last_used_step` are rejected
+    fn match_totp(
+        secret: &str,
+        code: &str,
+        last_used_step: Option<u64>,
+    ) -> Result<Option<u64>, AppError> {
+        let code = code.trim();
+        if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+
+        let secret = BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .map_err(|_| AppError {
+                message: "Invalid MFA secret".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let current_step = Utc::now().timestamp() as u64 / TOTP_STEP_SECONDS;
+        let first_step = current_step.saturating_sub(TOTP_SKEW_STEPS);
+
+        Ok((first_step..=current_step + TOTP_SKEW_STEPS)
+            .filter(|step| last_used_step.is_none_or(|last| *step > last))
+            .find(|step| format!("{:06}", Self::totp(&secret, *step)) == code))
+    }
+
+    /// Computes the TOTP value for a time step (RFC 6238 with HMAC-SHA1)
+    fn totp(secret: &[u8], step: u64) -> u32 {
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        // Dynamic truncation (RFC 4226 section 5.3)
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+
+        binary % 10u32.pow(TOTP_DIGITS)
+    }
+
+    /// Generates a random 160-bit secret, base32 encoded for authenticator apps
+    fn generate_secret() -> String {
+        let mut bytes = [0u8; 20];
+        rand_core::OsRng.fill_bytes(&mut bytes);
+        BASE32_NOPAD.encode(&bytes)
+    }
+
+    /// Builds the `otpauth://` URI authenticator apps read from a QR code
+    fn provisioning_uri(secret: &str, email: &str) -> String {
+        let issuer = env::var("MFA_ISSUER").unwrap_or_else(|_| "Rext".to_string());
+        let label: String =
+            url::form_urlencoded::byte_serialize(format!("{}:{}", issuer, email).as_bytes())
+                .collect();
+        let issuer: String = url::form_urlencoded::byte_serialize(issuer.as_bytes()).collect();
+
+        format!(
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            label.replace('+', "%20"),
+            secret,
+            issuer.replace('+', "%20"),
+            TOTP_DIGITS,
+            TOTP_STEP_SECONDS
+        )
+    }
+
+    /// Generates a recovery code like `a1b2c-d3e4f`
+    fn generate_recovery_code() -> String {
+        let mut bytes = [0u8; 5];
+        rand_core::OsRng.fill_bytes(&mut bytes);
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}-{}", &hex[..5], &hex[5..])
+    }
+
+    /// Recovery codes are compared without dashes, whitespace or case
+    fn normalize_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    fn invalid_code() -> AppError {
+        AppError {
+            message: "Invalid MFA code".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        // RFC 6238 appendix B, SHA-1 secret, truncated to 6 digits
+        let secret = b"12345678901234567890";
+        assert_eq!(MfaService::totp(secret, 59 / 30), 287082);
+        assert_eq!(MfaService::totp(secret, 1111111109 / 30), 81804);
+        assert_eq!(MfaService::totp(secret, 1234567890 / 30), 5924);
+        assert_eq!(MfaService::totp(secret, 2000000000 / 30), 279037);
+    }
+
+    #[test]
+    fn test_match_totp_rejects_replayed_steps() {
+        let secret = MfaService::generate_secret();
+        let raw = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+        let step = Utc::now().timestamp() as u64 / TOTP_STEP_SECONDS;
+        let code = format!("{:06}", MfaService::totp(&raw, step));
+
+        assert_eq!(
+            MfaService::match_totp(&secret, &code, None).unwrap(),
+            Some(step)
+        );
+        assert_eq!(
+            MfaService::match_totp(&secret, &code, Some(step)).unwrap(),
+            None
+        );
+        assert_eq!(
+            MfaService::match_totp(&secret, "abcdef", None).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = MfaService::provisioning_uri("JBSWY3DPEHPK3PXP", "user@example.com");
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains("user%40example.com"));
+        assert!(uri.contains("secret=JBSWY3DPEHPK3PXP"));
+    }
+}
@@ -1,6 +1,12 @@
+pub mod account_service;
 pub mod admin_service;
+pub mod api_key_service;
+pub mod audit_service;
 pub mod auth_service;
 pub mod database_service;
+pub mod login_throttle_service;
+pub mod mfa_service;
+pub mod oidc_service;
 pub mod permission_service;
 pub mod server_config;
 pub mod session_service;
@@ -8,3 +14,4 @@ pub mod startup;
 pub mod system_monitor;
 pub mod token_service;
 pub mod user_service;
+pub mod verification_service;
@@ -0,0 +1,339 @@
+//! OpenID Connect login service
+//!
+//! Starts and completes logins through external identity providers. A successful login links the
+//! provider account to a local user and ends in the same session and tokens as a password login.
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use sea_orm::*;
+use uuid::Uuid;
+
+use crate::control::services::{
+    audit_service::AuditService, auth_service::AuthService, mfa_service::MfaService,
+    token_service::TokenService, user_service::UserService,
+};
+use crate::domain::{audit::AuditEvent, auth::*, user::User};
+use crate::entity::models::{oidc_login_states, prelude::*, roles, user_identities};
+use crate::infrastructure::app_error::AppError;
+use crate::infrastructure::oidc::{OidcIdentity, OidcManager, OidcProviderConfig};
+
+/// How long a user has to finish signing in at the provider
+const LOGIN_STATE_TTL_MINUTES: i64 = 10;
+
+/// Service for logins through external identity providers
+pub struct OidcService;
+
+impl OidcService {
+    /// Starts a login, returns the provider's authorization URL and the state it will echo back
+    pub async fn begin_login(
+        db: &DatabaseConnection,
+        provider_name: &str,
+    ) -> Result<(String, String), AppError> {
+        let provider = OidcManager::provider(provider_name)?;
+        let metadata = provider.metadata().await?;
+
+        let state = TokenService::generate_opaque_token();
+        let nonce = TokenService::generate_opaque_token();
+        let code_verifier = TokenService::generate_opaque_token();
+        let authorization_url =
+            provider.authorization_url(&metadata, &state, &nonce, &code_verifier)?;
+
+        // Abandoned logins are cleaned up whenever a new one starts
+        let now = Utc::now();
+        OidcLoginStates::delete_many()
+            .filter(oidc_login_states::Column::ExpiresAt.lt(now.fixed_offset()))
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let login_state = oidc_login_states::ActiveModel {
+            state: Set(state.clone()),
+            provider: Set(provider.name.clone()),
+            code_verifier: Set(code_verifier),
+            nonce: Set(nonce),
+            expires_at: Set((now + Duration::minutes(LOGIN_STATE_TTL_MINUTES)).fixed_offset()),
+            created_at: Set(Some(now.fixed_offset())),
+        };
+        OidcLoginStates::insert(login_state)
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to start login".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok((authorization_url, state))
+    }
+
+    /// Completes a login with the code and state the provider redirected back with
+    pub async fn complete_login(
+        db: &DatabaseConnection,
+        provider_name: &str,
+        code: &str,
+        state: &str,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+    ) -> Result<LoginResult, AppError> {
+        let provider = OidcManager::provider(provider_name)?;
+        if code.is_empty() || state.is_empty() {
+            return Err(AppError {
+                message: "Code and state are required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let login_state = Self::take_login_state(db, provider, state).await?;
+
+        let metadata = provider.metadata().await?;
+        let id_token = provider
+            .exchange_code(&metadata, code, &login_state.code_verifier)
+            .await?;
+        let identity = provider
+            .verify_id_token(&metadata, &id_token, &login_state.nonce)
+            .await?;
+
+        let (user, created) =
+            Self::find_or_create_user(db, provider, &identity, ip_address.clone()).await?;
+        Self::sync_roles(db, provider, &identity, user.id, created).await?;
+
+        if let Some(challenge) = MfaService::challenge_for(db, &user, MfaScope::User).await? {
+            return Ok(LoginResult::MfaRequired(challenge));
+        }
+
+        AuthService::record_login(db, user.id);
+
+        let auth_token = AuthService::start_session(db, user.id, user_agent, ip_address).await?;
+        Ok(LoginResult::Authenticated(auth_token))
+    }
+
+    /// Deletes and returns a pending login, each state can only be used once
+    async fn take_login_state(
+        db: &DatabaseConnection,
+        provider: &OidcProviderConfig,
+        state: &str,
+    ) -> Result<oidc_login_states::Model, AppError> {
+        let invalid_state = || AppError {
+            message: "Invalid or expired login state".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        };
+
+        let login_state = OidcLoginStates::find_by_id(state.to_string())
+            .one(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .ok_or_else(invalid_state)?;
+
+        // Only the request that actually removes the row may continue
+        let deleted = OidcLoginStates::delete_by_id(state.to_string())
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if deleted.rows_affected == 0
+            || login_state.provider != provider.name
+            || login_state.expires_at.to_utc() < Utc::now()
+        {
+            return Err(invalid_state());
+        }
+
+        Ok(login_state)
+    }
+
+    /// Finds the user linked to the provider account, linking or creating one on first login
+    /// Returns the user and whether the account was just created
+    async fn find_or_create_user(
+        db: &DatabaseConnection,
+        provider: &OidcProviderConfig,
+        identity: &OidcIdentity,
+        ip_address: Option<String>,
+    ) -> Result<(User, bool), AppError> {
+        let db_error = |_| AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let linked = UserIdentities::find()
+            .filter(user_identities::Column::Provider.eq(&provider.name))
+            .filter(user_identities::Column::Subject.eq(&identity.subject))
+            .one(db)
+            .await
+            .map_err(db_error)?;
+
+        if let Some(linked) = linked {
+            let user = UserService::find_user_by_id(db, linked.user_id)
+                .await?
+                .ok_or(AppError {
+                    message: "User not found".to_string(),
+                    status_code: StatusCode::UNAUTHORIZED,
+                })?;
+
+            let mut linked: user_identities::ActiveModel = linked.into();
+            linked.email = Set(identity.email.clone());
+            linked.last_login_at = Set(Some(Utc::now().fixed_offset()));
+            linked.update(db).await.map_err(db_error)?;
+
+            return Ok((user, false));
+        }
+
+        let email = identity.email.clone().ok_or(AppError {
+            message: "Identity provider did not share an email address".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        })?;
+
+        // An existing account is only taken over when the provider vouches for the address,
+        // otherwise anyone could register someone else's email at the provider
+        let (user, created) = match UserService::find_user_by_email(db, &email).await? {
+            Some(_) if !identity.email_verified => {
+                return Err(AppError {
+                    message: "An account with this email already exists, sign in with your password to link it".to_string(),
+                    status_code: StatusCode::CONFLICT,
+                });
+            }
+            Some(user) => (user, false),
+            None => (
+                UserService::create_external_user(db, email.clone(), identity.email_verified)
+                    .await?,
+                true,
+            ),
+        };
+
+        let now = Utc::now().fixed_offset();
+        let link = user_identities::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user.id),
+            provider: Set(provider.name.clone()),
+            subject: Set(identity.subject.clone()),
+            email: Set(Some(email)),
+            created_at: Set(Some(now)),
+            last_login_at: Set(Some(now)),
+        };
+        UserIdentities::insert(link)
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to link identity".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        AuditService::record(
+            db,
+            AuditEvent {
+                action: "OIDC_LINK".to_string(),
+                path: format!("/api/v1/auth/oidc/{}/callback", provider.name),
+                status_code: Some(StatusCode::OK.as_u16() as i32),
+                user_id: Some(user.id),
+                actor_id: None,
+                ip_address,
+                user_agent: None,
+                message: format!(
+                    "{} account {} linked to {} user {}",
+                    provider.name,
+                    identity.subject,
+                    if created { "new" } else { "existing" },
+                    user.email
+                ),
+            },
+        )
+        .await?;
+
+        Ok((user, created))
+    }
+
+    /// Brings the user's roles in line with the provider's role claim
+    /// Only roles named in the provider's role mapping are added or removed, roles granted
+    /// locally are left alone
+    async fn sync_roles(
+        db: &DatabaseConnection,
+        provider: &OidcProviderConfig,
+        identity: &OidcIdentity,
+        user_id: Uuid,
+        created: bool,
+    ) -> Result<(), AppError> {
+        let mut wanted = identity.roles.clone();
+        if created
+            && wanted.is_empty()
+            && let Some(default_role) = &provider.default_role
+        {
+            wanted.push(default_role.clone());
+        }
+
+        let mut managed: Vec<&String> = provider.role_mapping.values().collect();
+        managed.extend(&wanted);
+        managed.sort();
+        managed.dedup();
+        if managed.is_empty() {
+            return Ok(());
+        }
+
+        let roles = Roles::find()
+            .filter(roles::Column::Name.is_in(managed.iter().map(|name| name.as_str())))
+            .all(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        let current = UserService::get_role_ids(db, user_id).await?;
+
+        for name in &wanted {
+            if !roles.iter().any(|role| &role.name == name) {
+                tracing::warn!(
+                    "OIDC provider {} maps to unknown role {}",
+                    provider.name,
+                    name
+                );
+            }
+        }
+
+        for role in roles {
+            let should_have = wanted.contains(&role.name);
+            let has = current.contains(&role.id);
+
+            let action = if should_have && !has {
+                UserService::assign_role(db, user_id, role.id).await?;
+                "ROLE_ASSIGN"
+            } else if !should_have && has && provider.role_claim.is_some() {
+                UserService::revoke_role(db, user_id, role.id).await?;
+                "ROLE_REVOKE"
+            } else {
+                continue;
+            };
+
+            AuditService::record(
+                db,
+                AuditEvent {
+                    action: action.to_string(),
+                    path: format!("/api/v1/auth/oidc/{}/callback", provider.name),
+                    status_code: Some(StatusCode::OK.as_u16() as i32),
+                    user_id: Some(user_id),
+                    actor_id: None,
+                    ip_address: None,
+                    user_agent: None,
+                    message: format!(
+                        "Role {} {} user {} by identity provider {}",
+                        role.name,
+                        if should_have {
+                            "assigned to"
+                        } else {
+                            "revoked from"
+                        },
+                        user_id,
+                        provider.name
+                    ),
+                },
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
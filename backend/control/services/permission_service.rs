@@ -4,116 +4,45 @@
 //! Doesn't go in infrastructure, since it's used in the bridge layer with a handler as well. I guess.
 //! TODO implement these services at handler level for granular control.
 
+use chrono::{DateTime, Utc};
 use sea_orm::*;
 use serde_json;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::{
-    control::services::database_service::DatabaseService,
-    domain::permissions::{Permission, PermissionSet},
-    entity::models::{roles, users},
+    control::services::{audit_service::AuditService, user_service::UserService},
+    domain::{
+        audit::AuditEvent,
+        permissions::{Permission, PermissionEffect, PermissionSet},
+    },
+    entity::models::{prelude::UserPermissions, roles, user_permissions},
     infrastructure::app_error::AppError,
 };
 use axum::http::StatusCode;
 
 /// Service for permission-related business operations
-#[allow(dead_code)]
 pub struct PermissionService;
 
 impl PermissionService {
     /// Check if a user has a specific permission
-    #[allow(dead_code)]
     pub async fn has_permission(
         db: &DatabaseConnection,
         user_id: Uuid,
         permission: &Permission,
     ) -> Result<bool, AppError> {
-        let user = DatabaseService::find_one_with_tracking(
-            db,
-            "users",
-            users::Entity::find_by_id(user_id),
-        )
-        .await
-        .map_err(|e| AppError {
-            message: format!("Database error: {}", e),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?
-        .ok_or(AppError {
-            message: "User not found".to_string(),
-            status_code: StatusCode::NOT_FOUND,
-        })?;
-
-        // Check if user has a role
-        if let Some(role_id) = user.role_id {
-            let role = roles::Entity::find_by_id(role_id)
-                .one(db)
-                .await
-                .map_err(|e| AppError {
-                    message: format!("Database error: {}", e),
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                })?;
-
-            if let Some(role_model) = role {
-                let permissions: Vec<String> =
-                    serde_json::from_str(&role_model.permissions).unwrap_or_else(|_| vec![]);
-
-                let permission_set = PermissionSet::from_strings(permissions);
-                Ok(permission_set.contains(permission))
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(false)
-        }
+        let permission_set = Self::get_user_permissions(db, user_id).await?;
+        Ok(permission_set.contains(permission))
     }
 
     /// Check if a user has any of the given permissions
-    #[allow(dead_code)]
     pub async fn has_any_permission(
         db: &DatabaseConnection,
         user_id: Uuid,
-        _permissions: &[Permission],
+        permissions: &[Permission],
     ) -> Result<bool, AppError> {
-        let user = DatabaseService::find_one_with_tracking(
-            db,
-            "users",
-            users::Entity::find_by_id(user_id),
-        )
-        .await
-        .map_err(|e| AppError {
-            message: format!("Database error: {}", e),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?
-        .ok_or(AppError {
-            message: "User not found".to_string(),
-            status_code: StatusCode::NOT_FOUND,
-        })?;
-
-        if let Some(role_id) = user.role_id {
-            let role = roles::Entity::find_by_id(role_id)
-                .one(db)
-                .await
-                .map_err(|e| AppError {
-                    message: format!("Database error: {}", e),
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                })?;
-
-            if let Some(role_model) = role {
-                let permissions: Vec<String> =
-                    serde_json::from_str(&role_model.permissions).unwrap_or_else(|_| vec![]);
-
-                let permission_set = PermissionSet::from_strings(permissions.clone());
-                let permission_vec: Vec<Permission> = permissions
-                    .iter()
-                    .map(|p| Permission::from_string(p))
-                    .collect();
-                Ok(permission_set.contains_any(&permission_vec))
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(false)
-        }
+        let permission_set = Self::get_user_permissions(db, user_id).await?;
+        Ok(permission_set.contains_any(permissions))
     }
 
     /// Check if a user has all of the given permissions
@@ -121,91 +50,323 @@ impl PermissionService {
     pub async fn has_all_permissions(
         db: &DatabaseConnection,
         user_id: Uuid,
-        _permissions: &[Permission],
+        permissions: &[Permission],
     ) -> Result<bool, AppError> {
-        let user = DatabaseService::find_one_with_tracking(
-            db,
-            "users",
-            users::Entity::find_by_id(user_id),
-        )
-        .await
-        .map_err(|e| AppError {
+        let permission_set = Self::get_user_permissions(db, user_id).await?;
+        Ok(permission_set.contains_all(permissions))
+    }
+
+    /// Get all permissions for a user, merged over all their roles and those roles' parents,
+    /// then adjusted by the user's own unexpired grants and denies
+    pub async fn get_user_permissions(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<PermissionSet, AppError> {
+        let role_ids = UserService::get_role_ids(db, user_id).await?;
+
+        let mut permission_set = if role_ids.is_empty() {
+            PermissionSet::new()
+        } else {
+            let roles = Self::load_roles(db).await?;
+            Self::merge_role_permissions(&roles, &role_ids)
+        };
+
+        let overrides = Self::get_user_overrides(db, user_id).await?;
+        Self::apply_user_overrides(&mut permission_set, &overrides, Utc::now());
+
+        Ok(permission_set)
+    }
+
+    /// Per-user grants and denies, including expired ones the purge job hasn't removed yet
+    pub async fn get_user_overrides(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<user_permissions::Model>, AppError> {
+        UserPermissions::find()
+            .filter(user_permissions::Column::UserId.eq(user_id))
+            .order_by_asc(user_permissions::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })
+    }
+
+    /// Grants or denies a permission to one user, replacing an earlier record for the same permission
+    pub async fn set_user_override(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        permission: &str,
+        effect: PermissionEffect,
+        expires_at: Option<DateTime<Utc>>,
+        reason: Option<String>,
+        created_by: Uuid,
+    ) -> Result<user_permissions::Model, AppError> {
+        let permission = permission.trim();
+        if !Self::is_valid_permission(permission) {
+            return Err(AppError {
+                message: format!("Invalid permission: {}", permission),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+        if let Some(expires_at) = expires_at
+            && expires_at <= Utc::now()
+        {
+            return Err(AppError {
+                message: "expires_at must be in the future".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+        if UserService::find_user_by_id(db, user_id).await?.is_none() {
+            return Err(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            });
+        }
+
+        let database_error = |e: DbErr| AppError {
             message: format!("Database error: {}", e),
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?
-        .ok_or(AppError {
-            message: "User not found".to_string(),
-            status_code: StatusCode::NOT_FOUND,
-        })?;
+        };
 
-        if let Some(role_id) = user.role_id {
-            let role = roles::Entity::find_by_id(role_id)
-                .one(db)
-                .await
-                .map_err(|e| AppError {
-                    message: format!("Database error: {}", e),
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                })?;
-
-            if let Some(role_model) = role {
-                let permissions: Vec<String> =
-                    serde_json::from_str(&role_model.permissions).unwrap_or_else(|_| vec![]);
-
-                let permission_set = PermissionSet::from_strings(permissions.clone());
-                let permission_vec: Vec<Permission> = permissions
-                    .iter()
-                    .map(|p| Permission::from_string(p))
-                    .collect();
-                Ok(permission_set.contains_all(&permission_vec))
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(false)
+        let existing = UserPermissions::find()
+            .filter(user_permissions::Column::UserId.eq(user_id))
+            .filter(user_permissions::Column::Permission.eq(permission))
+            .one(db)
+            .await
+            .map_err(database_error)?;
+
+        let record = user_permissions::ActiveModel {
+            id: Set(existing.as_ref().map(|e| e.id).unwrap_or_else(Uuid::new_v4)),
+            user_id: Set(user_id),
+            permission: Set(permission.to_string()),
+            effect: Set(effect.as_str().to_string()),
+            expires_at: Set(expires_at.map(Into::into)),
+            reason: Set(reason),
+            created_by: Set(Some(created_by)),
+            created_at: Set(Some(Utc::now().into())),
+        };
+
+        match existing {
+            Some(_) => record.update(db).await,
+            None => record.insert(db).await,
         }
+        .map_err(database_error)
     }
 
-    /// Get all permissions for a user
-    #[allow(dead_code)]
-    pub async fn get_user_permissions(
+    /// Removes a per-user grant or deny, returning the removed record
+    pub async fn remove_user_override(
         db: &DatabaseConnection,
         user_id: Uuid,
-    ) -> Result<PermissionSet, AppError> {
-        let user = DatabaseService::find_one_with_tracking(
-            db,
-            "users",
-            users::Entity::find_by_id(user_id),
-        )
-        .await
-        .map_err(|e| AppError {
+        override_id: Uuid,
+    ) -> Result<user_permissions::Model, AppError> {
+        let record = UserPermissions::find_by_id(override_id)
+            .filter(user_permissions::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .ok_or(AppError {
+                message: "Permission grant not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        UserPermissions::delete_by_id(override_id)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(record)
+    }
+
+    /// Deletes expired grants and denies and records an audit entry, returns how many were removed
+    pub async fn purge_expired_overrides(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let result = UserPermissions::delete_many()
+            .filter(user_permissions::Column::ExpiresAt.lte(now))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if result.rows_affected > 0 {
+            AuditService::record(
+                db,
+                AuditEvent {
+                    action: "PERMISSION_PURGE".to_string(),
+                    path: "scheduler:permission-purge".to_string(),
+                    status_code: None,
+                    user_id: None,
+                    actor_id: None,
+                    ip_address: None,
+                    user_agent: None,
+                    message: format!(
+                        "Purged {} expired user permission grants and denies",
+                        result.rows_affected
+                    ),
+                },
+            )
+            .await?;
+        }
+
+        Ok(result.rows_affected)
+    }
+
+    /// Applies grants and then denies, so a deny always wins over a grant or a role
+    fn apply_user_overrides(
+        permission_set: &mut PermissionSet,
+        overrides: &[user_permissions::Model],
+        now: DateTime<Utc>,
+    ) {
+        for record in overrides {
+            if record
+                .expires_at
+                .is_some_and(|expires_at| expires_at.to_utc() <= now)
+            {
+                continue;
+            }
+
+            let permission = Permission::from_string(&record.permission);
+            match PermissionEffect::from_db(&record.effect) {
+                PermissionEffect::Grant => permission_set.add(permission),
+                PermissionEffect::Deny => permission_set.deny(permission),
+            }
+        }
+    }
+
+    /// Union of the resolved permissions of several roles
+    fn merge_role_permissions(
+        roles: &HashMap<i32, roles::Model>,
+        role_ids: &[i32],
+    ) -> PermissionSet {
+        let mut permission_set = PermissionSet::new();
+        for role_id in role_ids {
+            permission_set.merge(&Self::resolve_role_permissions(roles, *role_id));
+        }
+        permission_set
+    }
+
+    /// Rejects handing out a permission the caller doesn't hold, so `admin:users` can't be
+    /// turned into `*` by granting it to oneself
+    pub fn ensure_can_grant(
+        caller: &PermissionSet,
+        permission: &Permission,
+    ) -> Result<(), AppError> {
+        if caller.covers(permission) {
+            return Ok(());
+        }
+        Err(AppError {
+            message: format!(
+                "You can't hand out {} without holding it yourself",
+                permission.to_string()
+            ),
+            status_code: StatusCode::FORBIDDEN,
+        })
+    }
+
+    /// Rejects assigning a role that, with the permissions it inherits, holds more than the caller
+    pub async fn ensure_can_assign_role(
+        db: &DatabaseConnection,
+        caller: &PermissionSet,
+        role_id: i32,
+    ) -> Result<(), AppError> {
+        let roles = Self::load_roles(db).await?;
+        for permission in Self::resolve_role_permissions(&roles, role_id).to_vec() {
+            Self::ensure_can_grant(caller, &permission)?;
+        }
+        Ok(())
+    }
+
+    /// Rejects role permissions, and permissions inherited from the parent, the caller doesn't hold
+    pub async fn ensure_can_grant_role(
+        db: &DatabaseConnection,
+        caller: &PermissionSet,
+        permissions: &[String],
+        parent_role_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        for permission in permissions {
+            Self::ensure_can_grant(caller, &Permission::from_string(permission.trim()))?;
+        }
+        if let Some(parent_role_id) = parent_role_id {
+            Self::ensure_can_assign_role(db, caller, parent_role_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Rejects a parent that doesn't exist or whose inheritance chain leads back to the role
+    pub async fn validate_parent_role(
+        db: &DatabaseConnection,
+        role_id: Option<i32>,
+        parent_role_id: i32,
+    ) -> Result<(), AppError> {
+        let roles = Self::load_roles(db).await?;
+
+        if !roles.contains_key(&parent_role_id) {
+            return Err(AppError {
+                message: "Parent role not found".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        if let Some(role_id) = role_id
+            && Self::ancestors(&roles, parent_role_id).contains(&role_id)
+        {
+            return Err(AppError {
+                message: "Role inheritance would create a cycle".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// All roles by ID, the table is small enough to walk inheritance chains in memory
+    async fn load_roles(db: &DatabaseConnection) -> Result<HashMap<i32, roles::Model>, AppError> {
+        let roles = roles::Entity::find().all(db).await.map_err(|e| AppError {
             message: format!("Database error: {}", e),
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?
-        .ok_or(AppError {
-            message: "User not found".to_string(),
-            status_code: StatusCode::NOT_FOUND,
         })?;
 
-        if let Some(role_id) = user.role_id {
-            let role = roles::Entity::find_by_id(role_id)
-                .one(db)
-                .await
-                .map_err(|e| AppError {
-                    message: format!("Database error: {}", e),
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                })?;
-
-            if let Some(role_model) = role {
-                let permissions: Vec<String> =
-                    serde_json::from_str(&role_model.permissions).unwrap_or_else(|_| vec![]);
-
-                Ok(PermissionSet::from_strings(permissions))
-            } else {
-                Ok(PermissionSet::new())
+        Ok(roles.into_iter().map(|role| (role.id, role)).collect())
+    }
+
+    /// The role itself followed by its parents, stops at a cycle instead of looping
+    fn ancestors(roles: &HashMap<i32, roles::Model>, role_id: i32) -> Vec<i32> {
+        let mut chain = Vec::new();
+        let mut next = Some(role_id);
+
+        while let Some(id) = next {
+            if chain.contains(&id) {
+                break;
             }
-        } else {
-            Ok(PermissionSet::new())
+            let Some(role) = roles.get(&id) else {
+                break;
+            };
+            chain.push(id);
+            next = role.parent_role_id;
+        }
+
+        chain
+    }
+
+    fn resolve_role_permissions(roles: &HashMap<i32, roles::Model>, role_id: i32) -> PermissionSet {
+        let mut permission_set = PermissionSet::new();
+
+        for id in Self::ancestors(roles, role_id) {
+            let permissions: Vec<String> =
+                serde_json::from_str(&roles[&id].permissions).unwrap_or_else(|_| vec![]);
+            permission_set.merge(&PermissionSet::from_strings(permissions));
         }
+
+        permission_set
     }
 
     /// Get all available permissions in the system
@@ -265,7 +426,6 @@ impl PermissionService {
     }
 
     /// Check if a permission string is valid
-    #[allow(dead_code)]
     pub fn is_valid_permission(permission_str: &str) -> bool {
         match permission_str {
             "*" | "admin:read" | "admin:write" | "admin:delete" | "admin:users" | "admin:roles"
@@ -276,3 +436,221 @@ impl PermissionService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(id: i32, permissions: &[&str], parent_role_id: Option<i32>) -> (i32, roles::Model) {
+        (
+            id,
+            roles::Model {
+                id,
+                name: format!("role-{}", id),
+                description: None,
+                permissions: serde_json::to_string(permissions).unwrap(),
+                created_at: None,
+                updated_at: None,
+                mfa_required: false,
+                parent_role_id,
+                max_sessions: None,
+                session_limit_action: None,
+                idle_timeout_minutes: None,
+                session_lifetime_hours: None,
+            },
+        )
+    }
+
+    #[test]
+    fn wildcards_match_by_segment() {
+        let set =
+            PermissionSet::from_strings(vec!["admin:*".to_string(), "user:*:own".to_string()]);
+
+        assert!(set.contains(&Permission::AdminUsers));
+        assert!(set.contains(&Permission::Custom("admin:users:export".to_string())));
+        assert!(set.contains(&Permission::Custom("user:write:own".to_string())));
+        assert!(!set.contains(&Permission::UserWrite));
+        assert!(!set.contains(&Permission::Custom("user:write:any".to_string())));
+        assert!(!set.contains(&Permission::SystemLogs));
+        assert!(PermissionSet::from_vec(vec![Permission::All]).contains(&Permission::SystemLogs));
+    }
+
+    #[test]
+    fn roles_inherit_along_the_chain_and_survive_cycles() {
+        let roles: HashMap<i32, roles::Model> = [
+            role(1, &["admin:read"], None),
+            role(2, &["admin:write"], Some(1)),
+            role(3, &["admin:delete"], Some(2)),
+            // Corrupt data, 4 and 5 point at each other
+            role(4, &["system:logs"], Some(5)),
+            role(5, &["system:health"], Some(4)),
+        ]
+        .into_iter()
+        .collect();
+
+        let editor = PermissionService::resolve_role_permissions(&roles, 3);
+        assert!(editor.contains_all(&[
+            Permission::AdminRead,
+            Permission::AdminWrite,
+            Permission::AdminDelete
+        ]));
+        assert!(
+            !PermissionService::resolve_role_permissions(&roles, 1)
+                .contains(&Permission::AdminWrite)
+        );
+
+        assert_eq!(PermissionService::ancestors(&roles, 3), vec![3, 2, 1]);
+        assert_eq!(PermissionService::ancestors(&roles, 4), vec![4, 5]);
+    }
+
+    #[test]
+    fn multiple_roles_merge_their_permissions() {
+        let roles: HashMap<i32, roles::Model> = [
+            role(1, &["admin:read"], None),
+            role(2, &["admin:write"], Some(1)),
+            role(3, &["system:logs"], None),
+        ]
+        .into_iter()
+        .collect();
+
+        let merged = PermissionService::merge_role_permissions(&roles, &[2, 3]);
+        assert!(merged.contains_all(&[
+            Permission::AdminRead,
+            Permission::AdminWrite,
+            Permission::SystemLogs
+        ]));
+        assert!(
+            !PermissionService::merge_role_permissions(&roles, &[3])
+                .contains(&Permission::AdminRead)
+        );
+        assert!(
+            PermissionService::merge_role_permissions(&roles, &[])
+                .to_vec()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn user_denies_win_over_roles_and_grants() {
+        let now = Utc::now();
+        let record = |permission: &str, effect: PermissionEffect, expires_in: Option<i64>| {
+            user_permissions::Model {
+                id: Uuid::new_v4(),
+                user_id: Uuid::nil(),
+                permission: permission.to_string(),
+                effect: effect.as_str().to_string(),
+                expires_at: expires_in.map(|secs| (now + chrono::Duration::seconds(secs)).into()),
+                reason: None,
+                created_by: None,
+                created_at: None,
+            }
+        };
+
+        let mut set = PermissionSet::from_strings(vec!["admin:*".to_string()]);
+        PermissionService::apply_user_overrides(
+            &mut set,
+            &[
+                record("admin:database", PermissionEffect::Deny, None),
+                record("system:logs", PermissionEffect::Grant, Some(60)),
+                record("system:health", PermissionEffect::Grant, Some(-60)),
+                record("user:*", PermissionEffect::Grant, None),
+                record("user:delete", PermissionEffect::Deny, None),
+            ],
+            now,
+        );
+
+        assert!(set.contains(&Permission::AdminUsers));
+        assert!(!set.contains(&Permission::AdminDatabase));
+        assert!(set.contains(&Permission::SystemLogs));
+        assert!(!set.contains(&Permission::SystemHealth));
+        assert!(set.contains(&Permission::UserRead));
+        assert!(!set.contains(&Permission::UserDelete));
+    }
+
+    #[test]
+    fn scopes_never_widen_a_set() {
+        let mut set =
+            PermissionSet::from_strings(vec!["admin:logs".to_string(), "user:read".to_string()]);
+        set.restrict_to(&[
+            Permission::Custom("admin:*".to_string()),
+            Permission::SystemLogs,
+        ]);
+
+        assert!(set.contains(&Permission::AdminLogs));
+        assert!(!set.contains(&Permission::UserRead));
+        assert!(!set.contains(&Permission::SystemLogs));
+
+        set.restrict_to(&[Permission::AdminLogs, Permission::UserRead]);
+        assert!(set.contains(&Permission::AdminLogs));
+        assert!(!set.contains(&Permission::UserRead));
+    }
+
+    #[test]
+    fn callers_only_hand_out_what_they_hold() {
+        let forbidden = |result: Result<(), AppError>| {
+            result.is_err_and(|e| e.status_code == StatusCode::FORBIDDEN)
+        };
+
+        let user_admin = PermissionSet::from_vec(vec![Permission::AdminUsers]);
+        assert!(forbidden(PermissionService::ensure_can_grant(
+            &user_admin,
+            &Permission::from_string("*")
+        )));
+        assert!(forbidden(PermissionService::ensure_can_grant(
+            &user_admin,
+            &Permission::from_string("admin:*")
+        )));
+        assert!(PermissionService::ensure_can_grant(&user_admin, &Permission::AdminUsers).is_ok());
+
+        // A wildcard the caller holds doesn't count when part of it is denied to them
+        let mut restricted = PermissionSet::from_vec(vec![Permission::All]);
+        restricted.deny(Permission::AdminDatabase);
+        assert!(forbidden(PermissionService::ensure_can_grant(
+            &restricted,
+            &Permission::from_string("admin:*")
+        )));
+        assert!(PermissionService::ensure_can_grant(&restricted, &Permission::AdminUsers).is_ok());
+    }
+
+    #[tokio::test]
+    async fn roles_only_get_what_their_editor_holds() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let table = Schema::new(DbBackend::Sqlite).create_table_from_entity(roles::Entity);
+        db.execute(db.get_database_backend().build(&table))
+            .await
+            .unwrap();
+        for (_, role) in [role(1, &["*"], None), role(2, &["admin:read"], None)] {
+            role.into_active_model().insert(&db).await.unwrap();
+        }
+
+        let role_admin =
+            PermissionSet::from_strings(vec!["admin:roles".to_string(), "admin:read".to_string()]);
+        let check = |permissions: &[&str], parent_role_id: Option<i32>| {
+            let permissions: Vec<String> = permissions.iter().map(|p| String::from(*p)).collect();
+            let (db, role_admin) = (&db, &role_admin);
+            async move {
+                PermissionService::ensure_can_grant_role(
+                    db,
+                    role_admin,
+                    &permissions,
+                    parent_role_id,
+                )
+                .await
+                .map_err(|e| e.status_code)
+            }
+        };
+
+        assert_eq!(check(&["*"], None).await, Err(StatusCode::FORBIDDEN));
+        assert_eq!(
+            check(&[" admin:* "], None).await,
+            Err(StatusCode::FORBIDDEN)
+        );
+        // Inherited permissions count as much as listed ones
+        assert_eq!(
+            check(&["admin:read"], Some(1)).await,
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(check(&["admin:read"], Some(2)).await, Ok(()));
+        assert_eq!(check(&["admin:roles"], None).await, Ok(()));
+    }
+}
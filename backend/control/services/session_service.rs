@@ -1,11 +1,15 @@
 use chrono::{Duration, Utc};
 use sea_orm::prelude::Expr;
 use sea_orm::*;
+use std::env;
 use uuid::Uuid;
 
-use crate::control::services::database_service::DatabaseService;
-use crate::entity::models::{prelude::*, user_sessions};
-use crate::infrastructure::app_error::AppError;
+use crate::control::services::{
+    database_service::DatabaseService, token_service::TokenService, user_service::UserService,
+};
+use crate::domain::session::{RoleSessionPolicy, SessionLimitAction, SessionPolicy};
+use crate::entity::models::{prelude::*, refresh_tokens, roles, user_sessions};
+use crate::infrastructure::{app_error::AppError, websocket::WEBSOCKET_MANAGER};
 use axum::http::StatusCode;
 
 /// Service for session-related business operations
@@ -19,13 +23,74 @@ impl SessionService {
         user_agent: Option<String>,
         ip_address: Option<String>,
         session_token: &str,
+    ) -> Result<user_sessions::Model, AppError> {
+        let policy = Self::policy_for_user(db, user_id).await?;
+        Self::enforce_session_limit(db, user_id, &policy).await?;
+
+        // The session lives as long as its refresh token family
+        let expires_at = Utc::now() + policy.lifetime;
+
+        Self::insert_session(
+            db,
+            user_id,
+            user_agent,
+            ip_address,
+            session_token,
+            expires_at,
+            None,
+        )
+        .await
+    }
+
+    /// Creates a session for an admin impersonating a user
+    /// It ends after IMPERSONATION_TTL_MINUTES and doesn't count against the user's session
+    /// limit, so it never signs the user out
+    pub async fn create_impersonation_session(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        impersonator_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        session_token: &str,
+    ) -> Result<user_sessions::Model, AppError> {
+        let expires_at = Utc::now() + Self::impersonation_lifetime();
+
+        Self::insert_session(
+            db,
+            user_id,
+            user_agent,
+            ip_address,
+            session_token,
+            expires_at,
+            Some(impersonator_id),
+        )
+        .await
+    }
+
+    /// How long an impersonation session lasts, configured with IMPERSONATION_TTL_MINUTES
+    /// (defaults to 30)
+    pub fn impersonation_lifetime() -> Duration {
+        let minutes = env::var("IMPERSONATION_TTL_MINUTES")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(30);
+
+        Duration::minutes(minutes)
+    }
+
+    async fn insert_session(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        user_agent: Option<String>,
+        ip_address: Option<String>,
+        session_token: &str,
+        expires_at: chrono::DateTime<Utc>,
+        impersonator_id: Option<Uuid>,
     ) -> Result<user_sessions::Model, AppError> {
         // Use the session token directly (UUID from JWT claims)
         let session_token_str = session_token.to_string();
 
-        // Calculate expiration time (24 hours from now)
-        let expires_at = Utc::now() + Duration::hours(24);
-
         // Create session ID
         let session_id = Uuid::new_v4();
 
@@ -40,6 +105,7 @@ impl SessionService {
             last_activity: Set(Some(Utc::now().fixed_offset())),
             expires_at: Set(expires_at.fixed_offset()),
             is_active: Set(true),
+            impersonator_id: Set(impersonator_id),
         };
 
         // Insert into database
@@ -54,6 +120,340 @@ impl SessionService {
         Ok(session)
     }
 
+    /// Lifetime of a session and therefore of its refresh tokens
+    /// Configured with REFRESH_TOKEN_TTL_HOURS, defaults to 7 days
+    pub fn session_lifetime() -> Duration {
+        let hours = env::var("REFRESH_TOKEN_TTL_HOURS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(24 * 7);
+
+        Duration::hours(hours)
+    }
+
+    /// Server wide session policy, roles can override each setting
+    /// Configured with SESSION_MAX_CONCURRENT (unlimited if 0 or unset), SESSION_LIMIT_ACTION
+    /// (evict or reject, defaults to evict), SESSION_IDLE_TIMEOUT_MINUTES (off if 0 or unset)
+    /// and REFRESH_TOKEN_TTL_HOURS for the absolute lifetime
+    pub fn default_policy() -> SessionPolicy {
+        let positive = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .filter(|value| *value > 0)
+        };
+
+        SessionPolicy {
+            max_sessions: positive("SESSION_MAX_CONCURRENT").map(|max| max as u64),
+            limit_action: env::var("SESSION_LIMIT_ACTION")
+                .ok()
+                .and_then(|value| SessionLimitAction::parse(&value))
+                .unwrap_or(SessionLimitAction::Evict),
+            idle_timeout: positive("SESSION_IDLE_TIMEOUT_MINUTES").map(Duration::minutes),
+            lifetime: Self::session_lifetime(),
+        }
+    }
+
+    /// Session policy for a user, combining the defaults with the settings of all their roles
+    pub async fn policy_for_user(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<SessionPolicy, AppError> {
+        let role_ids = UserService::get_role_ids(db, user_id).await?;
+        if role_ids.is_empty() {
+            return Ok(Self::default_policy());
+        }
+
+        let roles = Roles::find()
+            .filter(roles::Column::Id.is_in(role_ids))
+            .all(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let role_policies: Vec<RoleSessionPolicy> = roles
+            .iter()
+            .map(|role| RoleSessionPolicy {
+                max_sessions: role
+                    .max_sessions
+                    .filter(|max| *max > 0)
+                    .map(|max| max as u64),
+                limit_action: role
+                    .session_limit_action
+                    .as_deref()
+                    .and_then(SessionLimitAction::parse),
+                idle_timeout: role
+                    .idle_timeout_minutes
+                    .filter(|minutes| *minutes > 0)
+                    .map(|minutes| Duration::minutes(minutes.into())),
+                lifetime: role
+                    .session_lifetime_hours
+                    .filter(|hours| *hours > 0)
+                    .map(|hours| Duration::hours(hours.into())),
+            })
+            .collect();
+
+        Ok(Self::default_policy().with_roles(&role_policies))
+    }
+
+    /// Makes room for a new session, or refuses it, when the user is at their session limit
+    async fn enforce_session_limit(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        policy: &SessionPolicy,
+    ) -> Result<(), AppError> {
+        let Some(max_sessions) = policy.max_sessions else {
+            return Ok(());
+        };
+
+        let active = Self::get_user_active_session_count(db, user_id).await?;
+        if active < max_sessions {
+            return Ok(());
+        }
+
+        if policy.limit_action == SessionLimitAction::Reject {
+            return Err(AppError {
+                message: "Too many active sessions, sign out on another device first".to_string(),
+                status_code: StatusCode::CONFLICT,
+            });
+        }
+
+        let oldest = UserSessions::find()
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::IsActive.eq(true))
+            .filter(user_sessions::Column::ExpiresAt.gt(Utc::now().fixed_offset()))
+            .order_by_asc(user_sessions::Column::CreatedAt)
+            .limit(active - max_sessions + 1)
+            .all(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        for session in oldest {
+            tracing::info!(
+                session_id = %session.id,
+                user_id = %user_id,
+                "Evicting oldest session, session limit reached"
+            );
+            if let Ok(session_id) = Uuid::parse_str(&session.session_token) {
+                Self::invalidate_session(db, session_id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ends sessions that were idle for too long or outlived the absolute lifetime
+    /// The policy is read on every check, so tightening a role applies to existing sessions
+    async fn enforce_policy(
+        db: &DatabaseConnection,
+        session: &user_sessions::Model,
+    ) -> Result<(), AppError> {
+        let policy = Self::policy_for_user(db, session.user_id).await?;
+        let now = Utc::now();
+        let started_at = session.created_at.map(|dt| dt.to_utc()).unwrap_or(now);
+
+        let error = if let Some(idle_timeout) = policy.idle_timeout
+            && session
+                .last_activity
+                .map(|dt| dt.to_utc())
+                .unwrap_or(started_at)
+                + idle_timeout
+                < now
+        {
+            "Session timed out due to inactivity"
+        } else if started_at + policy.lifetime < now {
+            "Session expired"
+        } else {
+            return Ok(());
+        };
+
+        UserSessions::update_many()
+            .col_expr(user_sessions::Column::IsActive, Expr::value(false))
+            .filter(user_sessions::Column::Id.eq(session.id))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to end session: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        if let Ok(session_id) = Uuid::parse_str(&session.session_token) {
+            WEBSOCKET_MANAGER.close_session(session_id).await;
+        }
+
+        Err(AppError {
+            message: error.to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        })
+    }
+
+    /// Issues a new refresh token for a session
+    /// Returns the plain token, only its hash is stored
+    pub async fn issue_refresh_token(
+        db: &DatabaseConnection,
+        session: &user_sessions::Model,
+    ) -> Result<String, AppError> {
+        let token = TokenService::generate_opaque_token();
+
+        let refresh_token_active_model = refresh_tokens::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            session_id: Set(session.id),
+            token_hash: Set(TokenService::hash_opaque_token(&token)),
+            created_at: Set(Some(Utc::now().fixed_offset())),
+            // Rotation never extends the session, so every token expires with it
+            expires_at: Set(session.expires_at),
+            used_at: Set(None),
+        };
+
+        RefreshTokens::insert(refresh_token_active_model)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to create refresh token: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(token)
+    }
+
+    /// Exchanges a refresh token for a new one
+    /// Presenting a token that was already rotated revokes the whole session family
+    /// Returns the session the token belongs to and the new refresh token
+    pub async fn rotate_refresh_token(
+        db: &DatabaseConnection,
+        refresh_token: &str,
+    ) -> Result<(user_sessions::Model, String), AppError> {
+        let invalid_token = || AppError {
+            message: "Invalid refresh token".to_string(),
+            status_code: StatusCode::UNAUTHORIZED,
+        };
+
+        let stored_token = DatabaseService::find_one_with_tracking(
+            db,
+            "refresh_tokens",
+            RefreshTokens::find().filter(
+                refresh_tokens::Column::TokenHash
+                    .eq(TokenService::hash_opaque_token(refresh_token)),
+            ),
+        )
+        .await
+        .map_err(|e| AppError {
+            message: format!("Database error: {}", e),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?
+        .ok_or_else(invalid_token)?;
+
+        let session = DatabaseService::find_one_with_tracking(
+            db,
+            "user_sessions",
+            UserSessions::find_by_id(stored_token.session_id),
+        )
+        .await
+        .map_err(|e| AppError {
+            message: format!("Database error: {}", e),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?
+        .ok_or_else(invalid_token)?;
+
+        // A rotated token showing up again means it was copied, kill the whole family
+        if stored_token.used_at.is_some() {
+            Self::revoke_refresh_token_family(db, &session).await?;
+            return Err(AppError {
+                message: "Refresh token reuse detected, session revoked".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
+
+        if !session.is_active {
+            return Err(AppError {
+                message: "Session has been invalidated".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
+
+        Self::enforce_policy(db, &session).await?;
+
+        let now = Utc::now();
+        if stored_token.expires_at.to_utc() < now || session.expires_at.to_utc() < now {
+            return Err(AppError {
+                message: "Refresh token expired".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
+
+        // Mark the token as used, guarded on used_at so two concurrent refreshes can't both win
+        let result = RefreshTokens::update_many()
+            .col_expr(
+                refresh_tokens::Column::UsedAt,
+                Expr::value(now.fixed_offset()),
+            )
+            .filter(refresh_tokens::Column::Id.eq(stored_token.id))
+            .filter(refresh_tokens::Column::UsedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to rotate refresh token: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if result.rows_affected == 0 {
+            Self::revoke_refresh_token_family(db, &session).await?;
+            return Err(AppError {
+                message: "Refresh token reuse detected, session revoked".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
+
+        let new_refresh_token = Self::issue_refresh_token(db, &session).await?;
+
+        Ok((session, new_refresh_token))
+    }
+
+    /// Revokes a session and every refresh token issued for it
+    pub async fn revoke_refresh_token_family(
+        db: &DatabaseConnection,
+        session: &user_sessions::Model,
+    ) -> Result<(), AppError> {
+        tracing::warn!(
+            session_id = %session.id,
+            user_id = %session.user_id,
+            "Revoking session refresh token family"
+        );
+
+        UserSessions::update_many()
+            .col_expr(user_sessions::Column::IsActive, Expr::value(false))
+            .filter(user_sessions::Column::Id.eq(session.id))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to revoke session: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        if let Ok(session_id) = Uuid::parse_str(&session.session_token) {
+            WEBSOCKET_MANAGER.close_session(session_id).await;
+        }
+
+        RefreshTokens::update_many()
+            .col_expr(
+                refresh_tokens::Column::UsedAt,
+                Expr::value(Utc::now().fixed_offset()),
+            )
+            .filter(refresh_tokens::Column::SessionId.eq(session.id))
+            .filter(refresh_tokens::Column::UsedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to revoke refresh tokens: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(())
+    }
+
     /// Validates that a session exists and is active
     pub async fn validate_session(
         db: &DatabaseConnection,
@@ -92,9 +492,26 @@ impl SessionService {
             });
         }
 
+        Self::enforce_policy(db, &session).await?;
+
         Ok(session)
     }
 
+    /// Looks up a session by the JWT session ID without checking whether it's still valid
+    pub async fn find_session(
+        db: &DatabaseConnection,
+        session_token: &str,
+    ) -> Result<Option<user_sessions::Model>, AppError> {
+        UserSessions::find()
+            .filter(user_sessions::Column::SessionToken.eq(session_token))
+            .one(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })
+    }
+
     /// Updates session activity timestamp
     pub async fn update_session_activity(
         db: &DatabaseConnection,
@@ -105,7 +522,8 @@ impl SessionService {
         let session = DatabaseService::find_one_with_tracking(
             db,
             "user_sessions",
-            UserSessions::find().filter(user_sessions::Column::SessionToken.eq(session_id.to_string())),
+            UserSessions::find()
+                .filter(user_sessions::Column::SessionToken.eq(session_id.to_string())),
         )
         .await
         .map_err(|e| AppError {
@@ -167,7 +585,8 @@ impl SessionService {
         let session = DatabaseService::find_one_with_tracking(
             db,
             "user_sessions",
-            UserSessions::find().filter(user_sessions::Column::SessionToken.eq(session_id.to_string())),
+            UserSessions::find()
+                .filter(user_sessions::Column::SessionToken.eq(session_id.to_string())),
         )
         .await
         .map_err(|e| AppError {
@@ -194,9 +613,66 @@ impl SessionService {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             })?;
 
+        WEBSOCKET_MANAGER.close_session(session_id).await;
+
         Ok(())
     }
 
+    /// Invalidates one of a user's own sessions by its row ID
+    /// Returns the session so callers can tell whether it was the current one
+    pub async fn invalidate_user_session(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        id: Uuid,
+    ) -> Result<user_sessions::Model, AppError> {
+        let session = UserSessions::find_by_id(id)
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::IsActive.eq(true))
+            .one(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .ok_or(AppError {
+                message: "Session not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        let session_id = Uuid::parse_str(&session.session_token).map_err(|_| AppError {
+            message: "Invalid session".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+        Self::invalidate_session(db, session_id).await?;
+
+        Ok(session)
+    }
+
+    /// Invalidates every session of a user except the one with the given JWT session ID
+    pub async fn invalidate_other_user_sessions(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        keep_session_id: Uuid,
+    ) -> Result<u64, AppError> {
+        let result = UserSessions::update_many()
+            .col_expr(user_sessions::Column::IsActive, Expr::value(false))
+            .filter(user_sessions::Column::UserId.eq(user_id))
+            .filter(user_sessions::Column::IsActive.eq(true))
+            .filter(user_sessions::Column::SessionToken.ne(keep_session_id.to_string()))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to invalidate user sessions: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        WEBSOCKET_MANAGER
+            .close_user_sessions(user_id, Some(keep_session_id))
+            .await;
+
+        Ok(result.rows_affected)
+    }
+
     /// Invalidates all sessions for a user
     pub async fn invalidate_all_user_sessions(
         db: &DatabaseConnection,
@@ -213,6 +689,8 @@ impl SessionService {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             })?;
 
+        WEBSOCKET_MANAGER.close_user_sessions(user_id, None).await;
+
         Ok(result.rows_affected)
     }
 
@@ -234,7 +712,6 @@ impl SessionService {
     }
 
     /// Gets active session count for a user
-    #[allow(dead_code)]
     pub async fn get_user_active_session_count(
         db: &DatabaseConnection,
         user_id: Uuid,
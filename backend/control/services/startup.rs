@@ -7,6 +7,7 @@ use crate::control::services::{server_config::ServerConfigService, user_service:
 use crate::domain::permissions::DefaultPermissions;
 use crate::entity::models::roles;
 use crate::infrastructure::app_error::AppError;
+use crate::infrastructure::jwt_keys::JwtKeyManager;
 use crate::infrastructure::{
     database::DatabaseManager, job_queue::JobQueueManager, scheduler::SchedulerManager,
     server::ServerManager,
@@ -28,6 +29,9 @@ impl StartupService {
         let environment = env::var("ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
         println!("Environment: {}", environment);
 
+        // Load JWT signing keys, refuses the default secret in production
+        JwtKeyManager::initialize()?;
+
         // Create database connection
         let db = DatabaseManager::create_connection().await?;
 
@@ -37,22 +41,34 @@ impl StartupService {
         // Setup job queue storage
         DatabaseManager::setup_job_queue_storage(&pool).await?;
 
-        // Create job storage
+        // Create job storage used to queue outgoing mail
         let job_storage = JobQueueManager::create_storage(pool);
-
-        // Queue test job
-        println!("Queuing test job!");
-        JobQueueManager::produce_messages(&job_storage).await?;
+        JobQueueManager::initialize(job_storage);
 
         // Seed default roles if enabled
         Self::seed_default_roles(&db).await?;
 
+        // Give accounts from before email normalization their normalized address
+        Self::normalize_emails(&db).await?;
+
         // Seed admin user if enabled
         Self::seed_admin_user(&db).await?;
 
         Ok(db)
     }
 
+    /// Backfills normalized email addresses and warns about accounts that clash
+    async fn normalize_emails(db: &DatabaseConnection) -> Result<(), Box<dyn std::error::Error>> {
+        let unresolved = UserService::backfill_normalized_emails(db).await?;
+        if unresolved > 0 {
+            println!(
+                "⚠️  {} accounts share their email address with another account or have an invalid one, see GET /api/v1/admin/users/duplicates",
+                unresolved
+            );
+        }
+        Ok(())
+    }
+
     /// Seeds the admin user if it doesn't exist
     async fn seed_admin_user(db: &DatabaseConnection) -> Result<(), Box<dyn std::error::Error>> {
         // Check if admin user creation is enabled
@@ -90,7 +106,7 @@ impl StartupService {
                 let admin_role_id = admin_role.map(|role| role.id);
 
                 // Create admin user with admin role
-                match UserService::create_user_with_role(
+                match UserService::create_initial_admin(
                     db,
                     admin_email.clone(),
                     admin_password,
@@ -207,9 +223,9 @@ impl StartupService {
     }
 
     /// Runs the task scheduler
-    pub async fn run_scheduler() -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn run_scheduler(db: DatabaseConnection) -> Result<(), Box<dyn std::error::Error>> {
         let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env file");
-        SchedulerManager::run_scheduler(&database_url).await?;
+        SchedulerManager::run_scheduler(&database_url, db).await?;
         Ok(())
     }
 }
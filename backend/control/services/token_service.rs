@@ -1,14 +1,20 @@
 //! Token service for extracting, decoding, and validating JWT tokens
 use axum::http::{StatusCode, header};
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use base64::Engine;
+use rand_core::RngCore;
 use sea_orm::DatabaseConnection;
-use std::env;
+use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 use crate::{
     control::services::session_service::SessionService,
-    infrastructure::{app_error::AppError, jwt_claims::Claims},
+    infrastructure::{
+        app_error::AppError,
+        jwt_claims::Claims,
+        jwt_keys::JwtKeyManager,
+        session_cookies::{SESSION_COOKIE, SESSION_COOKIES},
+    },
 };
 
 /// Service for JWT token operations
@@ -32,10 +38,11 @@ impl TokenService {
 
     /// Extracts and validates a JWT token with session validation
     /// Returns the user ID and session ID if both token and session are valid
+    /// Returns the user, session and, while impersonating, admin IDs
     pub async fn extract_and_validate_token_with_session(
         db: &DatabaseConnection,
         token: &str,
-    ) -> Result<(Uuid, Uuid), AppError> {
+    ) -> Result<(Uuid, Uuid, Option<Uuid>), AppError> {
         // Validate JWT token and extract claims
         let claims = Self::validate_token_claims(&token)?;
 
@@ -52,12 +59,27 @@ impl TokenService {
         })?;
 
         // Validate session exists and is active
-        SessionService::validate_session(db, &claims.session_id).await?;
+        let session = SessionService::validate_session(db, &claims.session_id).await?;
+
+        // The session decides who is impersonating, the claim has to agree with it
+        let impersonator_id = claims
+            .act
+            .map(|act| Uuid::parse_str(&act.sub))
+            .transpose()
+            .ok()
+            .flatten();
+        if impersonator_id != session.impersonator_id {
+            return Err(AppError {
+                message: "Invalid session".to_string(),
+                status_code: StatusCode::UNAUTHORIZED,
+            });
+        }
 
-        Ok((user_id, session_id))
+        Ok((user_id, session_id, impersonator_id))
     }
 
-    /// Extracts JWT token from Authorization header
+    /// Extracts JWT token from Authorization header, or from the session cookie when
+    /// session cookies are enabled
     /// Returns the token (no validation is performed)
     pub fn extract_token_from_header(
         request: &axum::http::Request<axum::body::Body>,
@@ -65,11 +87,16 @@ impl TokenService {
         let auth_header = request
             .headers()
             .get(header::AUTHORIZATION)
-            .and_then(|header| header.to_str().ok())
-            .ok_or(AppError {
-                message: "Missing Authorization header".to_string(),
-                status_code: StatusCode::UNAUTHORIZED,
-            })?;
+            .and_then(|header| header.to_str().ok());
+
+        let Some(auth_header) = auth_header else {
+            return SESSION_COOKIES
+                .read(request.headers(), SESSION_COOKIE)
+                .ok_or(AppError {
+                    message: "Missing Authorization header".to_string(),
+                    status_code: StatusCode::UNAUTHORIZED,
+                });
+        };
 
         let token = auth_header.strip_prefix("Bearer ").ok_or(AppError {
             message: "Invalid Authorization header format".to_string(),
@@ -79,20 +106,31 @@ impl TokenService {
         Ok(token.to_string())
     }
 
+    /// Extracts an API key from the X-Api-Key header or an `Authorization: ApiKey <key>` header
+    /// Returns None when the request doesn't carry one (no validation is performed)
+    pub fn extract_api_key_from_header(
+        request: &axum::http::Request<axum::body::Body>,
+    ) -> Option<String> {
+        let headers = request.headers();
+        headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("ApiKey "))
+            })
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+    }
+
     /// Validates a JWT token and returns the user ID
     /// Returns the user ID if the token is valid
     pub fn validate_token(token: &str) -> Result<Uuid, AppError> {
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
-        let decoding_key = DecodingKey::from_secret(jwt_secret.as_ref());
-
-        // Decode and validate the token
-        let token_data =
-            decode::<Claims>(token, &decoding_key, &Validation::default()).map_err(|_| {
-                AppError {
-                    message: "Invalid token".to_string(),
-                    status_code: StatusCode::UNAUTHORIZED,
-                }
-            })?;
+        // Decode and validate the token against the configured signing keys
+        let claims: Claims = JwtKeyManager::verify(token)?;
 
         // Check if token is expired
         let current_time = SystemTime::now()
@@ -100,7 +138,7 @@ impl TokenService {
             .unwrap()
             .as_secs() as usize;
 
-        if token_data.claims.exp < current_time {
+        if claims.exp < current_time {
             return Err(AppError {
                 message: "Token expired".to_string(),
                 status_code: StatusCode::UNAUTHORIZED,
@@ -108,7 +146,7 @@ impl TokenService {
         }
 
         // Parse user ID from token
-        let user_id = Uuid::parse_str(&token_data.claims.sub).map_err(|_| AppError {
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError {
             message: "Invalid user ID in token".to_string(),
             status_code: StatusCode::UNAUTHORIZED,
         })?;
@@ -119,17 +157,8 @@ impl TokenService {
     /// Validates a JWT token and returns the Claims struct
     #[allow(dead_code)]
     pub fn validate_token_claims(token: &str) -> Result<Claims, AppError> {
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
-        let decoding_key = DecodingKey::from_secret(jwt_secret.as_ref());
-
-        // Decode and validate the token
-        let token_data =
-            decode::<Claims>(token, &decoding_key, &Validation::default()).map_err(|_| {
-                AppError {
-                    message: "Invalid token".to_string(),
-                    status_code: StatusCode::UNAUTHORIZED,
-                }
-            })?;
+        // Decode and validate the token against the configured signing keys
+        let claims: Claims = JwtKeyManager::verify(token)?;
 
         // Check if token is expired
         let current_time = SystemTime::now()
@@ -137,14 +166,27 @@ impl TokenService {
             .unwrap()
             .as_secs() as usize;
 
-        if token_data.claims.exp < current_time {
+        if claims.exp < current_time {
             return Err(AppError {
                 message: "Token expired".to_string(),
                 status_code: StatusCode::UNAUTHORIZED,
             });
         }
 
-        Ok(token_data.claims)
+        Ok(claims)
+    }
+
+    /// Generates a random, URL-safe opaque token (e.g. refresh tokens)
+    /// Only the hash of the token should ever be persisted
+    pub fn generate_opaque_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand_core::OsRng.fill_bytes(&mut bytes);
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Hashes an opaque token for storage and lookup
+    pub fn hash_opaque_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
     }
 }
 
@@ -169,6 +211,7 @@ mod tests {
             sub: user_id.to_string(),
             exp: expiration as usize,
             session_id: "".to_string(),
+            act: None,
         };
 
         encode(&Header::default(), &claims, &encoding_key).unwrap()
@@ -289,4 +332,16 @@ mod tests {
         let claims = result.unwrap();
         assert_eq!(claims.sub, user_id);
     }
+
+    #[test]
+    fn test_opaque_token_hashing() {
+        let token = TokenService::generate_opaque_token();
+        let other = TokenService::generate_opaque_token();
+        assert_ne!(token, other);
+
+        let hash = TokenService::hash_opaque_token(&token);
+        assert_eq!(hash, TokenService::hash_opaque_token(&token));
+        assert_ne!(hash, TokenService::hash_opaque_token(&other));
+        assert_eq!(hash.len(), 64);
+    }
 }
@@ -1,15 +1,11 @@
-use argon2::{
-    Argon2,
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-};
 use sea_orm::prelude::Expr;
 use sea_orm::*;
 use uuid::Uuid;
 
-use crate::control::services::database_service::DatabaseService;
-use crate::domain::{user::*, validation::*};
+use crate::control::services::{database_service::DatabaseService, token_service::TokenService};
+use crate::domain::{password::PasswordPolicy, user::*, validation::*};
 use crate::entity::models::{prelude::*, *};
-use crate::infrastructure::app_error::AppError;
+use crate::infrastructure::{app_error::AppError, password_hashing::PasswordHashing};
 use axum::http::StatusCode;
 
 /// Service for user-related business operations
@@ -23,12 +19,14 @@ impl UserService {
     ) -> Result<User, AppError> {
         // Validate input
         validate_registration_input(&registration.email, &registration.password)?;
+        let email = registration.email.trim().to_string();
+        let email_normalized = normalize_email(&email)?;
 
         // Check if user already exists
         let existing_user: Option<users::Model> = DatabaseService::find_one_with_tracking(
             db,
             "users",
-            Users::find().filter(users::Column::Email.eq(registration.email.clone())),
+            Users::find().filter(Self::email_condition(&email)),
         )
         .await
         .map_err(|_| AppError {
@@ -47,7 +45,7 @@ impl UserService {
         let password_hash = Self::hash_password(&registration.password)?;
 
         // Create user domain model
-        let user = User::create_new(registration.email, password_hash);
+        let user = User::create_new(email, password_hash);
 
         // Save to database
         let user_active_model = users::ActiveModel {
@@ -57,6 +55,11 @@ impl UserService {
             created_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
             last_login: Set(None),
             role_id: Set(None), // Default to no role
+            email_verified_at: Set(None),
+            pending_email: Set(None),
+            deletion_scheduled_at: Set(None),
+            password_changed_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
+            email_normalized: Set(Some(email_normalized)),
         };
 
         Users::insert(user_active_model)
@@ -77,14 +80,67 @@ impl UserService {
         password: String,
         role_id: Option<i32>,
     ) -> Result<User, AppError> {
-        // Validate input
-        validate_registration_input(&email, &password)?;
+        // Validate input, the signup domain lists don't apply to accounts admins create
+        validate_email(&email)?;
+        validate_password(&password, Some(&email))?;
+
+        let password_hash = Self::hash_password(&password)?;
+        Self::insert_user_with_role(db, email, password_hash, role_id).await
+    }
+
+    /// Creates a user migrated from another system with the password hash it already has
+    /// Argon2 and bcrypt hashes are accepted, they are upgraded when the user first signs in
+    pub async fn import_user_with_role(
+        db: &DatabaseConnection,
+        email: String,
+        password_hash: String,
+        role_id: Option<i32>,
+    ) -> Result<User, AppError> {
+        validate_email(&email)?;
+        if !PasswordHashing::is_supported(&password_hash) {
+            return Err(AppError {
+                message: "Unsupported password hash, expected an Argon2 or bcrypt hash".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        Self::insert_user_with_role(db, email, password_hash, role_id).await
+    }
+
+    /// Creates the admin account configured with ADMIN_EMAIL and ADMIN_PASSWORD on first start
+    /// The password policy only warns here, so the documented development default keeps working
+    pub async fn create_initial_admin(
+        db: &DatabaseConnection,
+        email: String,
+        password: String,
+        role_id: Option<i32>,
+    ) -> Result<User, AppError> {
+        validate_login_input(&email, &password)?;
+        if let Err(e) = validate_password(&password, Some(&email)) {
+            tracing::warn!(
+                "ADMIN_PASSWORD doesn't meet the password policy: {}",
+                e.message
+            );
+        }
+
+        let password_hash = Self::hash_password(&password)?;
+        Self::insert_user_with_role(db, email, password_hash, role_id).await
+    }
+
+    async fn insert_user_with_role(
+        db: &DatabaseConnection,
+        email: String,
+        password_hash: String,
+        role_id: Option<i32>,
+    ) -> Result<User, AppError> {
+        let email = email.trim().to_string();
+        let email_normalized = normalize_email(&email)?;
 
         // Check if user already exists
         let existing_user: Option<users::Model> = DatabaseService::find_one_with_tracking(
             db,
             "users",
-            Users::find().filter(users::Column::Email.eq(&email)),
+            Users::find().filter(Self::email_condition(&email)),
         )
         .await
         .map_err(|_| AppError {
@@ -99,11 +155,9 @@ impl UserService {
             });
         }
 
-        // Hash password
-        let password_hash = Self::hash_password(&password)?;
-
-        // Create user domain model
-        let user = User::create_new(email, password_hash);
+        // Create user domain model, accounts created by an admin don't need to verify their email
+        let mut user = User::create_new(email, password_hash);
+        user.email_verified_at = Some(chrono::Utc::now());
 
         // Save to database
         let user_active_model = users::ActiveModel {
@@ -113,6 +167,11 @@ impl UserService {
             created_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
             last_login: Set(None),
             role_id: Set(role_id),
+            email_verified_at: Set(user.email_verified_at.map(|dt| dt.fixed_offset())),
+            pending_email: Set(None),
+            deletion_scheduled_at: Set(None),
+            password_changed_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
+            email_normalized: Set(Some(email_normalized)),
         };
 
         Users::insert(user_active_model)
@@ -123,6 +182,51 @@ impl UserService {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             })?;
 
+        if let Some(role_id) = role_id {
+            Self::link_role(db, user.id, role_id).await?;
+        }
+
+        Ok(user)
+    }
+
+    /// Creates a user who signs in through an external identity provider
+    /// The account gets a random password nobody knows, a reset link can set a real one later
+    pub async fn create_external_user(
+        db: &DatabaseConnection,
+        email: String,
+        email_verified: bool,
+    ) -> Result<User, AppError> {
+        let email_normalized = validate_signup_email(&email)?;
+
+        let password_hash = Self::hash_password(&TokenService::generate_opaque_token())?;
+        let mut user = User::create_new(email.trim().to_string(), password_hash);
+        if email_verified {
+            user.email_verified_at = Some(chrono::Utc::now());
+        }
+
+        let user_active_model = users::ActiveModel {
+            id: Set(user.id),
+            email: Set(user.email.clone()),
+            password_hash: Set(user.password_hash.clone()),
+            created_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
+            last_login: Set(None),
+            role_id: Set(None),
+            email_verified_at: Set(user.email_verified_at.map(|dt| dt.fixed_offset())),
+            pending_email: Set(None),
+            deletion_scheduled_at: Set(None),
+            password_changed_at: Set(user.created_at.map(|dt| dt.fixed_offset())),
+            email_normalized: Set(Some(email_normalized)),
+        };
+
+        // The unique email index turns a concurrent signup into a conflict
+        Users::insert(user_active_model)
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "User already exists".to_string(),
+                status_code: StatusCode::CONFLICT,
+            })?;
+
         Ok(user)
     }
 
@@ -146,15 +250,23 @@ impl UserService {
         Ok(())
     }
 
-    /// Finds a user by email
+    /// Finds a user by email, ignoring case and how the domain is spelled
     pub async fn find_user_by_email(
         db: &DatabaseConnection,
         email: &str,
     ) -> Result<Option<User>, AppError> {
+        // An account that clashed with another one only matches its exact address, and wins
+        // over the account holding the normalized address when that address is typed
         let user_model: Option<users::Model> = DatabaseService::find_one_with_tracking(
             db,
             "users",
-            Users::find().filter(users::Column::Email.eq(email)),
+            Users::find()
+                .filter(Self::email_condition(email))
+                .order_by_with_nulls(
+                    users::Column::EmailNormalized,
+                    Order::Asc,
+                    sea_query::NullOrdering::First,
+                ),
         )
         .await
         .map_err(|_| AppError {
@@ -170,10 +282,97 @@ impl UserService {
                 model.created_at.map(|dt| dt.to_utc()),
                 model.last_login.map(|dt| dt.to_utc()),
                 model.role_id,
+                model.email_verified_at.map(|dt| dt.to_utc()),
             )
         }))
     }
 
+    /// Fills in the normalized address of accounts without one, oldest accounts first.
+    /// Returns how many accounts are left without one, because their address is invalid or
+    /// another account already has it
+    pub async fn backfill_normalized_emails(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let db_error = |_| AppError {
+            message: "Failed to normalize email addresses".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let pending = Users::find()
+            .filter(users::Column::EmailNormalized.is_null())
+            .order_by_asc(users::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(db_error)?;
+
+        let mut unresolved = 0;
+        for user in pending {
+            let Ok(normalized) = normalize_email(&user.email) else {
+                unresolved += 1;
+                continue;
+            };
+            let taken = Users::find()
+                .filter(users::Column::EmailNormalized.eq(&normalized))
+                .one(db)
+                .await
+                .map_err(db_error)?
+                .is_some();
+            if taken {
+                unresolved += 1;
+                continue;
+            }
+
+            Users::update_many()
+                .col_expr(users::Column::EmailNormalized, Expr::value(normalized))
+                .filter(users::Column::Id.eq(user.id))
+                .exec(db)
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(unresolved)
+    }
+
+    /// Accounts without a normalized address grouped by the address they would normalize to,
+    /// each group starts with the account holding it. Addresses that don't parse are grouped
+    /// by their lowercased form
+    pub async fn find_email_duplicates(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<(String, Vec<users::Model>)>, AppError> {
+        let db_error = |_| AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let unresolved = Users::find()
+            .filter(users::Column::EmailNormalized.is_null())
+            .order_by_asc(users::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(db_error)?;
+
+        let mut groups: std::collections::BTreeMap<String, Vec<users::Model>> =
+            std::collections::BTreeMap::new();
+        for user in unresolved {
+            let key =
+                normalize_email(&user.email).unwrap_or_else(|_| user.email.trim().to_lowercase());
+            groups.entry(key).or_default().push(user);
+        }
+
+        let holders = Users::find()
+            .filter(users::Column::EmailNormalized.is_in(groups.keys().cloned()))
+            .all(db)
+            .await
+            .map_err(db_error)?;
+        for holder in holders {
+            if let Some(key) = holder.email_normalized.clone()
+                && let Some(group) = groups.get_mut(&key)
+            {
+                group.insert(0, holder);
+            }
+        }
+
+        Ok(groups.into_iter().collect())
+    }
+
     /// Finds a user by ID
     pub async fn find_user_by_id(
         db: &DatabaseConnection,
@@ -195,6 +394,7 @@ impl UserService {
                 model.created_at.map(|dt| dt.to_utc()),
                 model.last_login.map(|dt| dt.to_utc()),
                 model.role_id,
+                model.email_verified_at.map(|dt| dt.to_utc()),
             )
         }))
     }
@@ -223,14 +423,15 @@ impl UserService {
 
         // Update email if provided
         if let Some(new_email) = email {
-            validate_email(&new_email)?;
+            let new_email = new_email.trim().to_string();
+            let email_normalized = normalize_email(&new_email)?;
 
             // Check if email is already taken by another user
             let existing_user = DatabaseService::find_one_with_tracking(
                 db,
                 "users",
                 Users::find()
-                    .filter(users::Column::Email.eq(&new_email))
+                    .filter(Self::email_condition(&new_email))
                     .filter(users::Column::Id.ne(user_id)),
             )
             .await
@@ -247,13 +448,15 @@ impl UserService {
             }
 
             user_active_model.email = Set(new_email);
+            user_active_model.email_normalized = Set(Some(email_normalized));
         }
 
         // Update password if provided
-        if let Some(new_password) = password {
-            validate_password(&new_password)?;
-            let password_hash = Self::hash_password(&new_password)?;
+        if let Some(new_password) = &password {
+            Self::check_new_password(db, &user_model, new_password).await?;
+            let password_hash = Self::hash_password(new_password)?;
             user_active_model.password_hash = Set(password_hash);
+            user_active_model.password_changed_at = Set(Some(chrono::Utc::now().fixed_offset()));
         }
 
         // Update role_id if provided
@@ -266,6 +469,20 @@ impl UserService {
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
         })?;
 
+        if password.is_some() {
+            Self::remember_password(db, user_id, &user_model.password_hash).await?;
+        }
+
+        // Replacing the primary role also replaces its assignment, other roles are kept
+        if let Some(new_role_id) = role_id
+            && user_model.role_id != Some(new_role_id)
+        {
+            if let Some(old_role_id) = user_model.role_id {
+                Self::unlink_role(db, user_id, old_role_id).await?;
+            }
+            Self::link_role(db, user_id, new_role_id).await?;
+        }
+
         Ok(User::new(
             updated_user.id,
             updated_user.email,
@@ -273,6 +490,7 @@ impl UserService {
             updated_user.created_at.map(|dt| dt.to_utc()),
             updated_user.last_login.map(|dt| dt.to_utc()),
             updated_user.role_id,
+            updated_user.email_verified_at.map(|dt| dt.to_utc()),
         ))
     }
 
@@ -299,31 +517,412 @@ impl UserService {
         Ok(())
     }
 
-    /// Verifies a user's password
-    pub fn verify_password(user: &User, password: &str) -> Result<bool, AppError> {
-        let parsed_hash = PasswordHash::new(&user.password_hash).map_err(|_| AppError {
-            message: "Invalid password hash".to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+    /// IDs of every role assigned to a user, the primary role first
+    pub async fn get_role_ids(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Vec<i32>, AppError> {
+        let user = Users::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        let assignments = UserRoles::find()
+            .filter(user_roles::Column::UserId.eq(user_id))
+            .order_by_asc(user_roles::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let mut role_ids: Vec<i32> = user.role_id.into_iter().collect();
+        for assignment in assignments {
+            if !role_ids.contains(&assignment.role_id) {
+                role_ids.push(assignment.role_id);
+            }
+        }
+
+        Ok(role_ids)
+    }
+
+    /// Gives a user an additional role, which becomes the primary role if they had none
+    /// Returns false when the role was already assigned
+    pub async fn assign_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+    ) -> Result<bool, AppError> {
+        let user = Self::find_user_by_id(db, user_id).await?.ok_or(AppError {
+            message: "User not found".to_string(),
+            status_code: StatusCode::NOT_FOUND,
         })?;
 
-        let argon2 = Argon2::default();
-        Ok(argon2
-            .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok())
+        let role = Roles::find_by_id(role_id)
+            .one(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        if role.is_none() {
+            return Err(AppError {
+                message: "Role not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            });
+        }
+
+        let assigned = Self::link_role(db, user_id, role_id).await?;
+
+        if user.role_id.is_none() {
+            Self::set_primary_role(db, user_id, Some(role_id)).await?;
+        }
+
+        Ok(assigned)
     }
 
-    /// Hashes a password using Argon2
-    fn hash_password(password: &str) -> Result<String, AppError> {
-        let salt = SaltString::generate(&mut rand_core::OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
+    /// Takes a role away from a user, promoting their next role when it was the primary one
+    pub async fn revoke_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+    ) -> Result<(), AppError> {
+        let role_ids = Self::get_role_ids(db, user_id).await?;
+        if !role_ids.contains(&role_id) {
+            return Err(AppError {
+                message: "Role is not assigned to this user".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            });
+        }
+
+        Self::unlink_role(db, user_id, role_id).await?;
+
+        if role_ids.first() == Some(&role_id) {
+            let next_primary = role_ids.iter().copied().find(|id| *id != role_id);
+            Self::set_primary_role(db, user_id, next_primary).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_primary_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        Users::update_many()
+            .col_expr(users::Column::RoleId, Expr::value(role_id))
+            .filter(users::Column::Id.eq(user_id))
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to update user".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        Ok(())
+    }
+
+    /// Inserts a role assignment, returns false if it already existed
+    async fn link_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+    ) -> Result<bool, AppError> {
+        let assignment = user_roles::ActiveModel {
+            user_id: Set(user_id),
+            role_id: Set(role_id),
+            created_at: Set(Some(chrono::Utc::now().fixed_offset())),
+        };
+
+        let inserted = UserRoles::insert(assignment)
+            .on_conflict(
+                sea_query::OnConflict::columns([
+                    user_roles::Column::UserId,
+                    user_roles::Column::RoleId,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to assign role".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(inserted > 0)
+    }
+
+    async fn unlink_role(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        role_id: i32,
+    ) -> Result<(), AppError> {
+        UserRoles::delete_by_id((user_id, role_id))
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to revoke role".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        Ok(())
+    }
+
+    /// Verifies a user's password
+    /// A hash made with outdated parameters or algorithm is replaced with a current one once the
+    /// password is known to be right
+    pub async fn verify_password(
+        db: &DatabaseConnection,
+        user: &User,
+        password: &str,
+    ) -> Result<bool, AppError> {
+        if !PasswordHashing::verify(&user.password_hash, password)? {
+            return Ok(false);
+        }
+
+        if PasswordHashing::needs_rehash(&user.password_hash) {
+            // Signing in shouldn't fail because the upgrade did
+            if let Err(e) = Self::rehash_password(db, user, password).await {
+                tracing::warn!(
+                    "Failed to upgrade password hash of {}: {}",
+                    user.id,
+                    e.message
+                );
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Validates and stores a new password for a user
+    pub async fn set_password(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let user = Self::validate_new_password(db, user_id, password).await?;
+        let password_hash = Self::hash_password(password)?;
+
+        Users::update_many()
+            .col_expr(users::Column::PasswordHash, Expr::value(password_hash))
+            .col_expr(
+                users::Column::PasswordChangedAt,
+                Expr::value(chrono::Utc::now().fixed_offset()),
+            )
+            .filter(users::Column::Id.eq(user_id))
+            .exec(db)
+            .await
             .map_err(|_| AppError {
-                message: "Failed to hash password".to_string(),
+                message: "Failed to update password".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Self::remember_password(db, user_id, &user.password_hash).await
+    }
+
+    /// Checks a user's new password against the password policy and their password history
+    pub async fn validate_new_password(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        password: &str,
+    ) -> Result<users::Model, AppError> {
+        let user = Users::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             })?
-            .to_string();
+            .ok_or(AppError {
+                message: "User not found".to_string(),
+                status_code: StatusCode::NOT_FOUND,
+            })?;
+
+        Self::check_new_password(db, &user, password).await?;
+        Ok(user)
+    }
 
-        Ok(password_hash)
+    /// When the user's password was last set, accounts from before it was tracked use their
+    /// creation date
+    pub async fn password_changed_at(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, AppError> {
+        let user = Users::find_by_id(user_id)
+            .one(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(user
+            .and_then(|user| user.password_changed_at.or(user.created_at))
+            .map(|dt| dt.to_utc()))
+    }
+
+    /// Marks a user's email address as verified
+    pub async fn mark_email_verified(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+    ) -> Result<(), AppError> {
+        let now = chrono::Utc::now();
+
+        Users::update_many()
+            .col_expr(
+                users::Column::EmailVerifiedAt,
+                Expr::value(now.fixed_offset()),
+            )
+            .filter(users::Column::Id.eq(user_id))
+            .filter(users::Column::EmailVerifiedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to verify email".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(())
+    }
+
+    /// Rejects passwords that break the policy or match one of the last PASSWORD_HISTORY_SIZE
+    /// passwords, the current one included
+    async fn check_new_password(
+        db: &DatabaseConnection,
+        user: &users::Model,
+        password: &str,
+    ) -> Result<(), AppError> {
+        validate_password(password, Some(&user.email))?;
+
+        let history_size = PasswordPolicy::from_env().history_size;
+        if history_size == 0 {
+            return Ok(());
+        }
+
+        let previous = PasswordHistory::find()
+            .filter(password_history::Column::UserId.eq(user.id))
+            .order_by_desc(password_history::Column::CreatedAt)
+            .limit(history_size - 1)
+            .all(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Database error".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let reused = std::iter::once(user.password_hash.as_str())
+            .chain(previous.iter().map(|entry| entry.password_hash.as_str()))
+            .any(|hash| Self::hash_matches(hash, password));
+        if reused {
+            return Err(AppError {
+                message: "Password was used recently, choose a different one".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Adds a replaced password to the history, keeping only as many as the policy checks
+    async fn remember_password(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), AppError> {
+        let db_error = |_| AppError {
+            message: "Failed to update password history".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        // The current password is always checked, the history holds the ones before it
+        let keep = PasswordPolicy::from_env().history_size.saturating_sub(1);
+        if keep > 0 {
+            let entry = password_history::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                user_id: Set(user_id),
+                password_hash: Set(password_hash.to_string()),
+                created_at: Set(Some(chrono::Utc::now().fixed_offset())),
+            };
+            PasswordHistory::insert(entry)
+                .exec(db)
+                .await
+                .map_err(db_error)?;
+        }
+
+        let expired: Vec<Uuid> = PasswordHistory::find()
+            .filter(password_history::Column::UserId.eq(user_id))
+            .order_by_desc(password_history::Column::CreatedAt)
+            .all(db)
+            .await
+            .map_err(db_error)?
+            .into_iter()
+            .skip(keep as usize)
+            .map(|entry| entry.id)
+            .collect();
+        if !expired.is_empty() {
+            PasswordHistory::delete_many()
+                .filter(password_history::Column::Id.is_in(expired))
+                .exec(db)
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores a fresh hash of the user's current password
+    /// Not a password change, so the password's age and history are left alone
+    async fn rehash_password(
+        db: &DatabaseConnection,
+        user: &User,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let password_hash = Self::hash_password(password)?;
+
+        // Only replace the hash that was verified, a concurrent password change wins
+        Users::update_many()
+            .col_expr(users::Column::PasswordHash, Expr::value(password_hash))
+            .filter(users::Column::Id.eq(user.id))
+            .filter(users::Column::PasswordHash.eq(&user.password_hash))
+            .exec(db)
+            .await
+            .map_err(|_| AppError {
+                message: "Failed to update password hash".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        tracing::info!("Upgraded password hash of user {}", user.id);
+        Ok(())
+    }
+
+    /// Matches the account an address belongs to, see find_user_by_email
+    fn email_condition(email: &str) -> Condition {
+        let email = email.trim();
+        let exact = Condition::all()
+            .add(users::Column::EmailNormalized.is_null())
+            .add(users::Column::Email.eq(email));
+        match normalize_email(email) {
+            Ok(normalized) => Condition::any()
+                .add(users::Column::EmailNormalized.eq(normalized))
+                .add(exact),
+            Err(_) => exact,
+        }
+    }
+
+    fn hash_matches(password_hash: &str, password: &str) -> bool {
+        PasswordHashing::verify(password_hash, password).unwrap_or(false)
+    }
+
+    /// Hashes a password using Argon2 with the configured parameters
+    fn hash_password(password: &str) -> Result<String, AppError> {
+        PasswordHashing::hash(password)
     }
 }
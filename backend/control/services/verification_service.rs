@@ -0,0 +1,261 @@
+//! Verification service
+//!
+//! Issues and redeems the single-use tokens behind email verification and password reset.
+//! Tokens are mailed through the job queue, only their hash is stored.
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use sea_orm::prelude::Expr;
+use sea_orm::*;
+use std::env;
+use uuid::Uuid;
+
+use crate::control::services::{
+    session_service::SessionService, token_service::TokenService, user_service::UserService,
+};
+use crate::domain::{auth::UserTokenPurpose, user::User, validation::*};
+use crate::entity::models::{prelude::*, *};
+use crate::infrastructure::{
+    app_error::AppError,
+    job_queue::{JobQueueManager, Message},
+};
+
+/// Service for email verification and password reset
+pub struct VerificationService;
+
+impl VerificationService {
+    /// Mails a verification link to a newly registered user
+    pub async fn send_verification_email(
+        db: &DatabaseConnection,
+        user: &User,
+    ) -> Result<(), AppError> {
+        let token = Self::issue_token(db, user.id, UserTokenPurpose::EmailVerification).await?;
+
+        JobQueueManager::enqueue(Message {
+            to: user.email.clone(),
+            subject: "Verify your email address".to_string(),
+            text: format!(
+                "Welcome! Confirm your email address by opening the link below:\n\n{}/verify-email?token={}\n\nThe link expires in {} hours.",
+                Self::app_url(),
+                token,
+                UserTokenPurpose::EmailVerification.lifetime().num_hours()
+            ),
+        })
+        .await
+    }
+
+    /// Redeems an email verification token
+    pub async fn verify_email(db: &DatabaseConnection, token: &str) -> Result<(), AppError> {
+        let user_id = Self::consume_token(db, token, UserTokenPurpose::EmailVerification).await?;
+        UserService::mark_email_verified(db, user_id).await
+    }
+
+    /// Mails a password reset link if an account exists for the email
+    /// Succeeds either way so the endpoint can't be used to discover accounts
+    pub async fn request_password_reset(
+        db: &DatabaseConnection,
+        email: &str,
+    ) -> Result<(), AppError> {
+        validate_email(email)?;
+
+        let Some(user) = UserService::find_user_by_email(db, email).await? else {
+            return Ok(());
+        };
+
+        let token = Self::issue_token(db, user.id, UserTokenPurpose::PasswordReset).await?;
+
+        JobQueueManager::enqueue(Message {
+            to: user.email,
+            subject: "Reset your password".to_string(),
+            text: format!(
+                "A password reset was requested for your account. Choose a new password by opening the link below:\n\n{}/reset-password?token={}\n\nThe link expires in {} minutes. If you didn't request this, you can ignore this email.",
+                Self::app_url(),
+                token,
+                UserTokenPurpose::PasswordReset.lifetime().num_minutes()
+            ),
+        })
+        .await
+    }
+
+    /// Redeems a password reset token, sets the new password and signs out every session
+    pub async fn reset_password(
+        db: &DatabaseConnection,
+        token: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        // Validate before the token is spent so a rejected password doesn't burn the link
+        let user_id = Self::find_token(db, token, UserTokenPurpose::PasswordReset)
+            .await?
+            .user_id;
+        UserService::validate_new_password(db, user_id, new_password).await?;
+
+        let user_id = Self::consume_token(db, token, UserTokenPurpose::PasswordReset).await?;
+        UserService::set_password(db, user_id, new_password).await?;
+
+        // The reset link was delivered to the mailbox, which also proves ownership of it
+        UserService::mark_email_verified(db, user_id).await?;
+
+        SessionService::invalidate_all_user_sessions(db, user_id).await?;
+
+        Ok(())
+    }
+
+    /// Mails a confirmation link to a new email address, the current address gets a notice.
+    /// The address only changes once the link is opened
+    pub async fn send_email_change(
+        db: &DatabaseConnection,
+        user: &User,
+        new_email: &str,
+    ) -> Result<(), AppError> {
+        let token = Self::issue_token(db, user.id, UserTokenPurpose::EmailChange).await?;
+
+        JobQueueManager::enqueue(Message {
+            to: new_email.to_string(),
+            subject: "Confirm your new email address".to_string(),
+            text: format!(
+                "Confirm that this is your new email address by opening the link below:\n\n{}/confirm-email-change?token={}\n\nThe link expires in {} hours.",
+                Self::app_url(),
+                token,
+                UserTokenPurpose::EmailChange.lifetime().num_hours()
+            ),
+        })
+        .await?;
+
+        JobQueueManager::enqueue(Message {
+            to: user.email.clone(),
+            subject: "Your email address is being changed".to_string(),
+            text: format!(
+                "A change of your account's email address to {} was requested. If you didn't request this, change your password right away.",
+                new_email
+            ),
+        })
+        .await
+    }
+
+    /// Redeems an email change token, returns the user whose pending address it confirms
+    pub async fn confirm_email_change(
+        db: &DatabaseConnection,
+        token: &str,
+    ) -> Result<Uuid, AppError> {
+        Self::consume_token(db, token, UserTokenPurpose::EmailChange).await
+    }
+
+    /// Creates a token for a user, replacing any unused token with the same purpose
+    async fn issue_token(
+        db: &DatabaseConnection,
+        user_id: Uuid,
+        purpose: UserTokenPurpose,
+    ) -> Result<String, AppError> {
+        UserTokens::delete_many()
+            .filter(user_tokens::Column::UserId.eq(user_id))
+            .filter(user_tokens::Column::Purpose.eq(purpose.as_str()))
+            .filter(user_tokens::Column::UsedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        let token = TokenService::generate_opaque_token();
+        let now = Utc::now();
+
+        let token_model = user_tokens::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            purpose: Set(purpose.as_str().to_string()),
+            token_hash: Set(TokenService::hash_opaque_token(&token)),
+            created_at: Set(Some(now.fixed_offset())),
+            expires_at: Set((now + purpose.lifetime()).fixed_offset()),
+            used_at: Set(None),
+        };
+
+        UserTokens::insert(token_model)
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Failed to create token: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        Ok(token)
+    }
+
+    /// Marks a token as used and returns the user it belongs to
+    async fn consume_token(
+        db: &DatabaseConnection,
+        token: &str,
+        purpose: UserTokenPurpose,
+    ) -> Result<Uuid, AppError> {
+        let token_model = Self::find_token(db, token, purpose).await?;
+        let now = Utc::now();
+
+        // Guard on used_at so two concurrent requests can't both redeem the token
+        let result = UserTokens::update_many()
+            .col_expr(user_tokens::Column::UsedAt, Expr::value(now.fixed_offset()))
+            .filter(user_tokens::Column::Id.eq(token_model.id))
+            .filter(user_tokens::Column::UsedAt.is_null())
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+
+        if result.rows_affected == 0 {
+            return Err(Self::invalid_token());
+        }
+
+        Ok(token_model.user_id)
+    }
+
+    /// Looks up a token that is still valid without using it
+    async fn find_token(
+        db: &DatabaseConnection,
+        token: &str,
+        purpose: UserTokenPurpose,
+    ) -> Result<user_tokens::Model, AppError> {
+        let invalid_token = Self::invalid_token;
+
+        if token.is_empty() {
+            return Err(AppError {
+                message: "Token is required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let now = Utc::now();
+
+        let token_model = UserTokens::find()
+            .filter(user_tokens::Column::TokenHash.eq(TokenService::hash_opaque_token(token)))
+            .filter(user_tokens::Column::Purpose.eq(purpose.as_str()))
+            .one(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .ok_or_else(invalid_token)?;
+
+        if token_model.used_at.is_some() || token_model.expires_at < now.fixed_offset() {
+            return Err(invalid_token());
+        }
+
+        Ok(token_model)
+    }
+
+    fn invalid_token() -> AppError {
+        AppError {
+            message: "Invalid or expired token".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Base URL of the frontend used in mailed links, configured with APP_URL
+    fn app_url() -> String {
+        env::var("APP_URL")
+            .unwrap_or_else(|_| "http://localhost:5173".to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
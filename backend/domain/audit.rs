@@ -0,0 +1,16 @@
+use uuid::Uuid;
+
+/// Security event recorded by the application itself rather than the request logger
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    /// Stored in the method column, e.g. "LOCKOUT"
+    pub action: String,
+    pub path: String,
+    pub status_code: Option<i32>,
+    pub user_id: Option<Uuid>,
+    /// Admin acting on behalf of user_id while impersonating them
+    pub actor_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub message: String,
+}
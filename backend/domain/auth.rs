@@ -1,21 +1,255 @@
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use crate::domain::validation::normalize_email;
+
 /// Domain model for authentication token
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct AuthToken {
     pub token: String,
+    pub refresh_token: String,
     pub user_id: Uuid,
     pub expires_at: DateTime<Utc>,
 }
 
 impl AuthToken {
-    pub fn new(token: String, user_id: Uuid, expires_at: DateTime<Utc>) -> Self {
+    pub fn new(
+        token: String,
+        refresh_token: String,
+        user_id: Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
         Self {
             token,
+            refresh_token,
             user_id,
             expires_at,
         }
     }
+
+    /// Seconds until the access token expires
+    pub fn expires_in(&self) -> i64 {
+        (self.expires_at - Utc::now()).num_seconds().max(0)
+    }
+}
+
+/// What a single-use user token can be redeemed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserTokenPurpose {
+    EmailVerification,
+    PasswordReset,
+    /// Confirms a new email address before it replaces the current one
+    EmailChange,
+}
+
+impl UserTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserTokenPurpose::EmailVerification => "email_verification",
+            UserTokenPurpose::PasswordReset => "password_reset",
+            UserTokenPurpose::EmailChange => "email_change",
+        }
+    }
+
+    /// How long a token stays valid
+    /// Configured with EMAIL_VERIFICATION_TTL_HOURS (defaults to 24, also used for email changes)
+    /// and PASSWORD_RESET_TTL_MINUTES (defaults to 60)
+    pub fn lifetime(&self) -> chrono::Duration {
+        match self {
+            UserTokenPurpose::EmailVerification | UserTokenPurpose::EmailChange => {
+                chrono::Duration::hours(configured("EMAIL_VERIFICATION_TTL_HOURS", 24))
+            }
+            UserTokenPurpose::PasswordReset => {
+                chrono::Duration::minutes(configured("PASSWORD_RESET_TTL_MINUTES", 60))
+            }
+        }
+    }
+}
+
+/// Login flow an MFA challenge was issued for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MfaScope {
+    User,
+    Admin,
+}
+
+impl MfaScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MfaScope::User => "user",
+            MfaScope::Admin => "admin",
+        }
+    }
+}
+
+/// TOTP secret handed to the user while enrolling
+#[derive(Debug, Clone)]
+pub struct MfaSetup {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Second login step, the token must be exchanged together with a valid code
+#[derive(Debug)]
+pub struct MfaChallenge {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    /// Present when the user's role requires MFA but they haven't enrolled yet
+    pub setup: Option<MfaSetup>,
+}
+
+impl MfaChallenge {
+    /// Seconds until the challenge expires
+    pub fn expires_in(&self) -> i64 {
+        (self.expires_at - Utc::now()).num_seconds().max(0)
+    }
+}
+
+/// Result of checking a user's password
+#[derive(Debug)]
+pub enum LoginResult {
+    Authenticated(AuthToken),
+    MfaRequired(MfaChallenge),
+}
+
+/// Session created by completing an MFA challenge
+#[derive(Debug)]
+pub struct MfaLogin {
+    pub auth_token: AuthToken,
+    /// Recovery codes, only set when the challenge also completed enrollment
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+/// A login attempt, identified by the account and the client it came from
+#[derive(Debug, Clone)]
+pub struct LoginAttempt {
+    /// Missing for steps that don't name an account, like MFA verification
+    pub email: Option<String>,
+    /// Set when a signed in user re-enters their password to confirm an account change
+    pub user_id: Option<Uuid>,
+    pub path: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl LoginAttempt {
+    pub fn new(
+        email: Option<&str>,
+        path: String,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Self {
+        Self {
+            email: email.map(|email| {
+                normalize_email(email).unwrap_or_else(|_| email.trim().to_lowercase())
+            }),
+            user_id: None,
+            path,
+            ip_address,
+            user_agent,
+        }
+    }
+
+    /// A signed in user confirming an account change with their current password
+    pub fn for_user(
+        user_id: Uuid,
+        path: &str,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Self {
+        Self {
+            email: None,
+            user_id: Some(user_id),
+            path: path.to_string(),
+            ip_address,
+            user_agent,
+        }
+    }
+
+    /// Throttle keys this attempt counts against, with the policy for each
+    pub fn throttle_keys(&self) -> Vec<(String, LoginThrottlePolicy)> {
+        let mut keys = Vec::new();
+        if let Some(email) = self.email.as_ref().filter(|email| !email.is_empty()) {
+            keys.push((format!("account:{}", email), LoginThrottlePolicy::account()));
+        }
+        if let Some(user_id) = self.user_id {
+            keys.push((format!("user:{}", user_id), LoginThrottlePolicy::account()));
+        }
+        if let Some(ip_address) = &self.ip_address {
+            keys.push((format!("ip:{}", ip_address), LoginThrottlePolicy::ip()));
+        }
+        keys
+    }
+}
+
+/// Limits for failed logins against one account or from one client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoginThrottlePolicy {
+    /// Failures allowed before each attempt has to wait
+    pub backoff_after: i32,
+    /// Failures that lock the key
+    pub lockout_after: i32,
+    pub base_delay: chrono::Duration,
+    pub max_delay: chrono::Duration,
+    pub lockout: chrono::Duration,
+    /// Failures older than this are forgotten
+    pub window: chrono::Duration,
+}
+
+impl LoginThrottlePolicy {
+    /// Per account policy
+    /// Configured with LOGIN_BACKOFF_AFTER (defaults to 3) and LOGIN_LOCKOUT_AFTER (defaults to 10)
+    pub fn account() -> Self {
+        Self::from_env(
+            configured("LOGIN_BACKOFF_AFTER", 3),
+            configured("LOGIN_LOCKOUT_AFTER", 10),
+        )
+    }
+
+    /// Per client IP policy, looser since many users can share an address
+    /// Configured with LOGIN_IP_BACKOFF_AFTER (defaults to 10) and LOGIN_IP_LOCKOUT_AFTER (defaults to 50)
+    pub fn ip() -> Self {
+        Self::from_env(
+            configured("LOGIN_IP_BACKOFF_AFTER", 10),
+            configured("LOGIN_IP_LOCKOUT_AFTER", 50),
+        )
+    }
+
+    fn from_env(backoff_after: i64, lockout_after: i64) -> Self {
+        Self {
+            backoff_after: backoff_after as i32,
+            lockout_after: lockout_after as i32,
+            base_delay: chrono::Duration::seconds(configured("LOGIN_BACKOFF_BASE_SECONDS", 1)),
+            max_delay: chrono::Duration::seconds(configured("LOGIN_BACKOFF_MAX_SECONDS", 300)),
+            lockout: chrono::Duration::minutes(configured("LOGIN_LOCKOUT_MINUTES", 15)),
+            window: chrono::Duration::minutes(configured("LOGIN_ATTEMPT_WINDOW_MINUTES", 15)),
+        }
+    }
+
+    /// How long to wait after the last failure before the next attempt is allowed,
+    /// doubling with every failure past `backoff_after`
+    pub fn backoff(&self, failed_count: i32) -> Option<chrono::Duration> {
+        if failed_count < self.backoff_after {
+            return None;
+        }
+        let exponent = (failed_count - self.backoff_after).min(30) as u32;
+        let delay = self
+            .base_delay
+            .checked_mul(2_i32.saturating_pow(exponent))
+            .unwrap_or(self.max_delay);
+        Some(delay.min(self.max_delay))
+    }
+
+    pub fn should_lock(&self, failed_count: i32) -> bool {
+        failed_count >= self.lockout_after
+    }
+}
+
+/// Reads an integer setting from the environment
+fn configured(var: &str, default: i64) -> i64 {
+    std::env::var(var)
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .unwrap_or(default)
 }
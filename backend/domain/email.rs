@@ -0,0 +1,291 @@
+use axum::http::StatusCode;
+
+use crate::infrastructure::app_error::AppError;
+
+/// Longest address that fits in an SMTP path
+const MAX_LENGTH: usize = 254;
+
+/// Longest local part SMTP accepts
+const MAX_LOCAL_LENGTH: usize = 64;
+
+/// Characters besides letters and digits allowed in an unquoted local part (RFC 5322 atext)
+const ATEXT_SYMBOLS: &str = "!#$%&'*+-/=?^_`{|}~";
+
+/// An email address that parsed as an RFC 5322 addr-spec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailAddress {
+    /// Part before the @, quoted only when it has to be
+    pub local: String,
+    /// Domain in lowercase ASCII, internationalized names in their punycode form
+    pub domain: String,
+}
+
+impl EmailAddress {
+    /// Parses an address, surrounding whitespace is ignored.
+    /// Local parts may be dot-atoms or quoted strings and contain UTF-8 (RFC 6531),
+    /// domains have to be DNS names with at least two labels, address literals aren't accepted
+    pub fn parse(input: &str) -> Result<Self, AppError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(AppError {
+                message: "Email is required".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+
+        let (local, domain) = split_address(input).ok_or_else(invalid)?;
+        let local = if let Some(quoted) = local.strip_prefix('"') {
+            let content = unquote(quoted.strip_suffix('"').ok_or_else(invalid)?)?;
+            // "john"@example.com and john@example.com are the same mailbox
+            if is_dot_atom(&content) {
+                content
+            } else {
+                local.to_string()
+            }
+        } else if is_dot_atom(local) {
+            local.to_string()
+        } else {
+            return Err(invalid());
+        };
+        if local.len() > MAX_LOCAL_LENGTH {
+            return Err(invalid());
+        }
+
+        let domain = normalize_domain(domain).ok_or_else(invalid)?;
+        if !domain.contains('.') {
+            return Err(invalid());
+        }
+
+        let address = Self { local, domain };
+        if address.normalized().len() > MAX_LENGTH {
+            return Err(AppError {
+                message: "Email address is too long".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            });
+        }
+        Ok(address)
+    }
+
+    /// Canonical form used to tell accounts apart, so addresses differing only in case
+    /// or in how the domain is spelled belong to the same account
+    pub fn normalized(&self) -> String {
+        format!("{}@{}", self.local.to_lowercase(), self.domain)
+    }
+}
+
+/// Domains new accounts may sign up with, configured with SIGNUP_ALLOWED_DOMAINS and
+/// SIGNUP_BLOCKED_DOMAINS. Entries also cover their subdomains
+#[derive(Debug, Clone, Default)]
+pub struct SignupDomains {
+    /// Only these domains may sign up, any domain if empty
+    pub allowed: Vec<String>,
+    /// Domains that may never sign up, checked before the allow list
+    pub blocked: Vec<String>,
+}
+
+impl SignupDomains {
+    /// Lists from the comma separated SIGNUP_ALLOWED_DOMAINS and SIGNUP_BLOCKED_DOMAINS
+    pub fn from_env() -> Self {
+        Self {
+            allowed: domain_list("SIGNUP_ALLOWED_DOMAINS"),
+            blocked: domain_list("SIGNUP_BLOCKED_DOMAINS"),
+        }
+    }
+
+    /// Checks the domain of an address that is about to sign up
+    pub fn check(&self, address: &EmailAddress) -> Result<(), AppError> {
+        let covers = |entry: &String| {
+            address.domain == *entry
+                || address
+                    .domain
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        };
+
+        if self.blocked.iter().any(covers)
+            || (!self.allowed.is_empty() && !self.allowed.iter().any(covers))
+        {
+            return Err(AppError {
+                message: "Signing up with this email domain is not allowed".to_string(),
+                status_code: StatusCode::FORBIDDEN,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Splits at the @ that separates local part and domain, a quoted local part may contain @ itself
+fn split_address(input: &str) -> Option<(&str, &str)> {
+    let at = if input.starts_with('"') {
+        let mut escaped = false;
+        let closing = input
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| {
+                let closes = !escaped && *c == '"';
+                escaped = !escaped && *c == '\\';
+                closes
+            })?
+            .0;
+        (input[closing + 1..].starts_with('@')).then_some(closing + 1)?
+    } else {
+        input.rfind('@')?
+    };
+
+    let (local, domain) = (&input[..at], &input[at + 1..]);
+    (!local.is_empty() && !domain.is_empty()).then_some((local, domain))
+}
+
+/// Whether the value is a dot-atom, allowing UTF-8 letters as RFC 6531 does
+fn is_dot_atom(value: &str) -> bool {
+    !value.is_empty()
+        && value.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom.chars().all(|c| {
+                    c.is_ascii_alphanumeric()
+                        || ATEXT_SYMBOLS.contains(c)
+                        || (!c.is_ascii() && !c.is_control() && !c.is_whitespace())
+                })
+        })
+}
+
+/// Contents of a quoted string with quoted pairs resolved
+fn unquote(quoted: &str) -> Result<String, AppError> {
+    let mut content = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => chars.next().ok_or_else(invalid)?,
+            '"' => return Err(invalid()),
+            c => c,
+        };
+        if c.is_control() || (c.is_whitespace() && c != ' ') {
+            return Err(invalid());
+        }
+        content.push(c);
+    }
+    Ok(content)
+}
+
+/// Lowercase ASCII form of a domain, None if it isn't a valid host name
+fn normalize_domain(domain: &str) -> Option<String> {
+    if domain.starts_with('[') || domain.ends_with('.') {
+        return None;
+    }
+    let ascii = idna::domain_to_ascii_strict(domain).ok()?;
+    // A numeric top level label would make this an IP address
+    let tld = ascii.rsplit('.').next()?;
+    (!tld.chars().all(|c| c.is_ascii_digit())).then_some(ascii)
+}
+
+fn domain_list(var: &str) -> Vec<String> {
+    std::env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .map(|entry| {
+            entry
+                .trim()
+                .trim_start_matches('@')
+                .trim_start_matches("*.")
+        })
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match normalize_domain(entry) {
+            Some(domain) => Some(domain),
+            None => {
+                tracing::warn!("Ignoring invalid domain {} in {}", entry, var);
+                None
+            }
+        })
+        .collect()
+}
+
+fn invalid() -> AppError {
+    AppError {
+        message: "Invalid email format".to_string(),
+        status_code: StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_parsed_and_normalized() {
+        let normalized = |input: &str| EmailAddress::parse(input).map(|a| a.normalized());
+
+        assert_eq!(normalized("  Bob@Example.COM ").unwrap(), "bob@example.com");
+        assert_eq!(
+            normalized("first.last+tag@sub.example.org").unwrap(),
+            "first.last+tag@sub.example.org"
+        );
+        assert_eq!(
+            normalized("\"john\"@example.com").unwrap(),
+            "john@example.com"
+        );
+        assert_eq!(
+            normalized("\"John Doe\"@example.com").unwrap(),
+            "\"john doe\"@example.com"
+        );
+        assert_eq!(
+            normalized("\"a@b\"@example.com").unwrap(),
+            "\"a@b\"@example.com"
+        );
+        assert_eq!(
+            normalized("jürgen@Bücher.example").unwrap(),
+            "jürgen@xn--bcher-kva.example"
+        );
+        assert_eq!(
+            normalized("user@xn--bcher-kva.example").unwrap(),
+            "user@xn--bcher-kva.example"
+        );
+
+        for invalid in [
+            "plainaddress",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            "user@@example.com",
+            ".user@example.com",
+            "user..name@example.com",
+            "user name@example.com",
+            "user@[192.168.0.1]",
+            "user@192.168.0.1",
+            "user@-example.com",
+            "user@example.com.",
+            "user@exa_mple.com",
+            "\"unterminated@example.com",
+        ] {
+            assert!(EmailAddress::parse(invalid).is_err(), "{}", invalid);
+        }
+        assert!(EmailAddress::parse(&format!("{}@example.com", "a".repeat(65))).is_err());
+        assert_eq!(
+            EmailAddress::parse(" ").unwrap_err().message,
+            "Email is required"
+        );
+    }
+
+    #[test]
+    fn signup_domains_cover_subdomains() {
+        let domains = SignupDomains {
+            allowed: vec![
+                "example.com".to_string(),
+                "xn--bcher-kva.example".to_string(),
+            ],
+            blocked: vec!["spam.example.com".to_string()],
+        };
+        let allowed = |email: &str| domains.check(&EmailAddress::parse(email).unwrap()).is_ok();
+
+        assert!(allowed("a@example.com"));
+        assert!(allowed("a@eu.example.com"));
+        assert!(allowed("a@BÜCHER.example"));
+        assert!(!allowed("a@notexample.com"));
+        assert!(!allowed("a@spam.example.com"));
+        assert!(!allowed("a@x.spam.example.com"));
+        assert!(
+            SignupDomains::default()
+                .check(&EmailAddress::parse("a@any.org").unwrap())
+                .is_ok()
+        );
+    }
+}
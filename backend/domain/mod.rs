@@ -1,4 +1,8 @@
+pub mod audit;
 pub mod auth;
+pub mod email;
+pub mod password;
 pub mod permissions;
+pub mod session;
 pub mod user;
 pub mod validation;
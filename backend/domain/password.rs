@@ -0,0 +1,275 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::infrastructure::app_error::AppError;
+
+/// Bundled list of common and breached passwords
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Rules new passwords have to follow
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Upper bound so hashing a huge password can't be used to tie up the server
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Reject passwords from the common password list
+    pub check_banned: bool,
+    /// Reject passwords built from the user's email address
+    pub check_email: bool,
+    /// How many of the latest passwords can't be used again, 0 turns the check off
+    pub history_size: u64,
+    /// Passwords older than this have to be reset at the next login, never if None
+    pub max_age: Option<Duration>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            check_banned: true,
+            check_email: true,
+            history_size: 5,
+            max_age: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Policy configured with the PASSWORD_* environment variables, see example.env
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            min_length: number("PASSWORD_MIN_LENGTH").unwrap_or(defaults.min_length as u64)
+                as usize,
+            max_length: number("PASSWORD_MAX_LENGTH").unwrap_or(defaults.max_length as u64)
+                as usize,
+            require_lowercase: flag("PASSWORD_REQUIRE_LOWERCASE", defaults.require_lowercase),
+            require_uppercase: flag("PASSWORD_REQUIRE_UPPERCASE", defaults.require_uppercase),
+            require_digit: flag("PASSWORD_REQUIRE_DIGIT", defaults.require_digit),
+            require_symbol: flag("PASSWORD_REQUIRE_SYMBOL", defaults.require_symbol),
+            check_banned: flag("PASSWORD_CHECK_BANNED", defaults.check_banned),
+            check_email: flag("PASSWORD_CHECK_EMAIL", defaults.check_email),
+            history_size: number("PASSWORD_HISTORY_SIZE").unwrap_or(defaults.history_size),
+            max_age: number("PASSWORD_MAX_AGE_DAYS")
+                .filter(|days| *days > 0)
+                .map(|days| Duration::days(days as i64)),
+        }
+    }
+
+    /// Checks a new password, the error names the first rule it breaks
+    pub fn check(&self, password: &str, email: Option<&str>) -> Result<(), AppError> {
+        let rejected = |message: String| {
+            Err(AppError {
+                message,
+                status_code: StatusCode::BAD_REQUEST,
+            })
+        };
+
+        let length = password.chars().count();
+        if length == 0 {
+            return rejected("Password is required".to_string());
+        }
+        if length < self.min_length {
+            return rejected(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            ));
+        }
+        if length > self.max_length {
+            return rejected(format!(
+                "Password must be at most {} characters",
+                self.max_length
+            ));
+        }
+
+        let classes = [
+            (
+                self.require_lowercase,
+                char::is_lowercase as fn(char) -> bool,
+                "a lowercase letter",
+            ),
+            (
+                self.require_uppercase,
+                char::is_uppercase,
+                "an uppercase letter",
+            ),
+            (self.require_digit, |c: char| c.is_ascii_digit(), "a digit"),
+            (
+                self.require_symbol,
+                |c: char| !c.is_alphanumeric() && !c.is_whitespace(),
+                "a symbol",
+            ),
+        ];
+        for (required, matches, name) in classes {
+            if required && !password.chars().any(matches) {
+                return rejected(format!("Password must contain {}", name));
+            }
+        }
+
+        if self.check_banned && is_common(password) {
+            return rejected(
+                "Password is too common, choose one that is harder to guess".to_string(),
+            );
+        }
+        if self.check_email
+            && let Some(email) = email
+            && resembles_email(password, email)
+        {
+            return rejected("Password must not contain your email address".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Whether a password set at `changed_at` has to be reset
+    pub fn is_expired(&self, changed_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.max_age
+            .is_some_and(|max_age| changed_at + max_age < now)
+    }
+}
+
+/// Whether the password, or its base without trailing digits and symbols, is on the common list.
+/// Simple letter substitutions like "p@ssw0rd" are undone first
+fn is_common(password: &str) -> bool {
+    let lower = password.to_lowercase();
+    let base = lower.trim_end_matches(|c: char| !c.is_alphabetic());
+    let list = common_passwords();
+
+    list.contains(&lower)
+        || (base.chars().count() >= 4 && (list.contains(base) || list.contains(&unleet(base))))
+}
+
+/// Undoes common letter to digit and symbol substitutions
+fn unleet(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            other => other,
+        })
+        .collect()
+}
+
+/// Whether the password contains the email's local part or a distinctive piece of it
+fn resembles_email(password: &str, email: &str) -> bool {
+    let password = unleet(&password.to_lowercase());
+    let email = email.to_lowercase();
+    let local = email.split('@').next().unwrap_or_default();
+    // Plus addressing doesn't make an address harder to guess
+    let local = local.split('+').next().unwrap_or_default();
+
+    (local.chars().count() >= 3 && password.contains(local))
+        || local
+            .split(['.', '_', '-'])
+            .any(|part| part.chars().count() >= 4 && password.contains(part))
+}
+
+/// The bundled list plus the file at PASSWORD_BANNED_LIST_PATH, loaded once
+fn common_passwords() -> &'static HashSet<String> {
+    static LIST: OnceLock<HashSet<String>> = OnceLock::new();
+    LIST.get_or_init(|| {
+        let extra = std::env::var("PASSWORD_BANNED_LIST_PATH")
+            .ok()
+            .and_then(|path| match std::fs::read_to_string(&path) {
+                Ok(contents) => Some(contents),
+                Err(e) => {
+                    tracing::warn!("Failed to read banned password list {}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        COMMON_PASSWORDS
+            .lines()
+            .chain(extra.lines())
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_lowercase())
+            .collect()
+    })
+}
+
+fn number(var: &str) -> Option<u64> {
+    std::env::var(var).ok().and_then(|value| value.parse().ok())
+}
+
+fn flag(var: &str, default: bool) -> bool {
+    std::env::var(var)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_rejects_weak_passwords() {
+        let policy = PasswordPolicy {
+            require_uppercase: true,
+            require_digit: true,
+            ..PasswordPolicy::default()
+        };
+        let rejects = |password: &str, message: &str| {
+            let error = policy
+                .check(password, Some("jane.doe@example.com"))
+                .unwrap_err();
+            assert_eq!(error.message, message, "{}", password);
+        };
+
+        rejects("Sh0rt", "Password must be at least 8 characters");
+        rejects("nouppercase1", "Password must contain an uppercase letter");
+        rejects("NoDigitsHere", "Password must contain a digit");
+        rejects(
+            "P@ssw0rd",
+            "Password is too common, choose one that is harder to guess",
+        );
+        rejects(
+            "Monkey2024!",
+            "Password is too common, choose one that is harder to guess",
+        );
+        rejects(
+            "JaneDoe1987",
+            "Password must not contain your email address",
+        );
+        rejects(
+            "xJane4Ever!",
+            "Password must not contain your email address",
+        );
+
+        assert!(
+            policy
+                .check("Correct7HorseBattery", Some("jane.doe@example.com"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn passwords_expire_after_max_age() {
+        let now = Utc::now();
+        let policy = PasswordPolicy {
+            max_age: Some(Duration::days(90)),
+            ..PasswordPolicy::default()
+        };
+        assert!(policy.is_expired(now - Duration::days(91), now));
+        assert!(!policy.is_expired(now - Duration::days(89), now));
+        assert!(!PasswordPolicy::default().is_expired(now - Duration::days(9000), now));
+    }
+}
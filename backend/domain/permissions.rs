@@ -92,7 +92,6 @@ impl Permission {
     }
 
     /// Get permission category
-    #[allow(dead_code)]
     pub fn category(&self) -> &'static str {
         match self {
             Permission::All => "super",
@@ -146,19 +145,123 @@ impl Permission {
     }
 
     /// Check if this permission includes another permission
-    #[allow(dead_code)]
+    ///
+    /// A `*` segment matches any single segment, a trailing `*` also matches everything below it,
+    /// so `admin:*` includes `admin:users` and `user:*:own` includes `user:write:own`.
     pub fn includes(&self, other: &Permission) -> bool {
         match self {
             Permission::All => true,
-            _ => self == other,
+            _ if self == other => true,
+            _ => {
+                let pattern = self.to_string();
+                let target = other.to_string();
+                let pattern: Vec<&str> = pattern.split(':').collect();
+                let target: Vec<&str> = target.split(':').collect();
+
+                let trailing_wildcard = pattern.last() == Some(&"*");
+                if target.len() < pattern.len()
+                    || (target.len() > pattern.len() && !trailing_wildcard)
+                {
+                    return false;
+                }
+
+                pattern
+                    .iter()
+                    .zip(&target)
+                    .all(|(p, t)| *p == "*" || p == t)
+            }
+        }
+    }
+
+    /// Whether this permission contains a wildcard segment
+    pub fn is_wildcard(&self) -> bool {
+        matches!(self, Permission::All) || self.to_string().split(':').any(|s| s == "*")
+    }
+}
+
+/// Type level handle for a permission, lets handlers declare what they require in their signature
+pub trait PermissionMarker: Send + Sync + 'static {
+    fn permission() -> Permission;
+}
+
+macro_rules! permission_markers {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[doc = concat!("Marker for [`Permission::", stringify!($name), "`]")]
+            #[allow(dead_code)]
+            pub struct $name;
+
+            impl super::PermissionMarker for $name {
+                fn permission() -> super::Permission {
+                    super::Permission::$name
+                }
+            }
+        )*
+    };
+}
+
+/// Marker types for the built-in permissions, e.g. `RequirePermission<markers::AdminUsers>`
+pub mod markers {
+    permission_markers!(
+        All,
+        AdminRead,
+        AdminWrite,
+        AdminDelete,
+        AdminUsers,
+        AdminRoles,
+        AdminLogs,
+        AdminDatabase,
+        AdminHealth,
+        AdminMetrics,
+        UserRead,
+        UserWrite,
+        UserDelete,
+        UserProfile,
+        UserCreate,
+        SystemHealth,
+        SystemMetrics,
+        SystemLogs,
+        SystemDatabase,
+    );
+}
+
+/// Whether a per-user permission record adds a permission or takes it away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionEffect {
+    Grant,
+    Deny,
+}
+
+impl PermissionEffect {
+    /// Value stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionEffect::Grant => "grant",
+            PermissionEffect::Deny => "deny",
+        }
+    }
+
+    /// Parses a stored value, anything unknown is treated as a deny to fail closed
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "grant" => PermissionEffect::Grant,
+            _ => PermissionEffect::Deny,
         }
     }
 }
 
 /// Collection of permissions with helper methods
+///
+/// Denied permissions win over anything granted, including wildcards. A set restricted to
+/// scopes, as for API keys, only contains permissions that a scope matches as well.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PermissionSet {
     permissions: HashSet<Permission>,
+    #[serde(default)]
+    denied: HashSet<Permission>,
+    #[serde(default)]
+    scopes: Option<HashSet<Permission>>,
 }
 
 impl PermissionSet {
@@ -166,6 +269,8 @@ impl PermissionSet {
     pub fn new() -> Self {
         Self {
             permissions: HashSet::new(),
+            denied: HashSet::new(),
+            scopes: None,
         }
     }
 
@@ -173,6 +278,8 @@ impl PermissionSet {
     pub fn from_vec(permissions: Vec<Permission>) -> Self {
         Self {
             permissions: permissions.into_iter().collect(),
+            denied: HashSet::new(),
+            scopes: None,
         }
     }
 
@@ -184,11 +291,31 @@ impl PermissionSet {
                 .into_iter()
                 .map(|s| Permission::from_string(&s))
                 .collect(),
+            denied: HashSet::new(),
+            scopes: None,
         }
     }
 
+    /// Deny a permission, a wildcard denies everything it matches
+    pub fn deny(&mut self, permission: Permission) {
+        self.denied.insert(permission);
+    }
+
+    /// Limit the set to permissions matched by one of `scopes`, repeated calls narrow it further
+    pub fn restrict_to(&mut self, scopes: &[Permission]) {
+        let scopes: HashSet<Permission> = scopes
+            .iter()
+            .filter(|scope| {
+                self.scopes
+                    .as_ref()
+                    .is_none_or(|current| Self::matches(current, scope))
+            })
+            .cloned()
+            .collect();
+        self.scopes = Some(scopes);
+    }
+
     /// Add a permission
-    #[allow(dead_code)]
     pub fn add(&mut self, permission: Permission) {
         self.permissions.insert(permission);
     }
@@ -199,10 +326,30 @@ impl PermissionSet {
         self.permissions.remove(permission);
     }
 
-    /// Check if set contains a permission
-    #[allow(dead_code)]
+    /// Check if set contains a permission, directly or through a wildcard, and it isn't denied
     pub fn contains(&self, permission: &Permission) -> bool {
-        self.permissions.contains(&Permission::All) || self.permissions.contains(permission)
+        if Self::matches(&self.denied, permission) {
+            return false;
+        }
+        if let Some(scopes) = &self.scopes
+            && !Self::matches(scopes, permission)
+        {
+            return false;
+        }
+        Self::matches(&self.permissions, permission)
+    }
+
+    /// Check if set holds everything `permission` stands for, a wildcard only counts when
+    /// nothing it matches is denied
+    pub fn covers(&self, permission: &Permission) -> bool {
+        self.contains(permission) && !self.denied.iter().any(|denied| permission.includes(denied))
+    }
+
+    fn matches(permissions: &HashSet<Permission>, permission: &Permission) -> bool {
+        permissions.contains(permission)
+            || permissions
+                .iter()
+                .any(|p| p.is_wildcard() && p.includes(permission))
     }
 
     /// Check if set contains any of the given permissions
@@ -218,22 +365,27 @@ impl PermissionSet {
     }
 
     /// Get all permissions as vector
-    #[allow(dead_code)]
     pub fn to_vec(&self) -> Vec<Permission> {
         self.permissions.iter().cloned().collect()
     }
 
-    /// Get all permissions as strings
+    /// Get all permissions as strings, leaving out the ones that are denied or out of scope
     pub fn to_strings(&self) -> Vec<String> {
-        self.permissions.iter().map(|p| p.to_string()).collect()
+        self.permissions
+            .iter()
+            .filter(|p| self.contains(p))
+            .map(|p| p.to_string())
+            .collect()
     }
 
     /// Merge with another permission set
-    #[allow(dead_code)]
     pub fn merge(&mut self, other: &PermissionSet) {
         for permission in &other.permissions {
             self.permissions.insert(permission.clone());
         }
+        for permission in &other.denied {
+            self.denied.insert(permission.clone());
+        }
     }
 }
 
@@ -0,0 +1,215 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+/// What happens to a login once a user already has the maximum number of sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionLimitAction {
+    /// Sign out the oldest session to make room
+    Evict,
+    /// Refuse the new login
+    Reject,
+}
+
+impl SessionLimitAction {
+    /// Value stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionLimitAction::Evict => "evict",
+            SessionLimitAction::Reject => "reject",
+        }
+    }
+
+    /// Parses a stored or configured value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "evict" => Some(SessionLimitAction::Evict),
+            "reject" => Some(SessionLimitAction::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Limits applied to a user's sessions
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionPolicy {
+    /// Most sessions at once, unlimited if None
+    pub max_sessions: Option<u64>,
+    pub limit_action: SessionLimitAction,
+    /// Sessions unused for this long end, never if None
+    pub idle_timeout: Option<Duration>,
+    /// Sessions end this long after sign in, however active they are
+    pub lifetime: Duration,
+}
+
+/// Session settings of a single role, unset fields defer to the defaults
+#[derive(Debug, Clone, Default)]
+pub struct RoleSessionPolicy {
+    pub max_sessions: Option<u64>,
+    pub limit_action: Option<SessionLimitAction>,
+    pub idle_timeout: Option<Duration>,
+    pub lifetime: Option<Duration>,
+}
+
+impl SessionPolicy {
+    /// Combines the policies of all of a user's roles, the strictest setting of each wins.
+    /// Settings no role defines come from `self`
+    pub fn with_roles(self, roles: &[RoleSessionPolicy]) -> Self {
+        Self {
+            max_sessions: roles
+                .iter()
+                .filter_map(|role| role.max_sessions)
+                .min()
+                .or(self.max_sessions),
+            limit_action: roles
+                .iter()
+                .filter_map(|role| role.limit_action)
+                .reduce(|a, b| {
+                    if a == SessionLimitAction::Reject || b == SessionLimitAction::Reject {
+                        SessionLimitAction::Reject
+                    } else {
+                        SessionLimitAction::Evict
+                    }
+                })
+                .unwrap_or(self.limit_action),
+            idle_timeout: roles
+                .iter()
+                .filter_map(|role| role.idle_timeout)
+                .min()
+                .or(self.idle_timeout),
+            lifetime: roles
+                .iter()
+                .filter_map(|role| role.lifetime)
+                .min()
+                .unwrap_or(self.lifetime),
+        }
+    }
+}
+
+/// Short description of the browser and operating system behind a user agent,
+/// e.g. "Firefox 128 on Linux"
+pub fn describe_user_agent(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent.filter(|ua| !ua.trim().is_empty()) else {
+        return "Unknown Device".to_string();
+    };
+
+    // Order matters, most browsers also claim to be Chrome and Safari
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("SamsungBrowser/", "Samsung Internet"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Version/", "Safari"),
+    ]
+    .iter()
+    .find_map(|(marker, name)| {
+        let start = user_agent.find(marker)? + marker.len();
+        let major = user_agent[start..]
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .filter(|version| !version.is_empty());
+        Some(match major {
+            Some(major) => format!("{} {}", name, major),
+            None => name.to_string(),
+        })
+    })
+    // Scripts and CLI tools like "curl/8.5.0"
+    .or_else(|| {
+        let product = user_agent.split([' ', '/']).next()?;
+        (!product.is_empty() && product != "Mozilla").then(|| product.to_string())
+    });
+
+    let os = [
+        ("Windows", "Windows"),
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("CrOS", "ChromeOS"),
+        ("Mac OS X", "macOS"),
+        ("Macintosh", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(browser), None) => browser,
+        (None, Some(os)) => format!("Unknown browser on {}", os),
+        (None, None) => "Unknown Device".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strictest_role_setting_wins() {
+        let defaults = SessionPolicy {
+            max_sessions: Some(10),
+            limit_action: SessionLimitAction::Evict,
+            idle_timeout: None,
+            lifetime: Duration::hours(168),
+        };
+        let roles = [
+            RoleSessionPolicy {
+                max_sessions: Some(3),
+                idle_timeout: Some(Duration::minutes(30)),
+                ..Default::default()
+            },
+            RoleSessionPolicy {
+                max_sessions: Some(5),
+                limit_action: Some(SessionLimitAction::Reject),
+                lifetime: Some(Duration::hours(12)),
+                ..Default::default()
+            },
+        ];
+
+        let policy = defaults.clone().with_roles(&roles);
+        assert_eq!(policy.max_sessions, Some(3));
+        assert_eq!(policy.limit_action, SessionLimitAction::Reject);
+        assert_eq!(policy.idle_timeout, Some(Duration::minutes(30)));
+        assert_eq!(policy.lifetime, Duration::hours(12));
+
+        // A role may also loosen the defaults
+        let loose = RoleSessionPolicy {
+            max_sessions: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(defaults.clone().with_roles(&[loose]).max_sessions, Some(50));
+        assert_eq!(defaults.clone().with_roles(&[]), defaults);
+    }
+
+    #[test]
+    fn user_agents_are_described_by_browser_and_os() {
+        let cases = [
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.2592.87",
+                "Edge 126 on Windows",
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+                "Firefox 128 on Linux",
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+                "Safari 17 on iOS",
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36",
+                "Chrome 126 on Android",
+            ),
+            ("curl/8.5.0", "curl"),
+        ];
+
+        for (user_agent, expected) in cases {
+            assert_eq!(describe_user_agent(Some(user_agent)), expected);
+        }
+        assert_eq!(describe_user_agent(None), "Unknown Device");
+    }
+}
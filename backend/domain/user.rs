@@ -11,6 +11,7 @@ pub struct User {
     #[allow(dead_code)]
     pub last_login: Option<DateTime<Utc>>,
     pub role_id: Option<i32>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -22,6 +23,7 @@ impl User {
         created_at: Option<DateTime<Utc>>,
         last_login: Option<DateTime<Utc>>,
         role_id: Option<i32>,
+        email_verified_at: Option<DateTime<Utc>>,
     ) -> Self {
         Self {
             id,
@@ -30,6 +32,7 @@ impl User {
             created_at,
             last_login,
             role_id,
+            email_verified_at,
         }
     }
 
@@ -42,8 +45,14 @@ impl User {
             created_at: Some(Utc::now()),
             last_login: None,
             role_id: None,
+            email_verified_at: None,
         }
     }
+
+    /// Whether the user has confirmed their email address
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
 }
 
 /// Domain model for user registration
@@ -1,55 +1,52 @@
+use crate::domain::email::{EmailAddress, SignupDomains};
+use crate::domain::password::PasswordPolicy;
 use crate::infrastructure::app_error::AppError;
 use axum::http::StatusCode;
 
 /// Validates email format
 pub fn validate_email(email: &str) -> Result<(), AppError> {
-    if email.is_empty() {
-        return Err(AppError {
-            message: "Email is required".to_string(),
-            status_code: StatusCode::BAD_REQUEST,
-        });
-    }
-
-    // Basic email validation - in production, consider using a proper email validation crate
-    if !email.contains('@') || !email.contains('.') {
-        return Err(AppError {
-            message: "Invalid email format".to_string(),
-            status_code: StatusCode::BAD_REQUEST,
-        });
-    }
-
-    Ok(())
+    EmailAddress::parse(email).map(|_| ())
 }
 
-/// Validates password strength
-pub fn validate_password(password: &str) -> Result<(), AppError> {
-    if password.is_empty() {
-        return Err(AppError {
-            message: "Password is required".to_string(),
-            status_code: StatusCode::BAD_REQUEST,
-        });
-    }
+/// Validates an email address and returns its normalized form
+pub fn normalize_email(email: &str) -> Result<String, AppError> {
+    EmailAddress::parse(email).map(|address| address.normalized())
+}
 
-    if password.len() < 6 {
-        return Err(AppError {
-            message: "Password must be at least 6 characters".to_string(),
-            status_code: StatusCode::BAD_REQUEST,
-        });
-    }
+/// Validates the email address of a new account against the signup domain lists and
+/// returns its normalized form
+pub fn validate_signup_email(email: &str) -> Result<String, AppError> {
+    let address = EmailAddress::parse(email)?;
+    SignupDomains::from_env().check(&address)?;
+    Ok(address.normalized())
+}
 
-    Ok(())
+/// Validates a new password against the configured password policy
+pub fn validate_password(password: &str, email: Option<&str>) -> Result<(), AppError> {
+    PasswordPolicy::from_env().check(password, email)
 }
 
 /// Validates registration input
 pub fn validate_registration_input(email: &str, password: &str) -> Result<(), AppError> {
-    validate_email(email)?;
-    validate_password(password)?;
+    validate_signup_email(email)?;
+    validate_password(password, Some(email))?;
     Ok(())
 }
 
 /// Validates login input
+/// Only checks that an email and password were given, accounts may predate the current rules
 pub fn validate_login_input(email: &str, password: &str) -> Result<(), AppError> {
-    validate_email(email)?;
-    validate_password(password)?;
+    if email.trim().is_empty() {
+        return Err(AppError {
+            message: "Email is required".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        });
+    }
+    if password.is_empty() {
+        return Err(AppError {
+            message: "Password is required".to_string(),
+            status_code: StatusCode::BAD_REQUEST,
+        });
+    }
     Ok(())
 }
@@ -24,6 +24,9 @@ pub struct Model {
     pub request_body: Option<String>,
     pub response_body: Option<String>,
     pub error_message: Option<String>,
+    /// Admin who made the request on behalf of user_id while impersonating them
+    #[schema(value_type = Option<String>)]
+    pub actor_id: Option<Uuid>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,20 @@
+//! `SeaORM` Entity for login_throttles table
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_throttles")]
+pub struct Model {
+    /// "account:<email>" or "ip:<address>"
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub key: String,
+    pub failed_count: i32,
+    pub last_failed_at: DateTimeWithTimeZone,
+    pub locked_until: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,8 +1,20 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.13
 
+pub mod api_keys;
 pub mod audit_logs;
 pub mod database_metrics;
+pub mod login_throttles;
+pub mod mfa_recovery_codes;
+pub mod oidc_login_states;
+pub mod password_history;
 pub mod prelude;
+pub mod rate_limit_buckets;
+pub mod refresh_tokens;
 pub mod roles;
+pub mod user_identities;
+pub mod user_mfa;
+pub mod user_permissions;
+pub mod user_roles;
 pub mod user_sessions;
+pub mod user_tokens;
 pub mod users;
@@ -0,0 +1,23 @@
+//! `SeaORM` Entity for oidc_login_states table
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "oidc_login_states")]
+pub struct Model {
+    /// Random value round-tripped through the provider
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub state: String,
+    pub provider: String,
+    /// PKCE verifier, only its hash is sent with the authorization request
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: DateTimeWithTimeZone,
+    pub created_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,7 +1,19 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.13
 
+pub use super::api_keys::Entity as ApiKeys;
 pub use super::audit_logs::Entity as AuditLogs;
 pub use super::database_metrics::Entity as DatabaseMetrics;
+pub use super::login_throttles::Entity as LoginThrottles;
+pub use super::mfa_recovery_codes::Entity as MfaRecoveryCodes;
+pub use super::oidc_login_states::Entity as OidcLoginStates;
+pub use super::password_history::Entity as PasswordHistory;
+pub use super::rate_limit_buckets::Entity as RateLimitBuckets;
+pub use super::refresh_tokens::Entity as RefreshTokens;
 pub use super::roles::Entity as Roles;
+pub use super::user_identities::Entity as UserIdentities;
+pub use super::user_mfa::Entity as UserMfa;
+pub use super::user_permissions::Entity as UserPermissions;
+pub use super::user_roles::Entity as UserRoles;
 pub use super::user_sessions::Entity as UserSessions;
+pub use super::user_tokens::Entity as UserTokens;
 pub use super::users::Entity as Users;
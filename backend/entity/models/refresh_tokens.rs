@@ -0,0 +1,44 @@
+//! `SeaORM` Entity for refresh_tokens table
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, utoipa::ToSchema,
+)]
+#[sea_orm(table_name = "refresh_tokens")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    #[schema(value_type = String)]
+    pub id: Uuid,
+    #[schema(value_type = String)]
+    pub session_id: Uuid,
+    #[sea_orm(unique)]
+    pub token_hash: String,
+    #[schema(value_type = String)]
+    pub created_at: Option<DateTimeWithTimeZone>,
+    #[schema(value_type = String)]
+    pub expires_at: DateTimeWithTimeZone,
+    #[schema(value_type = String)]
+    pub used_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user_sessions::Entity",
+        from = "Column::SessionId",
+        to = "super::user_sessions::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    UserSessions,
+}
+
+impl Related<super::user_sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSessions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -18,6 +18,15 @@ pub struct Model {
     pub created_at: Option<DateTimeWithTimeZone>,
     #[schema(value_type = String)]
     pub updated_at: Option<DateTimeWithTimeZone>,
+    pub mfa_required: bool,
+    /// Role whose permissions this role inherits
+    pub parent_role_id: Option<i32>,
+    /// Most sessions a member may have at once
+    pub max_sessions: Option<i32>,
+    /// "evict" or "reject" once max_sessions is reached
+    pub session_limit_action: Option<String>,
+    pub idle_timeout_minutes: Option<i32>,
+    pub session_lifetime_hours: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
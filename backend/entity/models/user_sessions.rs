@@ -23,6 +23,9 @@ pub struct Model {
     #[schema(value_type = String)]
     pub expires_at: DateTimeWithTimeZone,
     pub is_active: bool,
+    /// Admin impersonating the user with this session
+    #[schema(value_type = Option<String>)]
+    pub impersonator_id: Option<Uuid>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -19,6 +19,16 @@ pub struct Model {
     #[schema(value_type = String)]
     pub last_login: Option<DateTimeWithTimeZone>,
     pub role_id: Option<i32>,
+    #[schema(value_type = String)]
+    pub email_verified_at: Option<DateTimeWithTimeZone>,
+    pub pending_email: Option<String>,
+    #[schema(value_type = String)]
+    pub deletion_scheduled_at: Option<DateTimeWithTimeZone>,
+    #[schema(value_type = String)]
+    pub password_changed_at: Option<DateTimeWithTimeZone>,
+    /// Lowercased canonical address lookups use, None while it clashes with another account
+    #[sea_orm(unique)]
+    pub email_normalized: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,6 +1,6 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
@@ -32,6 +32,50 @@ impl IntoResponse for AppError {
     }
 }
 
+/// Error for endpoints that can be throttled, adds the Retry-After header AppError can't carry
+#[derive(Debug)]
+pub enum ThrottledError {
+    App(AppError),
+    TooManyRequests {
+        message: String,
+        retry_after_secs: u64,
+    },
+}
+
+impl From<AppError> for ThrottledError {
+    fn from(error: AppError) -> Self {
+        ThrottledError::App(error)
+    }
+}
+
+impl fmt::Display for ThrottledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThrottledError::App(error) => write!(f, "{}", error),
+            ThrottledError::TooManyRequests { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl StdError for ThrottledError {}
+
+impl IntoResponse for ThrottledError {
+    fn into_response(self) -> Response {
+        match self {
+            ThrottledError::App(error) => error.into_response(),
+            ThrottledError::TooManyRequests {
+                message,
+                retry_after_secs,
+            } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(ErrorResponse { message }),
+            )
+                .into_response(),
+        }
+    }
+}
+
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct MessageResponse {
@@ -28,6 +28,7 @@ impl CorsManager {
                 "accept".parse::<HeaderName>().unwrap(),
                 "origin".parse::<HeaderName>().unwrap(),
                 "x-requested-with".parse::<HeaderName>().unwrap(),
+                "x-csrf-token".parse::<HeaderName>().unwrap(),
             ])
             .allow_credentials(true)
     }
@@ -50,6 +51,7 @@ impl CorsManager {
                 "accept".parse::<HeaderName>().unwrap(),
                 "origin".parse::<HeaderName>().unwrap(),
                 "x-requested-with".parse::<HeaderName>().unwrap(),
+                "x-csrf-token".parse::<HeaderName>().unwrap(),
             ])
             .allow_credentials(true)
             .max_age(std::time::Duration::from_secs(3600)) // Cache preflight for 1 hour
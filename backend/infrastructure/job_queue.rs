@@ -1,9 +1,14 @@
 use apalis::prelude::*;
 use apalis_sql::sqlite::SqliteStorage;
-use chrono::Utc;
+use axum::http::StatusCode;
 use sea_orm::sqlx::SqlitePool;
 use serde::{Deserialize, Serialize};
-use std::io::Error;
+use std::{io::Error, sync::OnceLock};
+
+use crate::infrastructure::{app_error::AppError, mailer::MailerManager};
+
+/// Storage used to queue messages from request handlers
+static JOB_STORAGE: OnceLock<SqliteStorage<Message>> = OnceLock::new();
 
 /// Message structure for job queue
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,29 +27,34 @@ impl JobQueueManager {
         SqliteStorage::new(pool)
     }
 
-    /// Produces test messages for the job queue
-    pub async fn produce_messages(storage: &SqliteStorage<Message>) -> Result<(), Error> {
-        let mut storage = storage.clone();
-        for i in 0..1 {
-            storage
-                .schedule(
-                    Message {
-                        to: format!("test{i}@example.com"),
-                        text: "Test background job from apalis".to_string(),
-                        subject: "Background email job".to_string(),
-                    },
-                    (Utc::now() + chrono::Duration::seconds(4)).timestamp(),
-                )
-                .await
-                .unwrap();
+    /// Registers the storage used by `enqueue`
+    pub fn initialize(storage: SqliteStorage<Message>) {
+        if JOB_STORAGE.set(storage).is_err() {
+            eprintln!("Job queue storage already initialized");
         }
+    }
+
+    /// Queues a message to be sent by the job queue monitor
+    pub async fn enqueue(message: Message) -> Result<(), AppError> {
+        let mut storage = JOB_STORAGE
+            .get()
+            .ok_or(AppError {
+                message: "Job queue not initialized".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .clone();
+
+        storage.push(message).await.map_err(|e| AppError {
+            message: format!("Failed to queue message: {}", e),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+
         Ok(())
     }
 
-    /// Sends a message (job handler)
+    /// Sends a message through the configured mail transport (job handler)
     pub async fn send_message(message: Message) -> Result<(), Error> {
-        println!("Sending message: {:?}", message);
-        Ok(())
+        MailerManager::transport().send(&message)
     }
 
     /// Creates and runs the job queue monitor
@@ -6,4 +6,22 @@ pub struct Claims {
     pub sub: String,        // subject (user id)
     pub exp: usize,         // expiration time
     pub session_id: String, // session UUID for tracking
+    // actor (RFC 8693), the admin impersonating the subject
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActorClaims>,
+}
+
+// Actor claim, identifies who is acting on behalf of the subject
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActorClaims {
+    pub sub: String, // actor (admin user id)
+}
+
+// MFA challenge claims, exchanged together with a code for a session
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaChallengeClaims {
+    pub sub: String,     // subject (user id)
+    pub exp: usize,      // expiration time
+    pub purpose: String, // always "mfa_challenge", keeps access tokens from being accepted
+    pub scope: String,   // login flow that issued the challenge ("user" or "admin")
 }
@@ -0,0 +1,377 @@
+//! Forwards tracing events to the SystemLog WebSocket topic
+//!
+//! `WebSocketLogLayer` runs inside whatever emits the event, so it never waits: events are
+//! rate limited and pushed into a bounded queue, anything that doesn't fit is counted and
+//! dropped. `LogForwarder` drains the queue into the WebSocket manager on its own task.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::infrastructure::websocket::{LogSpan, WEBSOCKET_MANAGER, WebSocketMessage};
+
+/// How often the forwarder reports the events it had to drop
+const DROPPED_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Targets never forwarded, sending a log frame makes the WebSocket library log again
+const IGNORED_TARGETS: &[&str] = &["tungstenite", "tokio_tungstenite", module_path!()];
+
+tokio::task_local! {
+    /// Set while the forwarder runs, whatever it logs stays out of the queue
+    static FORWARDING: ();
+}
+
+/// Settings of the SystemLog forwarding
+#[derive(Debug, Clone)]
+pub struct LogBroadcastConfig {
+    /// Least severe level forwarded, OFF disables forwarding
+    pub level: LevelFilter,
+    /// Events waiting for the forwarder before new ones are dropped
+    pub queue_size: usize,
+    /// Events forwarded per second, the rest are dropped
+    pub max_per_second: u32,
+}
+
+impl LogBroadcastConfig {
+    /// Reads WEBSOCKET_LOG_LEVEL, WEBSOCKET_LOG_QUEUE_SIZE and WEBSOCKET_LOG_MAX_PER_SECOND.
+    /// Events are only seen here once they pass RUST_LOG
+    pub fn from_env() -> Self {
+        let level = env::var("WEBSOCKET_LOG_LEVEL")
+            .ok()
+            .and_then(|value| LevelFilter::from_str(&value).ok())
+            .unwrap_or(LevelFilter::INFO);
+        let queue_size = env::var("WEBSOCKET_LOG_QUEUE_SIZE")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(1000)
+            .max(1);
+        let max_per_second = env::var("WEBSOCKET_LOG_MAX_PER_SECOND")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(100)
+            .max(1);
+
+        Self {
+            level,
+            queue_size,
+            max_per_second,
+        }
+    }
+}
+
+/// Creates the layer and the forwarder that has to be spawned for its events to go out,
+/// None when forwarding is turned off
+pub fn log_broadcast(config: &LogBroadcastConfig) -> Option<(WebSocketLogLayer, LogForwarder)> {
+    if config.level == LevelFilter::OFF {
+        return None;
+    }
+
+    let (tx, rx) = mpsc::channel(config.queue_size);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let layer = WebSocketLogLayer {
+        tx,
+        level: config.level,
+        limiter: RateLimiter::new(config.max_per_second),
+        dropped: dropped.clone(),
+    };
+    Some((layer, LogForwarder { rx, dropped }))
+}
+
+/// Tracing layer that queues events for the SystemLog topic
+pub struct WebSocketLogLayer {
+    tx: mpsc::Sender<WebSocketMessage>,
+    level: LevelFilter,
+    limiter: RateLimiter,
+    dropped: Arc<AtomicU64>,
+}
+
+impl WebSocketLogLayer {
+    fn forwards(&self, event: &Event<'_>) -> bool {
+        let metadata = event.metadata();
+        metadata.level() <= &self.level
+            && !IGNORED_TARGETS
+                .iter()
+                .any(|ignored| metadata.target().starts_with(ignored))
+            && FORWARDING.try_with(|_| ()).is_err()
+    }
+}
+
+impl<S> Layer<S> for WebSocketLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut visitor = FieldVisitor::default();
+            attrs.record(&mut visitor);
+            span.extensions_mut().insert(SpanFields(visitor.fields));
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(SpanFields(fields)) = span.extensions_mut().get_mut::<SpanFields>()
+        {
+            let mut visitor = FieldVisitor {
+                fields: std::mem::take(fields),
+                message: None,
+            };
+            values.record(&mut visitor);
+            *fields = visitor.fields;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !self.forwards(event) {
+            return;
+        }
+        if !self.limiter.allow() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| LogSpan {
+                        name: span.name().to_string(),
+                        fields: span
+                            .extensions()
+                            .get::<SpanFields>()
+                            .map(|SpanFields(fields)| fields.clone())
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let metadata = event.metadata();
+        let message = WebSocketMessage::SystemLog {
+            level: metadata.level().as_str().to_lowercase(),
+            message: visitor.message.unwrap_or_default(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            target: metadata.target().to_string(),
+            fields: visitor.fields,
+            spans,
+        };
+
+        if self.tx.try_send(message).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Drains the layer's queue into the SystemLog topic
+pub struct LogForwarder {
+    rx: mpsc::Receiver<WebSocketMessage>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogForwarder {
+    /// Runs until the layer is gone
+    pub async fn run(mut self) {
+        FORWARDING
+            .scope((), async move {
+                let mut report = tokio::time::interval(DROPPED_REPORT_INTERVAL);
+                loop {
+                    tokio::select! {
+                        message = self.rx.recv() => match message {
+                            Some(message) => WEBSOCKET_MANAGER.broadcast(message).await,
+                            None => break,
+                        },
+                        _ = report.tick() => self.report_dropped().await,
+                    }
+                }
+            })
+            .await;
+    }
+
+    async fn report_dropped(&self) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped == 0 {
+            return;
+        }
+
+        let message = format!("{} log messages were dropped", dropped);
+        tracing::warn!("{} before reaching WebSocket clients", message);
+        WEBSOCKET_MANAGER
+            .broadcast(WebSocketMessage::SystemLog {
+                level: "warn".to_string(),
+                message,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                target: module_path!().to_string(),
+                fields: BTreeMap::from([("dropped".to_string(), dropped.into())]),
+                spans: Vec::new(),
+            })
+            .await;
+    }
+}
+
+/// Fields recorded on a span, kept in its extensions
+struct SpanFields(BTreeMap<String, serde_json::Value>);
+
+/// Collects fields as JSON values, the event's message separately
+#[derive(Default)]
+struct FieldVisitor {
+    fields: BTreeMap<String, serde_json::Value>,
+    message: Option<String>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: serde_json::Value) {
+        if field.name() == "message" {
+            self.message = Some(match value {
+                serde_json::Value::String(message) => message,
+                value => value.to_string(),
+            });
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.insert(field, value.to_string().into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value).into());
+    }
+}
+
+/// Fixed one second window, cheap enough to check on every event
+struct RateLimiter {
+    max_per_second: u32,
+    /// Second the window started at and the events let through in it
+    window: Mutex<(u64, u32)>,
+}
+
+impl RateLimiter {
+    fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            window: Mutex::new((0, 0)),
+        }
+    }
+
+    fn allow(&self) -> bool {
+        self.allow_at(chrono::Utc::now().timestamp() as u64)
+    }
+
+    fn allow_at(&self, second: u64) -> bool {
+        // A poisoned lock only means another thread panicked mid-event, the counts are still usable
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        if window.0 != second {
+            *window = (second, 0);
+        }
+        if window.1 >= self.max_per_second {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn events_are_forwarded_with_fields_and_spans() {
+        let config = LogBroadcastConfig {
+            level: LevelFilter::INFO,
+            queue_size: 2,
+            max_per_second: 100,
+        };
+        let (layer, mut forwarder) = log_broadcast(&config).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("request", method = "GET", user_id = tracing::field::Empty);
+            let _entered = span.enter();
+            span.record("user_id", 7);
+
+            // Events of this module are never forwarded, hence the targets
+            tracing::debug!(target: "app", "below the level");
+            tracing::warn!(target: "tungstenite::protocol", "ignored target");
+            tracing::warn!(target: "app", status_code = 404, found = false, "Request error");
+            tracing::info!(target: "app", "queued");
+            tracing::info!(target: "app", "queue is full");
+        });
+
+        match forwarder.rx.try_recv().unwrap() {
+            WebSocketMessage::SystemLog {
+                level,
+                message,
+                fields,
+                spans,
+                ..
+            } => {
+                assert_eq!(level, "warn");
+                assert_eq!(message, "Request error");
+                assert_eq!(fields["status_code"], 404);
+                assert_eq!(fields["found"], false);
+                assert_eq!(spans.len(), 1);
+                assert_eq!(spans[0].name, "request");
+                assert_eq!(spans[0].fields["method"], "GET");
+                assert_eq!(spans[0].fields["user_id"], 7);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+        assert!(forwarder.rx.try_recv().is_ok());
+        assert!(forwarder.rx.try_recv().is_err());
+        assert_eq!(forwarder.dropped.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn rate_limit_resets_every_second() {
+        let limiter = RateLimiter::new(2);
+        assert!(limiter.allow_at(10));
+        assert!(limiter.allow_at(10));
+        assert!(!limiter.allow_at(10));
+        assert!(limiter.allow_at(11));
+    }
+}
@@ -2,9 +2,11 @@ use std::env;
 use tracing_subscriber::{
     EnvFilter,
     fmt::{format::FmtSpan, time::UtcTime},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
 };
 
-use crate::infrastructure::websocket::broadcast_system_log;
+use crate::infrastructure::log_broadcast::{LogBroadcastConfig, log_broadcast};
 
 /// Logging configuration manager
 pub struct LoggingManager;
@@ -29,7 +31,9 @@ impl LoggingManager {
                 .add_directive("tower_http=warn".parse().unwrap())
         });
 
-        // Configure tracing subscriber with custom layer for WebSocket broadcasting
+        // Events at or above WEBSOCKET_LOG_LEVEL are also forwarded to the SystemLog topic
+        let (websocket_layer, forwarder) = log_broadcast(&LogBroadcastConfig::from_env()).unzip();
+
         let subscriber = tracing_subscriber::fmt()
             .with_env_filter(env_filter)
             .with_timer(UtcTime::rfc_3339())
@@ -41,37 +45,20 @@ impl LoggingManager {
 
         // Use JSON format in production, pretty format in development
         if environment == "production" {
-            subscriber.json().init();
+            subscriber.json().finish().with(websocket_layer).init();
         } else {
-            subscriber.pretty().init();
+            subscriber.pretty().finish().with(websocket_layer).init();
         }
 
-        // Set up custom event subscriber for WebSocket broadcasting
-        tracing::info!("Logging initialized for environment: {}", environment);
+        if let Some(forwarder) = forwarder {
+            tokio::spawn(forwarder.run());
+        }
 
-        // Broadcast the initialization message
-        let environment_clone = environment.clone();
-        tokio::spawn(async move {
-            broadcast_system_log(
-                "info".to_string(),
-                format!(
-                    "Logging system initialized for environment: {}",
-                    environment_clone
-                ),
-                "logging".to_string(),
-            )
-            .await;
-        });
+        tracing::info!("Logging initialized for environment: {}", environment);
     }
 
     /// Create a request ID for tracking requests across the system
     pub fn generate_request_id() -> String {
         uuid::Uuid::new_v4().to_string()
     }
-
-    /// Broadcast a log message to WebSocket clients
-    #[allow(dead_code)]
-    pub async fn broadcast_log(level: &str, message: &str, target: &str) {
-        broadcast_system_log(level.to_string(), message.to_string(), target.to_string()).await;
-    }
 }
@@ -0,0 +1,81 @@
+//! Mail transports
+//!
+//! Messages queued through `JobQueueManager` are delivered by the transport selected with
+//! `MAIL_TRANSPORT`. Only local sinks are built in, real providers plug in by implementing
+//! `MailTransport` and adding a branch to `MailerManager::transport`.
+
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{Error, Write},
+    path::PathBuf,
+};
+
+use crate::infrastructure::job_queue::Message;
+
+/// Delivers a single message
+pub trait MailTransport: Send + Sync {
+    fn send(&self, message: &Message) -> Result<(), Error>;
+}
+
+/// Prints messages to stdout, the default for local development
+pub struct StdoutTransport;
+
+impl MailTransport for StdoutTransport {
+    fn send(&self, message: &Message) -> Result<(), Error> {
+        println!("{}", MailerManager::format_message(message));
+        Ok(())
+    }
+}
+
+/// Appends messages to a file, handy for inspecting mails in tests
+pub struct FileTransport {
+    pub path: PathBuf,
+}
+
+impl MailTransport for FileTransport {
+    fn send(&self, message: &Message) -> Result<(), Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", MailerManager::format_message(message))
+    }
+}
+
+/// Mail transport manager
+pub struct MailerManager;
+
+impl MailerManager {
+    /// Creates the transport configured with MAIL_TRANSPORT (`stdout` or `file`)
+    /// The file transport writes to MAIL_FILE_PATH (defaults to `mail.log`)
+    pub fn transport() -> Box<dyn MailTransport> {
+        let transport = env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "stdout".to_string());
+
+        match transport.as_str() {
+            "file" => Box::new(FileTransport {
+                path: env::var("MAIL_FILE_PATH")
+                    .unwrap_or_else(|_| "mail.log".to_string())
+                    .into(),
+            }),
+            "stdout" => Box::new(StdoutTransport),
+            other => {
+                eprintln!("Unknown MAIL_TRANSPORT '{}', falling back to stdout", other);
+                Box::new(StdoutTransport)
+            }
+        }
+    }
+
+    /// Renders a message the way the local sinks write it
+    fn format_message(message: &Message) -> String {
+        let from = env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string());
+        format!(
+            "From: {}\nTo: {}\nSubject: {}\nDate: {}\n\n{}\n",
+            from,
+            message.to,
+            message.subject,
+            chrono::Utc::now().to_rfc2822(),
+            message.text
+        )
+    }
+}
@@ -3,10 +3,19 @@ pub mod cors;
 pub mod database;
 pub mod job_queue;
 pub mod jwt_claims;
+pub mod jwt_keys;
+pub mod log_broadcast;
 pub mod logging;
 pub mod macros;
+pub mod mailer;
+pub mod oidc;
 pub mod openapi;
+pub mod password_hashing;
 pub mod query_performance;
+pub mod rate_limit;
+pub mod request_metrics;
 pub mod scheduler;
 pub mod server;
+pub mod session_cookies;
+pub mod trusted_proxies;
 pub mod websocket;
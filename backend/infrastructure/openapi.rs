@@ -7,10 +7,13 @@ use crate::bridge::types::admin::{
     UsersQueryParams,
 };
 use crate::bridge::types::auth::{
-    AUTH_TAG, AuthUser, LoginRequest, LoginResponse, ProfileResponse, RegisterRequest,
-    RegisterResponse,
+    AUTH_TAG, AuthUser, ForgotPasswordRequest, JwksResponse, LoginRequest, LoginResponse,
+    MfaChallengeResponse, MfaCodeRequest, MfaRecoveryCodesResponse, MfaSetupResponse,
+    MfaStatusResponse, MfaVerifyRequest, ProfileResponse, RefreshRequest, RegisterRequest,
+    RegisterResponse, ResetPasswordRequest, VerifyEmailRequest,
 };
 use crate::infrastructure::app_error::{ErrorResponse, MessageResponse};
+use crate::infrastructure::jwt_keys::PublicJwk;
 
 /// OpenAPI documentation structure
 #[derive(OpenApi)]
@@ -33,7 +36,9 @@ use crate::infrastructure::app_error::{ErrorResponse, MessageResponse};
     ),
     components(
         schemas(
-            RegisterRequest, RegisterResponse, LoginRequest, LoginResponse, ProfileResponse, AuthUser,
+            RegisterRequest, RegisterResponse, LoginRequest, LoginResponse, RefreshRequest, ProfileResponse, AuthUser,
+            PublicJwk, JwksResponse, VerifyEmailRequest, ForgotPasswordRequest, ResetPasswordRequest,
+            MfaChallengeResponse, MfaSetupResponse, MfaVerifyRequest, MfaCodeRequest, MfaRecoveryCodesResponse, MfaStatusResponse,
             MessageResponse, ErrorResponse,
             AdminLoginRequest, AdminLoginResponse, AuditLogResponse,
             LogsQueryParams, UsersQueryParams, CreateUserRequest, UpdateUserRequest, UserResponse,
@@ -0,0 +1,156 @@
+//! Password hashing
+//!
+//! New passwords are hashed with Argon2id using the cost configured with PASSWORD_HASH_MEMORY_KIB,
+//! PASSWORD_HASH_ITERATIONS and PASSWORD_HASH_PARALLELISM. Hashes made with other parameters,
+//! and bcrypt hashes imported from older systems, still verify and are replaced with a current
+//! hash the next time the user signs in.
+
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use axum::http::StatusCode;
+use std::env;
+
+use crate::infrastructure::app_error::AppError;
+
+/// Prefixes of the bcrypt variants that can be verified
+const BCRYPT_PREFIXES: [&str; 3] = ["$2a$", "$2b$", "$2y$"];
+
+/// Hashes and verifies passwords
+pub struct PasswordHashing;
+
+impl PasswordHashing {
+    /// Argon2 cost for new hashes, defaults to the OWASP recommendation of 19 MiB, 2 iterations
+    /// and 1 lane. Invalid settings fall back to the defaults
+    pub fn params() -> Params {
+        let number = |var: &str, default: u32| {
+            env::var(var)
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        Params::new(
+            number("PASSWORD_HASH_MEMORY_KIB", Params::DEFAULT_M_COST),
+            number("PASSWORD_HASH_ITERATIONS", Params::DEFAULT_T_COST),
+            number("PASSWORD_HASH_PARALLELISM", Params::DEFAULT_P_COST),
+            None,
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                "Invalid password hashing parameters, using the defaults: {}",
+                e
+            );
+            Params::default()
+        })
+    }
+
+    /// Hashes a password with the configured parameters
+    pub fn hash(password: &str) -> Result<String, AppError> {
+        Self::hash_with(password, Self::params())
+    }
+
+    /// Checks a password against an Argon2 or bcrypt hash
+    pub fn verify(password_hash: &str, password: &str) -> Result<bool, AppError> {
+        if Self::is_bcrypt(password_hash) {
+            return bcrypt::verify(password, password_hash).map_err(|_| Self::invalid_hash());
+        }
+
+        let parsed = PasswordHash::new(password_hash).map_err(|_| Self::invalid_hash())?;
+        // The parameters are read from the hash itself
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    }
+
+    /// Whether a hash should be replaced, because it isn't Argon2id or uses other parameters
+    pub fn needs_rehash(password_hash: &str) -> bool {
+        Self::needs_rehash_with(password_hash, &Self::params())
+    }
+
+    /// Whether a hash can be verified, used to check imported hashes
+    pub fn is_supported(password_hash: &str) -> bool {
+        if Self::is_bcrypt(password_hash) {
+            return password_hash.parse::<bcrypt::HashParts>().is_ok();
+        }
+        PasswordHash::new(password_hash).is_ok_and(|parsed| {
+            Algorithm::try_from(parsed.algorithm).is_ok() && Params::try_from(&parsed).is_ok()
+        })
+    }
+
+    fn hash_with(password: &str, params: Params) -> Result<String, AppError> {
+        let salt = SaltString::generate(&mut rand_core::OsRng);
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| AppError {
+                message: "Failed to hash password".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?
+            .to_string())
+    }
+
+    fn needs_rehash_with(password_hash: &str, params: &Params) -> bool {
+        let Ok(parsed) = PasswordHash::new(password_hash) else {
+            return true;
+        };
+        let Ok(current) = Params::try_from(&parsed) else {
+            return true;
+        };
+
+        Algorithm::try_from(parsed.algorithm) != Ok(Algorithm::Argon2id)
+            || parsed.version != Some(Version::V0x13.into())
+            || current.m_cost() != params.m_cost()
+            || current.t_cost() != params.t_cost()
+            || current.p_cost() != params.p_cost()
+    }
+
+    fn is_bcrypt(password_hash: &str) -> bool {
+        BCRYPT_PREFIXES
+            .iter()
+            .any(|prefix| password_hash.starts_with(prefix))
+    }
+
+    fn invalid_hash() -> AppError {
+        AppError {
+            message: "Invalid password hash".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outdated_hashes_verify_and_need_rehash() {
+        let cheap = Params::new(1024, 1, 1, None).unwrap();
+        let stronger = Params::new(2048, 2, 1, None).unwrap();
+
+        let argon2 = PasswordHashing::hash_with("correct horse", cheap.clone()).unwrap();
+        assert!(PasswordHashing::verify(&argon2, "correct horse").unwrap());
+        assert!(!PasswordHashing::verify(&argon2, "wrong horse").unwrap());
+        assert!(!PasswordHashing::needs_rehash_with(&argon2, &cheap));
+        assert!(PasswordHashing::needs_rehash_with(&argon2, &stronger));
+
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, cheap.clone())
+            .hash_password(
+                b"correct horse",
+                &SaltString::generate(&mut rand_core::OsRng),
+            )
+            .unwrap()
+            .to_string();
+        assert!(PasswordHashing::verify(&argon2i, "correct horse").unwrap());
+        assert!(PasswordHashing::needs_rehash_with(&argon2i, &cheap));
+
+        let bcrypt = bcrypt::hash("correct horse", 4).unwrap();
+        assert!(PasswordHashing::is_supported(&bcrypt));
+        assert!(PasswordHashing::verify(&bcrypt, "correct horse").unwrap());
+        assert!(!PasswordHashing::verify(&bcrypt, "wrong horse").unwrap());
+        assert!(PasswordHashing::needs_rehash_with(&bcrypt, &cheap));
+
+        assert!(!PasswordHashing::is_supported("plaintext"));
+        assert!(!PasswordHashing::is_supported("$1$md5$crypt"));
+    }
+}
@@ -0,0 +1,404 @@
+//! Token bucket rate limiting
+//!
+//! Each policy gives every client a bucket of `capacity` tokens that refills over `period`.
+//! Buckets live in memory by default, RATE_LIMIT_STORE=database keeps them in SQLite so
+//! limits survive restarts and are shared between server processes.
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use sea_orm::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::entity::models::{prelude::*, rate_limit_buckets};
+use crate::infrastructure::app_error::AppError;
+
+/// Most buckets kept in memory, the least recently used one makes room for a new client
+const MAX_MEMORY_BUCKETS: usize = 10_000;
+
+/// Time an empty bucket takes to fill up again, every policy refills over a minute
+const REFILL_PERIOD: Duration = Duration::from_secs(60);
+
+/// What identifies a client for a policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// Client IP address
+    Ip,
+    /// Authenticated user, falls back to the IP for anonymous requests
+    User,
+    /// API key the auth middleware accepted, falls back to the user and then the IP
+    ApiKey,
+}
+
+/// Limit applied to one group of routes
+#[derive(Debug, Clone)]
+pub struct RateLimitPolicy {
+    /// Identifies the policy in bucket keys and its environment override
+    pub name: &'static str,
+    pub capacity: u32,
+    pub period: Duration,
+    pub key: RateLimitKey,
+}
+
+impl RateLimitPolicy {
+    /// Allows `requests` per minute with bursts of the same size
+    /// Overridden with RATE_LIMIT_<NAME>_PER_MINUTE, e.g. RATE_LIMIT_REGISTER_PER_MINUTE
+    pub fn per_minute(name: &'static str, requests: u32, key: RateLimitKey) -> Self {
+        let capacity = env::var(format!("RATE_LIMIT_{}_PER_MINUTE", name.to_uppercase()))
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(requests)
+            .max(1);
+
+        Self {
+            name,
+            capacity,
+            period: REFILL_PERIOD,
+            key,
+        }
+    }
+
+    /// Tokens added back per second
+    fn refill_rate(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+
+    /// Value for the RateLimit-Policy header, e.g. "5;w=60"
+    pub fn header_value(&self) -> String {
+        format!("{};w={}", self.capacity, self.period.as_secs())
+    }
+}
+
+/// Outcome of taking a token
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset_secs: u64,
+    /// Seconds until the next token, set when the request was rejected
+    pub retry_after_secs: Option<u64>,
+}
+
+/// A bucket's state, refilled lazily whenever a token is taken
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+}
+
+impl Bucket {
+    /// Refills for `elapsed` seconds, then tries to take one token
+    fn take(&mut self, policy: &RateLimitPolicy, elapsed: f64) -> RateLimitDecision {
+        let rate = policy.refill_rate();
+        let capacity = policy.capacity as f64;
+        self.tokens = (self.tokens + elapsed.max(0.0) * rate).min(capacity);
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+
+        RateLimitDecision {
+            allowed,
+            limit: policy.capacity,
+            remaining: self.tokens.floor() as u32,
+            reset_secs: ((capacity - self.tokens) / rate).ceil() as u64,
+            retry_after_secs: (!allowed)
+                .then(|| ((1.0 - self.tokens) / rate).ceil().max(1.0) as u64),
+        }
+    }
+}
+
+/// A bucket kept in memory
+struct MemoryBucket {
+    bucket: Bucket,
+    updated_at: Instant,
+    /// Position in `MemoryBuckets::recency`
+    tick: u64,
+}
+
+/// Buckets kept in memory, at most `capacity` of them
+struct MemoryBuckets {
+    capacity: usize,
+    buckets: HashMap<String, MemoryBucket>,
+    /// Keys by the tick of their last use, the first one is evicted when the map is full
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl MemoryBuckets {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            buckets: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn take(&mut self, key: &str, policy: &RateLimitPolicy, now: Instant) -> RateLimitDecision {
+        self.tick += 1;
+
+        let decision = match self.buckets.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.tick);
+                entry.tick = self.tick;
+                let decision = entry
+                    .bucket
+                    .take(policy, now.duration_since(entry.updated_at).as_secs_f64());
+                entry.updated_at = now;
+                decision
+            }
+            None => {
+                if self.buckets.len() >= self.capacity
+                    && let Some((_, oldest)) = self.recency.pop_first()
+                {
+                    self.buckets.remove(&oldest);
+                }
+
+                let mut bucket = Bucket {
+                    tokens: policy.capacity as f64,
+                };
+                let decision = bucket.take(policy, 0.0);
+                self.buckets.insert(
+                    key.to_string(),
+                    MemoryBucket {
+                        bucket,
+                        updated_at: now,
+                        tick: self.tick,
+                    },
+                );
+                decision
+            }
+        };
+
+        self.recency.insert(self.tick, key.to_string());
+        decision
+    }
+}
+
+/// Where buckets are kept
+enum RateLimitStore {
+    Memory(Mutex<MemoryBuckets>),
+    Database(DatabaseConnection),
+}
+
+impl RateLimitStore {
+    /// Store selected by RATE_LIMIT_STORE, "memory" (default) or "database"
+    fn from_env(db: DatabaseConnection) -> Self {
+        match env::var("RATE_LIMIT_STORE").as_deref() {
+            Ok("database") => RateLimitStore::Database(db),
+            _ => RateLimitStore::Memory(Mutex::new(MemoryBuckets::new(MAX_MEMORY_BUCKETS))),
+        }
+    }
+
+    async fn take(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, AppError> {
+        match self {
+            RateLimitStore::Memory(buckets) => {
+                let mut buckets = buckets.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(buckets.take(key, policy, Instant::now()))
+            }
+            RateLimitStore::Database(db) => Self::take_from_database(db, key, policy).await,
+        }
+    }
+
+    async fn take_from_database(
+        db: &DatabaseConnection,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, AppError> {
+        let database_error = |_| AppError {
+            message: "Database error".to_string(),
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let now = Utc::now();
+        let txn = db.begin().await.map_err(database_error)?;
+
+        let existing = RateLimitBuckets::find_by_id(key.to_string())
+            .one(&txn)
+            .await
+            .map_err(database_error)?;
+
+        let (mut bucket, elapsed) = match &existing {
+            Some(model) => (
+                Bucket {
+                    tokens: model.tokens,
+                },
+                (now - model.updated_at.to_utc()).num_milliseconds() as f64 / 1000.0,
+            ),
+            None => (
+                Bucket {
+                    tokens: policy.capacity as f64,
+                },
+                0.0,
+            ),
+        };
+        let decision = bucket.take(policy, elapsed);
+
+        let model = rate_limit_buckets::ActiveModel {
+            key: Set(key.to_string()),
+            tokens: Set(bucket.tokens),
+            updated_at: Set(now.into()),
+        };
+        match existing {
+            Some(_) => model.update(&txn).await.map(|_| ()),
+            None => model.insert(&txn).await.map(|_| ()),
+        }
+        .map_err(database_error)?;
+        txn.commit().await.map_err(database_error)?;
+
+        Ok(decision)
+    }
+}
+
+/// A policy together with the store holding its buckets, used as middleware state
+#[derive(Clone)]
+pub struct RateLimiter {
+    pub policy: Arc<RateLimitPolicy>,
+    store: Arc<RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitPolicy, db: DatabaseConnection) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            store: Arc::new(RateLimitStore::from_env(db)),
+        }
+    }
+
+    /// Takes a token from the client's bucket, `client` is already resolved from the policy's key
+    pub async fn check(&self, client: &str) -> Result<RateLimitDecision, AppError> {
+        let key = format!("{}:{}", self.policy.name, client);
+        self.store.take(&key, &self.policy).await
+    }
+
+    /// Deletes database buckets not touched for a whole refill period, they are full again
+    /// and a missing bucket starts out full, so no client's limit changes
+    pub async fn purge_idle_buckets(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let idle_since = Utc::now() - REFILL_PERIOD;
+        let result = RateLimitBuckets::delete_many()
+            .filter(rate_limit_buckets::Column::UpdatedAt.lte(idle_since))
+            .exec(db)
+            .await
+            .map_err(|e| AppError {
+                message: format!("Database error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            })?;
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(capacity: u32) -> RateLimitPolicy {
+        RateLimitPolicy {
+            name: "test",
+            capacity,
+            period: Duration::from_secs(60),
+            key: RateLimitKey::Ip,
+        }
+    }
+
+    #[test]
+    fn bucket_allows_bursts_then_refills() {
+        let policy = policy(3);
+        let mut bucket = Bucket { tokens: 3.0 };
+
+        for remaining in [2, 1, 0] {
+            let decision = bucket.take(&policy, 0.0);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let rejected = bucket.take(&policy, 0.0);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after_secs, Some(20));
+        assert_eq!(rejected.reset_secs, 60);
+
+        // One token every 20 seconds
+        assert!(bucket.take(&policy, 20.0).allowed);
+        assert!(!bucket.take(&policy, 1.0).allowed);
+    }
+
+    #[test]
+    fn bucket_never_exceeds_capacity() {
+        let policy = policy(2);
+        let mut bucket = Bucket { tokens: 0.0 };
+
+        let decision = bucket.take(&policy, 3600.0);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+    }
+
+    #[tokio::test]
+    async fn memory_store_keeps_clients_apart() {
+        let store = RateLimitStore::Memory(Mutex::new(MemoryBuckets::new(10)));
+        let policy = policy(1);
+
+        assert!(store.take("test:a", &policy).await.unwrap().allowed);
+        assert!(!store.take("test:a", &policy).await.unwrap().allowed);
+        assert!(store.take("test:b", &policy).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn idle_database_buckets_are_purged() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let table =
+            Schema::new(DbBackend::Sqlite).create_table_from_entity(rate_limit_buckets::Entity);
+        db.execute(db.get_database_backend().build(&table))
+            .await
+            .unwrap();
+
+        let store = RateLimitStore::Database(db.clone());
+        let policy = policy(1);
+        assert!(store.take("test:busy", &policy).await.unwrap().allowed);
+        rate_limit_buckets::ActiveModel {
+            key: Set("test:idle".to_string()),
+            tokens: Set(0.0),
+            updated_at: Set((Utc::now() - REFILL_PERIOD * 2).into()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(RateLimiter::purge_idle_buckets(&db).await.unwrap(), 1);
+        let remaining = RateLimitBuckets::find().all(&db).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].key, "test:busy");
+
+        // The busy client is still limited, the idle one starts over with a full bucket
+        assert!(!store.take("test:busy", &policy).await.unwrap().allowed);
+        assert!(store.take("test:idle", &policy).await.unwrap().allowed);
+    }
+
+    #[test]
+    fn memory_store_evicts_the_least_recently_used_bucket() {
+        let mut buckets = MemoryBuckets::new(2);
+        let policy = policy(1);
+        let now = Instant::now();
+
+        assert!(buckets.take("a", &policy, now).allowed);
+        assert!(buckets.take("b", &policy, now).allowed);
+        assert!(!buckets.take("a", &policy, now).allowed);
+
+        // "b" was used longest ago, "a" keeps its empty bucket
+        assert!(buckets.take("c", &policy, now).allowed);
+        assert_eq!(buckets.buckets.len(), 2);
+        assert_eq!(buckets.recency.len(), 2);
+        assert!(!buckets.take("a", &policy, now).allowed);
+        assert!(buckets.take("b", &policy, now).allowed);
+        assert!(!buckets.buckets.contains_key("c"));
+    }
+}
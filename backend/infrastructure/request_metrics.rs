@@ -0,0 +1,440 @@
+//! Rolling request metrics
+//!
+//! `request_logging_middleware` records every request here, counted per route and status class
+//! in 5 second buckets with a latency histogram each. Buckets older than the longest window are
+//! dropped, so the figures for the 1, 5 and 15 minute windows come from memory without touching
+//! `audit_logs`.
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Length of one bucket
+const BUCKET_SECS: u64 = 5;
+
+/// Windows the metrics are reported for
+pub const WINDOWS_SECS: [u64; 3] = [60, 5 * 60, 15 * 60];
+
+/// Buckets kept, enough for the longest window
+const BUCKET_COUNT: u64 = 15 * 60 / BUCKET_SECS;
+
+/// Upper bound of the first latency bucket in microseconds
+const LATENCY_BASE_MICROS: f64 = 100.0;
+
+/// Each latency bucket is this much wider than the previous one, so percentiles are
+/// at most 20% above the real value
+const LATENCY_GROWTH: f64 = 1.2;
+
+/// Latency buckets, the last one also takes everything above a minute
+const LATENCY_BUCKETS: usize = 74;
+
+/// Routes listed in a snapshot, busiest first
+const TOP_ROUTES: usize = 10;
+
+/// Status code class, e.g. 2xx
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StatusClass(u16);
+
+impl StatusClass {
+    pub fn of(status: StatusCode) -> Self {
+        Self(status.as_u16() / 100)
+    }
+
+    fn is_success(&self) -> bool {
+        self.0 < 4
+    }
+
+    fn is_client_error(&self) -> bool {
+        self.0 == 4
+    }
+
+    fn is_server_error(&self) -> bool {
+        self.0 >= 5
+    }
+
+    fn label(&self) -> String {
+        format!("{}xx", self.0)
+    }
+}
+
+/// Figures for one window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsWindow {
+    pub window_secs: u64,
+    pub total_requests: u64,
+    pub requests_per_second: f64,
+    /// Share of 1xx, 2xx and 3xx responses, from 0 to 1
+    pub success_rate: f64,
+    /// Share of 4xx responses
+    pub client_error_rate: f64,
+    /// Share of 5xx responses
+    pub error_rate: f64,
+    pub avg_response_time_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Figures for one route over the 5 minute window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteMetrics {
+    pub method: String,
+    /// Route template, e.g. /api/v1/admin/users/{id}
+    pub route: String,
+    pub total_requests: u64,
+    /// Requests per status class, e.g. {"2xx": 10, "4xx": 1}
+    pub status_classes: BTreeMap<String, u64>,
+    /// Share of 5xx responses
+    pub error_rate: f64,
+    pub avg_response_time_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Everything the broadcaster publishes
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    /// One entry per `WINDOWS_SECS`, shortest first
+    pub windows: Vec<MetricsWindow>,
+    /// Busiest routes of the 5 minute window
+    pub routes: Vec<RouteMetrics>,
+}
+
+/// Request counts and latencies of one route and status class
+#[derive(Debug, Clone)]
+struct Stats {
+    count: u64,
+    total_micros: u64,
+    max_micros: u64,
+    histogram: Vec<u32>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            total_micros: 0,
+            max_micros: 0,
+            histogram: vec![0; LATENCY_BUCKETS],
+        }
+    }
+}
+
+impl Stats {
+    fn record(&mut self, micros: u64) {
+        self.count += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+        self.histogram[latency_bucket(micros)] += 1;
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.count += other.count;
+        self.total_micros += other.total_micros;
+        self.max_micros = self.max_micros.max(other.max_micros);
+        for (count, other) in self.histogram.iter_mut().zip(&other.histogram) {
+            *count += other;
+        }
+    }
+
+    fn avg_ms(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.total_micros as f64 / self.count as f64 / 1000.0
+    }
+
+    /// Upper bound of the bucket holding the percentile, capped at the slowest request
+    fn percentile_ms(&self, percentile: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = ((self.count as f64 * percentile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.histogram.iter().enumerate() {
+            seen += *count as u64;
+            if seen >= rank {
+                let upper = LATENCY_BASE_MICROS * LATENCY_GROWTH.powi(bucket as i32);
+                return upper.min(self.max_micros as f64) / 1000.0;
+            }
+        }
+        self.max_micros as f64 / 1000.0
+    }
+}
+
+fn latency_bucket(micros: u64) -> usize {
+    if micros as f64 <= LATENCY_BASE_MICROS {
+        return 0;
+    }
+    let bucket = ((micros as f64 / LATENCY_BASE_MICROS).ln() / LATENCY_GROWTH.ln()).ceil();
+    (bucket as usize).min(LATENCY_BUCKETS - 1)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RouteKey {
+    method: String,
+    route: String,
+    class: StatusClass,
+}
+
+/// A route's requests over all status classes
+#[derive(Default)]
+struct RouteTotals {
+    stats: Stats,
+    classes: BTreeMap<StatusClass, u64>,
+}
+
+/// Requests recorded during one `BUCKET_SECS` interval
+struct Bucket {
+    /// Interval number since the aggregator started
+    tick: u64,
+    routes: HashMap<RouteKey, Stats>,
+}
+
+/// In-process rolling window aggregator
+pub struct RequestMetrics {
+    started: Instant,
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl RequestMetrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Records a finished request, `route` should be the route template to keep the
+    /// number of routes bounded
+    pub fn record(&self, method: &str, route: &str, status: StatusCode, latency: Duration) {
+        self.record_at(self.started.elapsed(), method, route, status, latency);
+    }
+
+    /// Figures for every window in `WINDOWS_SECS`
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot_at(self.started.elapsed())
+    }
+
+    fn record_at(
+        &self,
+        elapsed: Duration,
+        method: &str,
+        route: &str,
+        status: StatusCode,
+        latency: Duration,
+    ) {
+        let tick = elapsed.as_secs() / BUCKET_SECS;
+        let key = RouteKey {
+            method: method.to_string(),
+            route: route.to_string(),
+            class: StatusClass::of(status),
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        // A request that raced past the start of a new bucket is counted in it
+        if buckets.back().is_none_or(|bucket| bucket.tick < tick) {
+            buckets.push_back(Bucket {
+                tick,
+                routes: HashMap::new(),
+            });
+        }
+        while buckets
+            .front()
+            .is_some_and(|bucket| bucket.tick + BUCKET_COUNT <= tick)
+        {
+            buckets.pop_front();
+        }
+
+        if let Some(bucket) = buckets.back_mut() {
+            bucket
+                .routes
+                .entry(key)
+                .or_default()
+                .record(latency.as_micros() as u64);
+        }
+    }
+
+    fn snapshot_at(&self, elapsed: Duration) -> MetricsSnapshot {
+        let tick = elapsed.as_secs() / BUCKET_SECS;
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Everything recorded within a window, per route and status class
+        let window_stats = |window_secs: u64| {
+            let first_tick = (tick + 1).saturating_sub(window_secs / BUCKET_SECS);
+            let mut stats: HashMap<&RouteKey, Stats> = HashMap::new();
+            for bucket in buckets.iter().filter(|bucket| bucket.tick >= first_tick) {
+                for (key, route_stats) in &bucket.routes {
+                    stats.entry(key).or_default().merge(route_stats);
+                }
+            }
+            stats
+        };
+
+        let windows = WINDOWS_SECS
+            .iter()
+            .map(|window_secs| {
+                let stats = window_stats(*window_secs);
+                let mut total = Stats::default();
+                let (mut successes, mut client_errors, mut server_errors) = (0, 0, 0);
+                for (key, route_stats) in &stats {
+                    total.merge(route_stats);
+                    if key.class.is_success() {
+                        successes += route_stats.count;
+                    } else if key.class.is_client_error() {
+                        client_errors += route_stats.count;
+                    } else if key.class.is_server_error() {
+                        server_errors += route_stats.count;
+                    }
+                }
+
+                // Right after startup the window isn't full yet
+                let covered_secs = (*window_secs as f64).min(elapsed.as_secs_f64()).max(1.0);
+                MetricsWindow {
+                    window_secs: *window_secs,
+                    total_requests: total.count,
+                    requests_per_second: total.count as f64 / covered_secs,
+                    success_rate: ratio(successes, total.count),
+                    client_error_rate: ratio(client_errors, total.count),
+                    error_rate: ratio(server_errors, total.count),
+                    avg_response_time_ms: total.avg_ms(),
+                    p50_ms: total.percentile_ms(0.50),
+                    p95_ms: total.percentile_ms(0.95),
+                    p99_ms: total.percentile_ms(0.99),
+                }
+            })
+            .collect();
+
+        let mut routes: HashMap<(&str, &str), RouteTotals> = HashMap::new();
+        for (key, route_stats) in window_stats(WINDOWS_SECS[1]) {
+            let totals = routes
+                .entry((key.method.as_str(), key.route.as_str()))
+                .or_default();
+            totals.stats.merge(&route_stats);
+            *totals.classes.entry(key.class).or_default() += route_stats.count;
+        }
+        let mut routes: Vec<RouteMetrics> = routes
+            .into_iter()
+            .map(
+                |((method, route), RouteTotals { stats, classes })| RouteMetrics {
+                    method: method.to_string(),
+                    route: route.to_string(),
+                    total_requests: stats.count,
+                    error_rate: ratio(
+                        classes
+                            .iter()
+                            .filter(|(class, _)| class.is_server_error())
+                            .map(|(_, count)| count)
+                            .sum(),
+                        stats.count,
+                    ),
+                    status_classes: classes
+                        .into_iter()
+                        .map(|(class, count)| (class.label(), count))
+                        .collect(),
+                    avg_response_time_ms: stats.avg_ms(),
+                    p95_ms: stats.percentile_ms(0.95),
+                },
+            )
+            .collect();
+        routes.sort_by(|a, b| {
+            b.total_requests
+                .cmp(&a.total_requests)
+                .then_with(|| a.route.cmp(&b.route))
+        });
+        routes.truncate(TOP_ROUTES);
+
+        MetricsSnapshot { windows, routes }
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+/// Global request metrics, fed by `request_logging_middleware`
+pub static REQUEST_METRICS: once_cell::sync::Lazy<RequestMetrics> =
+    once_cell::sync::Lazy::new(RequestMetrics::new);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_roll_and_report_percentiles() {
+        let metrics = RequestMetrics::new();
+        let at = |secs: u64| Duration::from_secs(secs);
+        let ms = Duration::from_millis;
+
+        // 100 requests 10 minutes ago, only the 15 minute window still sees them
+        for _ in 0..100 {
+            metrics.record_at(
+                at(0),
+                "GET",
+                "/api/v1/auth/profile",
+                StatusCode::OK,
+                ms(1000),
+            );
+        }
+        // 98 fast successes, a client error and a slow server error within the last minute
+        for _ in 0..98 {
+            metrics.record_at(
+                at(590),
+                "GET",
+                "/api/v1/auth/profile",
+                StatusCode::OK,
+                ms(10),
+            );
+        }
+        metrics.record_at(
+            at(595),
+            "POST",
+            "/api/v1/auth/login",
+            StatusCode::UNAUTHORIZED,
+            ms(10),
+        );
+        metrics.record_at(
+            at(599),
+            "GET",
+            "/api/v1/admin/users/{id}",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ms(3000),
+        );
+
+        let snapshot = metrics.snapshot_at(at(600));
+        let (minute, fifteen) = (&snapshot.windows[0], &snapshot.windows[2]);
+
+        assert_eq!(minute.total_requests, 100);
+        assert!((minute.requests_per_second - 100.0 / 60.0).abs() < 1e-9);
+        assert!((minute.success_rate - 0.98).abs() < 1e-9);
+        assert!((minute.client_error_rate - 0.01).abs() < 1e-9);
+        assert!((minute.error_rate - 0.01).abs() < 1e-9);
+        assert!(minute.p50_ms >= 10.0 && minute.p50_ms <= 12.0);
+        assert!(minute.p99_ms >= 10.0 && minute.p99_ms <= 12.0);
+        assert_eq!(snapshot.windows[1].total_requests, 100);
+
+        assert_eq!(fifteen.total_requests, 200);
+        assert!(fifteen.p95_ms >= 1000.0 && fifteen.p95_ms <= 1200.0);
+
+        assert_eq!(snapshot.routes[0].route, "/api/v1/auth/profile");
+        assert_eq!(snapshot.routes[0].total_requests, 98);
+        let failing = snapshot
+            .routes
+            .iter()
+            .find(|route| route.route == "/api/v1/admin/users/{id}")
+            .unwrap();
+        assert_eq!(failing.error_rate, 1.0);
+        assert_eq!(failing.p95_ms, 3000.0);
+        assert_eq!(failing.status_classes.get("5xx"), Some(&1));
+
+        // Old buckets are dropped once they leave the longest window
+        metrics.record_at(at(1000), "GET", "/", StatusCode::OK, ms(1));
+        assert_eq!(metrics.buckets.lock().unwrap().len(), 3);
+    }
+}
@@ -2,9 +2,15 @@ use apalis::prelude::*;
 use apalis_cron::{CronStream, Schedule};
 use apalis_sql::sqlite::SqliteStorage;
 use chrono::{DateTime, Utc};
-use sea_orm::sqlx::SqlitePool;
+use sea_orm::{DatabaseConnection, sqlx::SqlitePool};
 use serde::{Deserialize, Serialize};
-use std::{io::Error, str::FromStr};
+use std::{env, io::Error, str::FromStr};
+
+use crate::control::services::{
+    account_service::AccountService, login_throttle_service::LoginThrottleService,
+    permission_service::PermissionService,
+};
+use crate::infrastructure::rate_limit::RateLimiter;
 
 /// Reminder structure for scheduled tasks
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +22,46 @@ impl From<DateTime<Utc>> for Reminder {
     }
 }
 
+/// Scheduled purge of expired per-user permission grants
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionPurge(DateTime<Utc>);
+
+impl From<DateTime<Utc>> for PermissionPurge {
+    fn from(t: DateTime<Utc>) -> Self {
+        PermissionPurge(t)
+    }
+}
+
+/// Scheduled deletion of accounts whose grace period has ended
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AccountPurge(DateTime<Utc>);
+
+impl From<DateTime<Utc>> for AccountPurge {
+    fn from(t: DateTime<Utc>) -> Self {
+        AccountPurge(t)
+    }
+}
+
+/// Scheduled deletion of database rate limit buckets that have filled up again
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitPurge(DateTime<Utc>);
+
+impl From<DateTime<Utc>> for RateLimitPurge {
+    fn from(t: DateTime<Utc>) -> Self {
+        RateLimitPurge(t)
+    }
+}
+
+/// Scheduled deletion of login throttle keys that no longer count
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct LoginThrottlePurge(DateTime<Utc>);
+
+impl From<DateTime<Utc>> for LoginThrottlePurge {
+    fn from(t: DateTime<Utc>) -> Self {
+        LoginThrottlePurge(t)
+    }
+}
+
 /// Task scheduler manager
 pub struct SchedulerManager;
 
@@ -26,8 +72,68 @@ impl SchedulerManager {
         Ok(())
     }
 
+    /// Deletes expired permission grants and denies, they already stopped applying when they expired
+    pub async fn handle_permission_purge(
+        _job: PermissionPurge,
+        db: Data<DatabaseConnection>,
+    ) -> Result<(), Error> {
+        let purged = PermissionService::purge_expired_overrides(&db)
+            .await
+            .map_err(|e| Error::other(e.message))?;
+        if purged > 0 {
+            tracing::info!("Purged {} expired user permission grants", purged);
+        }
+        Ok(())
+    }
+
+    /// Deletes accounts that were scheduled for deletion and not reclaimed in time
+    pub async fn handle_account_purge(
+        _job: AccountPurge,
+        db: Data<DatabaseConnection>,
+    ) -> Result<(), Error> {
+        let deleted = AccountService::purge_deleted_accounts(&db)
+            .await
+            .map_err(|e| Error::other(e.message))?;
+        if deleted > 0 {
+            tracing::info!("Deleted {} accounts after their grace period", deleted);
+        }
+        Ok(())
+    }
+
+    /// Deletes rate limit buckets idle for a refill period, RATE_LIMIT_STORE=database would
+    /// otherwise keep a row for every client ever seen
+    pub async fn handle_rate_limit_purge(
+        _job: RateLimitPurge,
+        db: Data<DatabaseConnection>,
+    ) -> Result<(), Error> {
+        let purged = RateLimiter::purge_idle_buckets(&db)
+            .await
+            .map_err(|e| Error::other(e.message))?;
+        if purged > 0 {
+            tracing::info!("Purged {} idle rate limit buckets", purged);
+        }
+        Ok(())
+    }
+
+    /// Deletes login throttle keys whose failures and lockout have expired
+    pub async fn handle_login_throttle_purge(
+        _job: LoginThrottlePurge,
+        db: Data<DatabaseConnection>,
+    ) -> Result<(), Error> {
+        let purged = LoginThrottleService::purge_expired(&db)
+            .await
+            .map_err(|e| Error::other(e.message))?;
+        if purged > 0 {
+            tracing::info!("Purged {} expired login throttles", purged);
+        }
+        Ok(())
+    }
+
     /// Creates and runs the task scheduler
-    pub async fn run_scheduler(database_url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn run_scheduler(
+        database_url: &str,
+        db: DatabaseConnection,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         // Create DB pool for cron
         let cron_pool = SqlitePool::connect(database_url).await.unwrap();
         let schedule = Schedule::from_str("0 */1 * * * *").unwrap(); // every minute
@@ -41,7 +147,64 @@ impl SchedulerManager {
             .backend(cron_backend)
             .build_fn(Self::handle_tick);
 
-        Monitor::new().register(worker).run().await.unwrap();
+        // Every 15 minutes unless PERMISSION_PURGE_SCHEDULE says otherwise
+        let purge_schedule =
+            env::var("PERMISSION_PURGE_SCHEDULE").unwrap_or_else(|_| "0 */15 * * * *".to_string());
+        let purge_schedule = Schedule::from_str(&purge_schedule)?;
+        let purge_storage = SqliteStorage::new(SqlitePool::connect(database_url).await?);
+        let purge_backend = CronStream::new(purge_schedule).pipe_to_storage(purge_storage);
+
+        let purge_worker = WorkerBuilder::new("permission-purge")
+            .data(db.clone())
+            .backend(purge_backend)
+            .build_fn(Self::handle_permission_purge);
+
+        // Hourly unless ACCOUNT_PURGE_SCHEDULE says otherwise
+        let account_schedule =
+            env::var("ACCOUNT_PURGE_SCHEDULE").unwrap_or_else(|_| "0 0 * * * *".to_string());
+        let account_schedule = Schedule::from_str(&account_schedule)?;
+        let account_storage = SqliteStorage::new(SqlitePool::connect(database_url).await?);
+        let account_backend = CronStream::new(account_schedule).pipe_to_storage(account_storage);
+
+        let account_worker = WorkerBuilder::new("account-purge")
+            .data(db.clone())
+            .backend(account_backend)
+            .build_fn(Self::handle_account_purge);
+
+        // Every 10 minutes unless RATE_LIMIT_PURGE_SCHEDULE says otherwise
+        let rate_limit_schedule =
+            env::var("RATE_LIMIT_PURGE_SCHEDULE").unwrap_or_else(|_| "0 */10 * * * *".to_string());
+        let rate_limit_schedule = Schedule::from_str(&rate_limit_schedule)?;
+        let rate_limit_storage = SqliteStorage::new(SqlitePool::connect(database_url).await?);
+        let rate_limit_backend =
+            CronStream::new(rate_limit_schedule).pipe_to_storage(rate_limit_storage);
+
+        let rate_limit_worker = WorkerBuilder::new("rate-limit-purge")
+            .data(db.clone())
+            .backend(rate_limit_backend)
+            .build_fn(Self::handle_rate_limit_purge);
+
+        // Every 15 minutes unless LOGIN_THROTTLE_PURGE_SCHEDULE says otherwise
+        let throttle_schedule = env::var("LOGIN_THROTTLE_PURGE_SCHEDULE")
+            .unwrap_or_else(|_| "0 */15 * * * *".to_string());
+        let throttle_schedule = Schedule::from_str(&throttle_schedule)?;
+        let throttle_storage = SqliteStorage::new(SqlitePool::connect(database_url).await?);
+        let throttle_backend = CronStream::new(throttle_schedule).pipe_to_storage(throttle_storage);
+
+        let throttle_worker = WorkerBuilder::new("login-throttle-purge")
+            .data(db)
+            .backend(throttle_backend)
+            .build_fn(Self::handle_login_throttle_purge);
+
+        Monitor::new()
+            .register(worker)
+            .register(purge_worker)
+            .register(account_worker)
+            .register(rate_limit_worker)
+            .register(throttle_worker)
+            .run()
+            .await
+            .unwrap();
         Ok(())
     }
 }
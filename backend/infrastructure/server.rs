@@ -8,13 +8,13 @@ use std::{
 use tokio::net::TcpListener;
 use tower_http::services::ServeDir;
 use utoipa::OpenApi;
-use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::{router::OpenApiRouter, routes};
 use utoipa_rapidoc::RapiDoc;
 use utoipa_redoc::{Redoc, Servable};
 use utoipa_scalar::{Scalar, Servable as ScalarServable};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::bridge::middleware::logging::request_logging_middleware;
+use crate::bridge::middleware::{csrf::csrf_middleware, logging::request_logging_middleware};
 use crate::bridge::routes::admin::admin_router;
 use crate::bridge::routes::auth::auth_router;
 use crate::infrastructure::cors::CorsManager;
@@ -37,6 +37,7 @@ impl ServerManager {
         let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
             .nest("/api/v1/auth", auth_router(db.clone()))
             .nest("/api/v1/admin", admin_router(db.clone()))
+            .routes(routes!(crate::bridge::handlers::auth::jwks_handler))
             .split_for_parts();
 
         // Create WebSocket router with database state
@@ -54,6 +55,7 @@ impl ServerManager {
             .merge(Scalar::with_url("/scalar", api))
             .route("/", get(Self::root_handler))
             .merge(websocket_router)
+            .route_layer(middleware::from_fn(csrf_middleware))
             .route_layer(middleware::from_fn_with_state(
                 db.clone(),
                 request_logging_middleware,
@@ -97,8 +99,11 @@ impl ServerManager {
             address.port()
         );
 
-        axum::serve(listener, router.into_make_service())
-            .await
-            .map_err(|e| Error::new(std::io::ErrorKind::Interrupted, e))
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .map_err(|e| Error::new(std::io::ErrorKind::Interrupted, e))
     }
 }
@@ -0,0 +1,220 @@
+//! Cookie based sessions
+//!
+//! With SESSION_COOKIES=true, logins also set the access and refresh tokens as HttpOnly cookies,
+//! so browsers don't have to keep them in storage scripts can read. Requests authenticated by
+//! cookie have to repeat the CSRF cookie in the X-CSRF-Token header (double submit).
+
+use axum::http::{HeaderMap, HeaderValue, header};
+use std::env;
+
+use crate::control::services::{
+    auth_service::AuthService, session_service::SessionService, token_service::TokenService,
+};
+
+/// Cookie holding the access token
+pub const SESSION_COOKIE: &str = "rext_session";
+
+/// Cookie holding the refresh token, only sent to the auth endpoints
+pub const REFRESH_COOKIE: &str = "rext_refresh";
+
+/// Cookie holding the CSRF token, readable by the frontend
+pub const CSRF_COOKIE: &str = "rext_csrf";
+
+/// Header the frontend copies the CSRF cookie into
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Path the refresh cookie is scoped to
+const REFRESH_COOKIE_PATH: &str = "/api/v1/auth";
+
+/// Builds and reads the session cookies
+#[derive(Debug, Clone)]
+pub struct SessionCookieManager {
+    /// Whether logins set session cookies at all
+    enabled: bool,
+    /// SameSite attribute, "Strict", "Lax" or "None"
+    same_site: &'static str,
+    /// Whether cookies are only sent over HTTPS
+    secure: bool,
+}
+
+impl SessionCookieManager {
+    /// Settings from SESSION_COOKIES (defaults to false), SESSION_COOKIE_SAMESITE (defaults to
+    /// Strict) and SESSION_COOKIE_SECURE, which can only be turned off for plain HTTP setups
+    pub fn from_env() -> Self {
+        let flag = |name: &str, default: bool| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<bool>().ok())
+                .unwrap_or(default)
+        };
+        Self::new(
+            flag("SESSION_COOKIES", false),
+            &env::var("SESSION_COOKIE_SAMESITE").unwrap_or_default(),
+            flag("SESSION_COOKIE_SECURE", true),
+        )
+    }
+
+    /// Settings given directly, an unknown SameSite mode means Strict
+    pub fn new(enabled: bool, same_site: &str, secure: bool) -> Self {
+        let same_site = match same_site.to_lowercase().as_str() {
+            "lax" => "Lax",
+            "none" => "None",
+            _ => "Strict",
+        };
+        Self {
+            enabled,
+            same_site,
+            secure,
+        }
+    }
+
+    /// Appends Set-Cookie headers for a new token pair and a fresh CSRF token
+    /// Does nothing unless session cookies are enabled
+    pub fn set_session(&self, headers: &mut HeaderMap, token: &str, refresh_token: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        let access_max_age = AuthService::access_token_lifetime().num_seconds();
+        let session_max_age = SessionService::session_lifetime().num_seconds();
+        let csrf_token = TokenService::generate_opaque_token();
+
+        for cookie in [
+            self.cookie(SESSION_COOKIE, token, "/", access_max_age, true),
+            self.cookie(
+                REFRESH_COOKIE,
+                refresh_token,
+                REFRESH_COOKIE_PATH,
+                session_max_age,
+                true,
+            ),
+            self.cookie(CSRF_COOKIE, &csrf_token, "/", session_max_age, false),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                headers.append(header::SET_COOKIE, value);
+            }
+        }
+    }
+
+    /// Appends Set-Cookie headers removing all session cookies
+    pub fn clear_session(&self, headers: &mut HeaderMap) {
+        if !self.enabled {
+            return;
+        }
+
+        for cookie in [
+            self.cookie(SESSION_COOKIE, "", "/", 0, true),
+            self.cookie(REFRESH_COOKIE, "", REFRESH_COOKIE_PATH, 0, true),
+            self.cookie(CSRF_COOKIE, "", "/", 0, false),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                headers.append(header::SET_COOKIE, value);
+            }
+        }
+    }
+
+    /// Reads a cookie from the request headers
+    /// Returns None when session cookies are disabled
+    pub fn read(&self, headers: &HeaderMap, name: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Whether the request would be authenticated by a session cookie rather than a header
+    pub fn authenticates_with_cookie(&self, headers: &HeaderMap) -> bool {
+        !headers.contains_key(header::AUTHORIZATION)
+            && !headers.contains_key("x-api-key")
+            && (self.read(headers, SESSION_COOKIE).is_some()
+                || self.read(headers, REFRESH_COOKIE).is_some())
+    }
+
+    /// Whether the X-CSRF-Token header matches the CSRF cookie
+    pub fn csrf_token_valid(&self, headers: &HeaderMap) -> bool {
+        let (Some(cookie), Some(header)) = (
+            self.read(headers, CSRF_COOKIE),
+            headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()),
+        ) else {
+            return false;
+        };
+
+        // Compare without short-circuiting so timing doesn't reveal the token
+        cookie.len() == header.len()
+            && cookie
+                .bytes()
+                .zip(header.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    /// Formats a Set-Cookie value
+    fn cookie(&self, name: &str, value: &str, path: &str, max_age: i64, http_only: bool) -> String {
+        let mut cookie = format!(
+            "{}={}; Path={}; Max-Age={}; SameSite={}",
+            name, value, path, max_age, self.same_site
+        );
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        // Browsers reject SameSite=None without Secure
+        if self.secure || self.same_site == "None" {
+            cookie.push_str("; Secure");
+        }
+        cookie
+    }
+}
+
+/// Session cookie settings from the environment
+pub static SESSION_COOKIES: once_cell::sync::Lazy<SessionCookieManager> =
+    once_cell::sync::Lazy::new(SessionCookieManager::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csrf_header_has_to_match_the_cookie() {
+        let cookies = SessionCookieManager::new(true, "strict", true);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; rext_session=abc; rext_csrf=token-1"),
+        );
+        assert_eq!(
+            cookies.read(&headers, SESSION_COOKIE).as_deref(),
+            Some("abc")
+        );
+        assert!(cookies.authenticates_with_cookie(&headers));
+        assert!(!cookies.csrf_token_valid(&headers));
+
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("token-2"));
+        assert!(!cookies.csrf_token_valid(&headers));
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("token-1"));
+        assert!(cookies.csrf_token_valid(&headers));
+
+        // With session cookies off, cookies authenticate nothing
+        let disabled = SessionCookieManager::new(false, "strict", true);
+        headers.remove(CSRF_HEADER);
+        assert_eq!(disabled.read(&headers, SESSION_COOKIE), None);
+        assert!(!disabled.authenticates_with_cookie(&headers));
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("token-1"));
+
+        // Bearer tokens can't be sent by another site, so they don't need the CSRF token
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc"),
+        );
+        assert!(!cookies.authenticates_with_cookie(&headers));
+    }
+}
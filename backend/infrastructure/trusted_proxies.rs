@@ -0,0 +1,164 @@
+//! Client addresses behind reverse proxies
+//!
+//! The peer address of the connection is the client unless it belongs to a proxy listed in
+//! TRUSTED_PROXIES. Only then is X-Forwarded-For read, from the right, skipping the hops added
+//! by trusted proxies; everything left of the first untrusted hop was written by the client.
+
+use std::{env, net::IpAddr};
+
+/// An address range written as `10.0.0.0/8`, a bare address covers only itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    fn parse(value: &str) -> Option<Self> {
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value, None),
+        };
+        let address = address.trim().parse::<IpAddr>().ok()?.to_canonical();
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|p| *p <= max_prefix)?,
+            None => max_prefix,
+        };
+        Some(Self { address, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Proxies whose X-Forwarded-For entries are believed
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<Network>,
+}
+
+impl TrustedProxies {
+    /// Comma separated addresses and CIDR ranges from TRUSTED_PROXIES, none if unset.
+    /// Invalid entries are logged and skipped
+    pub fn from_env() -> Self {
+        Self::parse(&env::var("TRUSTED_PROXIES").unwrap_or_default())
+    }
+
+    fn parse(value: &str) -> Self {
+        let networks = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let network = Network::parse(entry);
+                if network.is_none() {
+                    tracing::warn!("Ignoring invalid TRUSTED_PROXIES entry {:?}", entry);
+                }
+                network
+            })
+            .collect();
+        Self { networks }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// The client behind `peer`, given the request's X-Forwarded-For values in header order
+    pub fn client_ip<'a>(
+        &self,
+        peer: Option<IpAddr>,
+        forwarded_for: impl IntoIterator<Item = &'a str>,
+    ) -> Option<IpAddr> {
+        let mut client = peer?;
+        if !self.contains(client) {
+            return Some(client);
+        }
+
+        let hops: Vec<&str> = forwarded_for
+            .into_iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        for hop in hops.into_iter().rev() {
+            // Garbage means the chain can't be followed further, the last proxy is the best we know
+            let Ok(hop) = hop.parse::<IpAddr>() else {
+                break;
+            };
+            client = hop.to_canonical();
+            if !self.contains(client) {
+                break;
+            }
+        }
+        Some(client)
+    }
+}
+
+/// Proxies configured through TRUSTED_PROXIES
+pub static TRUSTED_PROXIES: once_cell::sync::Lazy<TrustedProxies> =
+    once_cell::sync::Lazy::new(TrustedProxies::from_env);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn forwarded_for_is_only_read_from_trusted_proxies() {
+        let none = TrustedProxies::default();
+        let proxies = TrustedProxies::parse("10.0.0.0/8, ::1, not-an-ip");
+        assert_eq!(proxies.networks.len(), 2);
+
+        // Without trusted proxies a forged header changes nothing
+        assert_eq!(
+            none.client_ip(ip("203.0.113.7"), ["1.2.3.4"]),
+            ip("203.0.113.7")
+        );
+        // Neither does it from a peer that isn't a trusted proxy
+        assert_eq!(
+            proxies.client_ip(ip("203.0.113.7"), ["1.2.3.4"]),
+            ip("203.0.113.7")
+        );
+
+        // The right-most untrusted hop wins, whatever the client put in front of it
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.2"), ["1.2.3.4, 198.51.100.9", "10.0.0.1"]),
+            ip("198.51.100.9")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("::1"), ["198.51.100.9"]),
+            ip("198.51.100.9")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("::ffff:10.0.0.2"), ["198.51.100.9"]),
+            ip("198.51.100.9")
+        );
+
+        // Without a usable header the proxy is all that is known
+        assert_eq!(proxies.client_ip(ip("10.0.0.2"), []), ip("10.0.0.2"));
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.2"), ["1.2.3.4, junk, 10.0.0.1"]),
+            ip("10.0.0.1")
+        );
+        assert_eq!(proxies.client_ip(None, ["1.2.3.4"]), None);
+    }
+}
@@ -1,7 +1,23 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::{RwLock, broadcast};
+use tokio::sync::{Mutex, Notify, RwLock, broadcast};
+use uuid::Uuid;
+
+use crate::infrastructure::{
+    app_error::AppError,
+    request_metrics::{MetricsSnapshot, MetricsWindow, REQUEST_METRICS, RouteMetrics},
+};
+
+/// Messages a connection can queue before it starts missing them
+const CONNECTION_BUFFER_SIZE: usize = 1000;
+
+/// Published messages kept for replay unless WEBSOCKET_REPLAY_BUFFER_SIZE says otherwise
+const DEFAULT_REPLAY_BUFFER_SIZE: usize = 1000;
 
 /// WebSocket message types for real-time monitoring
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,22 +37,34 @@ pub enum WebSocketMessage {
         user_agent: Option<String>,
         error_message: Option<String>,
     },
-    /// System log message
+    /// System log message, forwarded from tracing events
     #[serde(rename = "SystemLog")]
     SystemLog {
         level: String,
         message: String,
         timestamp: String,
         target: String,
+        /// Structured fields of the event
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        fields: BTreeMap<String, serde_json::Value>,
+        /// Spans the event happened in, outermost first
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        spans: Vec<LogSpan>,
     },
     /// Performance metrics update
     #[serde(rename = "PerformanceMetrics")]
     PerformanceMetrics {
+        /// Requests of the last minute, as are the rates and the average below
         total_requests: u64,
         success_rate: f64,
         avg_response_time: f64,
+        /// Share of 5xx responses
         error_rate: f64,
         active_connections: u32,
+        /// Throughput, latency percentiles and error rates of the 1, 5 and 15 minute windows
+        windows: Vec<MetricsWindow>,
+        /// Busiest routes of the last 5 minutes
+        routes: Vec<RouteMetrics>,
     },
     /// Connection status
     #[serde(rename = "ConnectionStatus")]
@@ -50,47 +78,469 @@ pub enum WebSocketMessage {
     Ping,
     #[serde(rename = "Pong")]
     Pong,
+    /// Confirms a Subscribe command with the filter in effect
+    #[serde(rename = "Subscribed")]
+    Subscribed {
+        topic: Topic,
+        filter: SubscriptionFilter,
+    },
+    /// Confirms an Unsubscribe command
+    #[serde(rename = "Unsubscribed")]
+    Unsubscribed { topic: Topic },
+    /// A command was rejected
+    #[serde(rename = "Error")]
+    Error { message: String },
+    /// Messages of the topic with sequence numbers from `from` to `to`, inclusive, were dropped
+    /// from the replay buffer and can't be sent. Some of them may not have matched the filter
+    #[serde(rename = "Gap")]
+    Gap { topic: Topic, from: u64, to: u64 },
+}
+
+/// A message as it goes out, with its sequence number next to the `type` tag
+///
+/// Published messages are numbered one after the other, replies to a single connection carry
+/// the number of the last published message. A client resumes after the highest number it saw
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedMessage {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub message: WebSocketMessage,
+}
+
+impl WebSocketMessage {
+    /// Topic the message is published on, None for replies meant for a single connection
+    pub fn topic(&self) -> Option<Topic> {
+        match self {
+            WebSocketMessage::AuditLog { .. } => Some(Topic::AuditLog),
+            WebSocketMessage::SystemLog { .. } => Some(Topic::SystemLog),
+            WebSocketMessage::PerformanceMetrics { .. } => Some(Topic::PerformanceMetrics),
+            _ => None,
+        }
+    }
+}
+
+/// A span a SystemLog event happened in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSpan {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// Streams a connection can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Topic {
+    AuditLog,
+    SystemLog,
+    PerformanceMetrics,
+}
+
+/// Narrows a subscription down on the server, unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionFilter {
+    /// AuditLog: only requests whose path starts with this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_prefix: Option<String>,
+    /// AuditLog: lowest status code, inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_status: Option<i32>,
+    /// AuditLog: highest status code, inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_status: Option<i32>,
+    /// AuditLog: only requests made by this user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// SystemLog: least severe level to receive, one of trace, debug, info, warn or error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_level: Option<String>,
+}
+
+impl SubscriptionFilter {
+    /// Checks that the filter makes sense for the topic
+    pub fn validate(&self, topic: Topic) -> Result<(), AppError> {
+        let invalid = |message: String| {
+            Err(AppError {
+                message,
+                status_code: StatusCode::BAD_REQUEST,
+            })
+        };
+
+        let audit_fields = self.path_prefix.is_some()
+            || self.min_status.is_some()
+            || self.max_status.is_some()
+            || self.user_id.is_some();
+        if audit_fields && topic != Topic::AuditLog {
+            return invalid(format!(
+                "path_prefix, min_status, max_status and user_id only apply to AuditLog, not {:?}",
+                topic
+            ));
+        }
+        if self.min_level.is_some() && topic != Topic::SystemLog {
+            return invalid(format!(
+                "min_level only applies to SystemLog, not {:?}",
+                topic
+            ));
+        }
+
+        for status in [self.min_status, self.max_status].into_iter().flatten() {
+            if !(100..=599).contains(&status) {
+                return invalid(format!("Invalid status code {}", status));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_status, self.max_status)
+            && min > max
+        {
+            return invalid("min_status can't be above max_status".to_string());
+        }
+        if let Some(user_id) = &self.user_id
+            && Uuid::parse_str(user_id).is_err()
+        {
+            return invalid("Invalid user ID format".to_string());
+        }
+        if let Some(level) = &self.min_level
+            && tracing::Level::from_str(level).is_err()
+        {
+            return invalid(format!("Invalid log level {}", level));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a message published on the filtered topic passes
+    pub fn matches(&self, message: &WebSocketMessage) -> bool {
+        match message {
+            WebSocketMessage::AuditLog {
+                path,
+                status_code,
+                user_id,
+                ..
+            } => {
+                self.path_prefix
+                    .as_ref()
+                    .is_none_or(|prefix| path.starts_with(prefix.as_str()))
+                    && self
+                        .min_status
+                        .is_none_or(|min| status_code.is_some_and(|status| status >= min))
+                    && self
+                        .max_status
+                        .is_none_or(|max| status_code.is_some_and(|status| status <= max))
+                    && self.user_id.as_ref().is_none_or(|wanted| {
+                        user_id
+                            .as_ref()
+                            .is_some_and(|user_id| user_id.eq_ignore_ascii_case(wanted))
+                    })
+            }
+            WebSocketMessage::SystemLog { level, .. } => {
+                let min_level = self
+                    .min_level
+                    .as_deref()
+                    .and_then(|level| tracing::Level::from_str(level).ok());
+                match (min_level, tracing::Level::from_str(level)) {
+                    // More severe levels compare as smaller
+                    (Some(min_level), Ok(level)) => level <= min_level,
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Commands clients send over the WebSocket
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum WebSocketCommand {
+    /// Authenticates a connection opened without a token, has to be the first message
+    #[serde(rename = "Auth")]
+    Auth { token: String },
+    /// Starts receiving a topic, or replaces the filter of an existing subscription
+    #[serde(rename = "Subscribe")]
+    Subscribe {
+        topic: Topic,
+        #[serde(default)]
+        filter: SubscriptionFilter,
+        /// Highest sequence number the client saw before reconnecting, the buffered messages
+        /// after it are replayed
+        #[serde(default)]
+        since: Option<u64>,
+    },
+    /// Stops receiving a topic
+    #[serde(rename = "Unsubscribe")]
+    Unsubscribe { topic: Topic },
+    #[serde(rename = "Ping")]
+    Ping,
+}
+
+/// Admin session a WebSocket connection was authenticated with
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionOwner {
+    pub user_id: Uuid,
+    /// JWT session ID, the connection is closed when the session is invalidated
+    pub session_id: Uuid,
+    /// When the session expires, the connection is closed then too
+    pub expires_at: DateTime<Utc>,
+}
+
+/// An open connection and what's needed to reach or close it
+struct Connection {
+    tx: broadcast::Sender<SequencedMessage>,
+    owner: ConnectionOwner,
+    closed: Arc<Notify>,
+    /// Topics the connection receives
+    subscriptions: HashMap<Topic, Subscription>,
+}
+
+/// A topic a connection receives
+struct Subscription {
+    filter: SubscriptionFilter,
+    /// Last sequence number published before the subscription started, nothing older is replayed
+    /// to make up for a lagging connection
+    since: u64,
+}
+
+/// Sequence numbers and the most recent published messages
+struct History {
+    /// Sequence number of the last published message
+    last_seq: u64,
+    messages: VecDeque<SequencedMessage>,
+    capacity: usize,
+    /// Sequence number of the newest message of each topic that was dropped from the buffer
+    evicted: HashMap<Topic, u64>,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            last_seq: 0,
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            evicted: HashMap::new(),
+        }
+    }
+
+    /// Numbers a published message and keeps it, dropping the oldest one when full
+    fn publish(&mut self, message: WebSocketMessage) -> SequencedMessage {
+        self.last_seq += 1;
+        let message = SequencedMessage {
+            seq: self.last_seq,
+            message,
+        };
+
+        if self.messages.len() == self.capacity
+            && let Some(oldest) = self.messages.pop_front()
+            && let Some(topic) = oldest.message.topic()
+        {
+            self.evicted.insert(topic, oldest.seq);
+        }
+        if self.capacity > 0 {
+            self.messages.push_back(message.clone());
+        }
+        message
+    }
+
+    /// Numbers a reply with the current position of the stream
+    fn reply(&self, message: WebSocketMessage) -> SequencedMessage {
+        SequencedMessage {
+            seq: self.last_seq,
+            message,
+        }
+    }
+
+    /// Buffered messages of the topics published after the given sequence numbers and passing
+    /// their filters, preceded by a Gap for each topic the buffer no longer covers
+    fn replay(&self, wanted: &[(Topic, &SubscriptionFilter, u64)]) -> Vec<SequencedMessage> {
+        let mut replay: Vec<SequencedMessage> = wanted
+            .iter()
+            .filter_map(|(topic, _, after)| {
+                let evicted = *self.evicted.get(topic)?;
+                (evicted > *after).then(|| {
+                    self.reply(WebSocketMessage::Gap {
+                        topic: *topic,
+                        from: after + 1,
+                        to: evicted,
+                    })
+                })
+            })
+            .collect();
+
+        replay.extend(
+            self.messages
+                .iter()
+                .filter(|message| {
+                    wanted.iter().any(|(topic, filter, after)| {
+                        message.seq > *after
+                            && message.message.topic() == Some(*topic)
+                            && filter.matches(&message.message)
+                    })
+                })
+                .cloned(),
+        );
+        replay
+    }
 }
 
 /// WebSocket connection manager
 pub struct WebSocketManager {
-    /// Broadcast channel for sending messages to all connected clients
-    tx: broadcast::Sender<WebSocketMessage>,
     /// Active connections with their IDs
-    connections: Arc<RwLock<HashMap<String, broadcast::Sender<WebSocketMessage>>>>,
+    connections: Arc<RwLock<HashMap<String, Connection>>>,
+    /// Locked before `connections` by everything that sends, so messages are numbered in the
+    /// order connections receive them
+    history: Mutex<History>,
 }
 
 impl WebSocketManager {
-    /// Create a new WebSocket manager
+    /// Create a new WebSocket manager, WEBSOCKET_REPLAY_BUFFER_SIZE sets how many published
+    /// messages are kept for replay
     pub fn new() -> Self {
-        let (tx, _) = broadcast::channel(1000); // Buffer size of 1000 messages
+        let replay_capacity = env::var("WEBSOCKET_REPLAY_BUFFER_SIZE")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_REPLAY_BUFFER_SIZE);
+        Self::with_replay_capacity(replay_capacity)
+    }
+
+    /// Create a manager keeping the given number of published messages for replay
+    pub fn with_replay_capacity(replay_capacity: usize) -> Self {
         Self {
-            tx,
             connections: Arc::new(RwLock::new(HashMap::new())),
+            history: Mutex::new(History::new(replay_capacity)),
         }
     }
 
-    /// Subscribe to the broadcast channel
-    pub fn subscribe(&self) -> broadcast::Receiver<WebSocketMessage> {
-        self.tx.subscribe()
-    }
-
-    /// Broadcast a message to all connected clients
+    /// Publishes a message to the connections subscribed to its topic whose filter it passes
     pub async fn broadcast(&self, message: WebSocketMessage) {
-        if let Err(e) = self.tx.send(message) {
-            tracing::warn!("Failed to broadcast message: {}", e);
+        let Some(topic) = message.topic() else {
+            tracing::warn!("Not broadcasting a message without a topic: {:?}", message);
+            return;
+        };
+
+        let mut history = self.history.lock().await;
+        let message = history.publish(message);
+        for connection in self.connections.read().await.values() {
+            if connection
+                .subscriptions
+                .get(&topic)
+                .is_some_and(|subscription| subscription.filter.matches(&message.message))
+            {
+                // Fails only when the connection is going away
+                let _ = connection.tx.send(message.clone());
+            }
         }
     }
 
-    /// Add a new connection
-    #[allow(dead_code)]
+    /// Numbers a message sent outside of a connection's channel with the current position
+    pub async fn sequence(&self, message: WebSocketMessage) -> SequencedMessage {
+        self.history.lock().await.reply(message)
+    }
+
+    /// Add a new connection without subscriptions, the returned `Notify` fires when its session ends
     pub async fn add_connection(
         &self,
         connection_id: String,
-    ) -> broadcast::Receiver<WebSocketMessage> {
-        let (tx, _rx) = broadcast::channel(100);
-        self.connections.write().await.insert(connection_id, tx);
-        self.subscribe()
+        owner: ConnectionOwner,
+    ) -> (broadcast::Receiver<SequencedMessage>, Arc<Notify>) {
+        let (tx, rx) = broadcast::channel(CONNECTION_BUFFER_SIZE);
+        let closed = Arc::new(Notify::new());
+        self.connections.write().await.insert(
+            connection_id,
+            Connection {
+                tx,
+                owner,
+                closed: closed.clone(),
+                subscriptions: HashMap::new(),
+            },
+        );
+        (rx, closed)
+    }
+
+    /// Subscribes a connection to a topic, replacing the filter if it already is, and confirms
+    /// with a Subscribed message. With `since` the buffered messages published after it follow,
+    /// before any new one. A `since` ahead of the stream, as after a server restart, replays the
+    /// whole buffer. Permissions have to be checked by the caller
+    pub async fn subscribe(
+        &self,
+        connection_id: &str,
+        topic: Topic,
+        filter: SubscriptionFilter,
+        since: Option<u64>,
+    ) -> bool {
+        let history = self.history.lock().await;
+        let mut connections = self.connections.write().await;
+        let Some(connection) = connections.get_mut(connection_id) else {
+            return false;
+        };
+
+        let _ = connection
+            .tx
+            .send(history.reply(WebSocketMessage::Subscribed {
+                topic,
+                filter: filter.clone(),
+            }));
+        if let Some(since) = since {
+            let since = if since > history.last_seq { 0 } else { since };
+            for message in history.replay(&[(topic, &filter, since)]) {
+                let _ = connection.tx.send(message);
+            }
+        }
+
+        connection.subscriptions.insert(
+            topic,
+            Subscription {
+                filter,
+                since: history.last_seq,
+            },
+        );
+        true
+    }
+
+    /// Buffered messages a lagging connection missed after `after`, for its current subscriptions
+    pub async fn replay(&self, connection_id: &str, after: u64) -> Vec<SequencedMessage> {
+        let history = self.history.lock().await;
+        let connections = self.connections.read().await;
+        let Some(connection) = connections.get(connection_id) else {
+            return Vec::new();
+        };
+
+        let wanted: Vec<_> = connection
+            .subscriptions
+            .iter()
+            .map(|(topic, subscription)| {
+                (*topic, &subscription.filter, after.max(subscription.since))
+            })
+            .collect();
+        history.replay(&wanted)
+    }
+
+    /// Unsubscribes a connection from a topic
+    pub async fn unsubscribe(&self, connection_id: &str, topic: Topic) -> bool {
+        match self.connections.write().await.get_mut(connection_id) {
+            Some(connection) => connection.subscriptions.remove(&topic).is_some(),
+            None => false,
+        }
+    }
+
+    /// Closes the connections opened with a session
+    pub async fn close_session(&self, session_id: Uuid) {
+        self.close_where(|owner| owner.session_id == session_id)
+            .await;
+    }
+
+    /// Closes a user's connections, except those opened with `keep_session_id`
+    pub async fn close_user_sessions(&self, user_id: Uuid, keep_session_id: Option<Uuid>) {
+        self.close_where(|owner| {
+            owner.user_id == user_id && Some(owner.session_id) != keep_session_id
+        })
+        .await;
+    }
+
+    async fn close_where(&self, matches: impl Fn(&ConnectionOwner) -> bool) {
+        for connection in self.connections.read().await.values() {
+            if matches(&connection.owner) {
+                // notify_one keeps the permit if the connection isn't waiting right now
+                connection.closed.notify_one();
+            }
+        }
     }
 
     /// Remove a connection
@@ -104,10 +554,10 @@ impl WebSocketManager {
     }
 
     /// Send a message to a specific connection
-    #[allow(dead_code)]
     pub async fn send_to_connection(&self, connection_id: &str, message: WebSocketMessage) -> bool {
-        if let Some(tx) = self.connections.read().await.get(connection_id) {
-            tx.send(message).is_ok()
+        let history = self.history.lock().await;
+        if let Some(connection) = self.connections.read().await.get(connection_id) {
+            connection.tx.send(history.reply(message)).is_ok()
         } else {
             false
         }
@@ -146,71 +596,211 @@ pub async fn broadcast_audit_log(
     WEBSOCKET_MANAGER.broadcast(message).await;
 }
 
-/// Helper function to broadcast system logs
-pub async fn broadcast_system_log(level: String, message: String, target: String) {
-    let message = WebSocketMessage::SystemLog {
-        level,
-        message,
-        timestamp: chrono::Utc::now().to_rfc3339(),
-        target,
-    };
-    WEBSOCKET_MANAGER.broadcast(message).await;
-}
-
 /// Helper function to broadcast performance metrics
-#[allow(dead_code)]
-pub async fn broadcast_performance_metrics(
-    total_requests: u64,
-    success_rate: f64,
-    avg_response_time: f64,
-    error_rate: f64,
-) {
+pub async fn broadcast_performance_metrics(snapshot: MetricsSnapshot) {
     let active_connections = WEBSOCKET_MANAGER.connection_count().await as u32;
+    let latest = snapshot.windows.first().cloned().unwrap_or_default();
     let message = WebSocketMessage::PerformanceMetrics {
-        total_requests,
-        success_rate,
-        avg_response_time,
-        error_rate,
+        total_requests: latest.total_requests,
+        success_rate: latest.success_rate,
+        avg_response_time: latest.avg_response_time_ms,
+        error_rate: latest.error_rate,
         active_connections,
+        windows: snapshot.windows,
+        routes: snapshot.routes,
     };
     WEBSOCKET_MANAGER.broadcast(message).await;
 }
 
 /// Start a background task that periodically broadcasts performance metrics
 pub async fn start_metrics_broadcaster() {
-    let manager = &WEBSOCKET_MANAGER;
-
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30)); // Every 30 seconds
 
         loop {
             interval.tick().await;
 
-            // Get connection count
-            let active_connections = manager.connection_count().await as u32;
+            broadcast_performance_metrics(REQUEST_METRICS.snapshot()).await;
 
-            // For now, we'll send basic metrics
-            // In a real implementation, you'd calculate these from audit logs
-            let message = WebSocketMessage::PerformanceMetrics {
-                total_requests: 0, // This will be calculated from audit logs
-                success_rate: 0.0,
-                avg_response_time: 0.0,
-                error_rate: 0.0,
+            let active_connections = WEBSOCKET_MANAGER.connection_count().await;
+            tracing::debug!(
+                target: "metrics_broadcaster",
                 active_connections,
-            };
-
-            manager.broadcast(message).await;
-
-            // Broadcast a heartbeat log
-            broadcast_system_log(
-                "debug".to_string(),
-                format!(
-                    "Metrics broadcast - Active connections: {}",
-                    active_connections
-                ),
-                "metrics_broadcaster".to_string(),
+                "Metrics broadcast"
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ending_a_session_closes_its_connections() {
+        let manager = WebSocketManager::new();
+        let user_id = Uuid::new_v4();
+        let owner = |session_id| ConnectionOwner {
+            user_id,
+            session_id,
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let (_, first_closed) = manager.add_connection("a".to_string(), owner(first)).await;
+        let (_, second_closed) = manager.add_connection("b".to_string(), owner(second)).await;
+        let closed = |notify: &Arc<Notify>| {
+            let notify = notify.clone();
+            async move {
+                tokio::time::timeout(std::time::Duration::from_millis(10), notify.notified())
+                    .await
+                    .is_ok()
+            }
+        };
+
+        manager.close_session(first).await;
+        assert!(closed(&first_closed).await);
+        assert!(!closed(&second_closed).await);
+
+        manager.close_user_sessions(user_id, Some(second)).await;
+        assert!(!closed(&second_closed).await);
+        manager.close_user_sessions(user_id, None).await;
+        assert!(closed(&second_closed).await);
+    }
+
+    #[tokio::test]
+    async fn resuming_replays_buffered_messages_or_reports_the_gap() {
+        let manager = WebSocketManager::with_replay_capacity(2);
+        let owner = ConnectionOwner {
+            user_id: Uuid::new_v4(),
+            session_id: Uuid::new_v4(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        let system_log = |message: &str| WebSocketMessage::SystemLog {
+            level: "info".to_string(),
+            message: message.to_string(),
+            timestamp: String::new(),
+            target: String::new(),
+            fields: BTreeMap::new(),
+            spans: Vec::new(),
+        };
+        let received = |rx: &mut broadcast::Receiver<SequencedMessage>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|message| match message.message {
+                    WebSocketMessage::SystemLog { message: text, .. } => {
+                        format!("{} {}", message.seq, text)
+                    }
+                    WebSocketMessage::Gap { from, to, .. } => {
+                        format!("{} gap {}-{}", message.seq, from, to)
+                    }
+                    other => format!("{} {:?}", message.seq, other.topic()),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (mut live, _) = manager.add_connection("live".to_string(), owner).await;
+        manager
+            .subscribe(
+                "live",
+                Topic::SystemLog,
+                SubscriptionFilter::default(),
+                None,
             )
             .await;
+        for message in ["one", "two", "three"] {
+            manager.broadcast(system_log(message)).await;
         }
-    });
+        assert_eq!(received(&mut live), ["0 None", "1 one", "2 two", "3 three"]);
+
+        // The client saw up to 2, three is still buffered
+        let (mut resumed, _) = manager.add_connection("resumed".to_string(), owner).await;
+        manager
+            .subscribe(
+                "resumed",
+                Topic::SystemLog,
+                SubscriptionFilter::default(),
+                Some(2),
+            )
+            .await;
+        assert_eq!(received(&mut resumed), ["3 None", "3 three"]);
+
+        // One was dropped from the buffer
+        let (mut late, _) = manager.add_connection("late".to_string(), owner).await;
+        manager
+            .subscribe(
+                "late",
+                Topic::SystemLog,
+                SubscriptionFilter::default(),
+                Some(0),
+            )
+            .await;
+        assert_eq!(
+            received(&mut late),
+            ["3 None", "3 gap 1-1", "2 two", "3 three"]
+        );
+
+        // Lagging connections only get what was published since they subscribed
+        assert_eq!(manager.replay("late", 0).await.len(), 0);
+        manager.broadcast(system_log("four")).await;
+        assert_eq!(manager.replay("live", 3).await.len(), 1);
+    }
+
+    #[test]
+    fn filters_are_validated_and_applied() {
+        let audit_log = |path: &str, status_code: i32| WebSocketMessage::AuditLog {
+            id: String::new(),
+            timestamp: String::new(),
+            method: "GET".to_string(),
+            path: path.to_string(),
+            status_code: Some(status_code),
+            response_time_ms: None,
+            user_id: Some("6F9619FF-8B86-D011-B42D-00C04FC964FF".to_string()),
+            ip_address: None,
+            user_agent: None,
+            error_message: None,
+        };
+        let system_log = |level: &str| WebSocketMessage::SystemLog {
+            level: level.to_string(),
+            message: String::new(),
+            timestamp: String::new(),
+            target: String::new(),
+            fields: BTreeMap::new(),
+            spans: Vec::new(),
+        };
+
+        let errors = SubscriptionFilter {
+            path_prefix: Some("/api/v1/admin".to_string()),
+            min_status: Some(400),
+            user_id: Some("6f9619ff-8b86-d011-b42d-00c04fc964ff".to_string()),
+            ..SubscriptionFilter::default()
+        };
+        assert!(errors.validate(Topic::AuditLog).is_ok());
+        assert!(errors.validate(Topic::SystemLog).is_err());
+        assert!(errors.matches(&audit_log("/api/v1/admin/users", 403)));
+        assert!(!errors.matches(&audit_log("/api/v1/admin/users", 200)));
+        assert!(!errors.matches(&audit_log("/api/v1/auth/login", 401)));
+
+        let warnings = SubscriptionFilter {
+            min_level: Some("warn".to_string()),
+            ..SubscriptionFilter::default()
+        };
+        assert!(warnings.validate(Topic::SystemLog).is_ok());
+        assert!(warnings.matches(&system_log("error")));
+        assert!(warnings.matches(&system_log("WARN")));
+        assert!(!warnings.matches(&system_log("info")));
+
+        let inverted = SubscriptionFilter {
+            min_status: Some(500),
+            max_status: Some(400),
+            ..SubscriptionFilter::default()
+        };
+        assert!(inverted.validate(Topic::AuditLog).is_err());
+        assert!(
+            SubscriptionFilter {
+                min_level: Some("loud".to_string()),
+                ..SubscriptionFilter::default()
+            }
+            .validate(Topic::SystemLog)
+            .is_err()
+        );
+    }
 }
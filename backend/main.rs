@@ -9,6 +9,9 @@ use infrastructure::{logging::LoggingManager, websocket::start_metrics_broadcast
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Load environment variables before logging reads its settings from them
+    dotenvy::dotenv().ok();
+
     // Initialize logging first
     LoggingManager::initialize();
 
@@ -19,9 +22,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Run all services concurrently
     let _result = tokio::join!(
-        StartupService::run_server(db),
+        StartupService::run_server(db.clone()),
         StartupService::run_job_queue_monitor(),
-        StartupService::run_scheduler(),
+        StartupService::run_scheduler(db),
         start_metrics_broadcaster()
     );
 
@@ -35,7 +35,9 @@ fn main() {
         build_frontend();
     } else {
         println!("cargo:warning=Skipping frontend build in development mode");
-        println!("cargo:warning=Set ENVIRONMENT=production or BUILD_FRONTEND=true to build frontend");
+        println!(
+            "cargo:warning=Set ENVIRONMENT=production or BUILD_FRONTEND=true to build frontend"
+        );
         return;
     }
 }
@@ -123,4 +125,4 @@ fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -5,6 +5,21 @@ mod m20250720_000001_create_users;
 mod m20250101_000004_create_database_metrics;
 mod m20250101_000005_create_user_sessions;
 mod m20250727_055016_user_roles;
+mod m20250801_000001_create_refresh_tokens;
+mod m20250802_000001_create_user_tokens;
+mod m20250803_000001_create_user_mfa;
+mod m20250804_000001_create_login_throttles;
+mod m20250805_000001_create_rate_limit_buckets;
+mod m20250806_000001_add_role_parent;
+mod m20250807_000001_create_user_roles;
+mod m20250808_000001_create_user_permissions;
+mod m20250809_000001_create_api_keys;
+mod m20250810_000001_create_user_identities;
+mod m20250811_000001_add_role_session_policy;
+mod m20250812_000001_add_user_account_changes;
+mod m20250813_000001_create_password_history;
+mod m20250814_000001_add_user_email_normalized;
+mod m20250815_000001_add_impersonation;
 
 pub struct Migrator;
 
@@ -17,6 +32,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20250101_000002_create_audit_logs::Migration),
             Box::new(m20250101_000004_create_database_metrics::Migration),
             Box::new(m20250101_000005_create_user_sessions::Migration),
+            Box::new(m20250801_000001_create_refresh_tokens::Migration),
+            Box::new(m20250802_000001_create_user_tokens::Migration),
+            Box::new(m20250803_000001_create_user_mfa::Migration),
+            Box::new(m20250804_000001_create_login_throttles::Migration),
+            Box::new(m20250805_000001_create_rate_limit_buckets::Migration),
+            Box::new(m20250806_000001_add_role_parent::Migration),
+            Box::new(m20250807_000001_create_user_roles::Migration),
+            Box::new(m20250808_000001_create_user_permissions::Migration),
+            Box::new(m20250809_000001_create_api_keys::Migration),
+            Box::new(m20250810_000001_create_user_identities::Migration),
+            Box::new(m20250811_000001_add_role_session_policy::Migration),
+            Box::new(m20250812_000001_add_user_account_changes::Migration),
+            Box::new(m20250813_000001_create_password_history::Migration),
+            Box::new(m20250814_000001_add_user_email_normalized::Migration),
+            Box::new(m20250815_000001_add_impersonation::Migration),
         ]
     }
 }
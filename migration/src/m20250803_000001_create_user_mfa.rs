@@ -0,0 +1,152 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Roles can require their members to use MFA
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Roles::Table)
+                    .add_column(
+                        ColumnDef::new(Roles::MfaRequired)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // TOTP secret per user, enabled once the first code is confirmed
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserMfa::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(UserMfa::UserId).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(UserMfa::Secret).string().not_null())
+                    .col(
+                        ColumnDef::new(UserMfa::EnabledAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(UserMfa::LastUsedStep).big_integer().null())
+                    .col(
+                        ColumnDef::new(UserMfa::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_mfa_user_id")
+                            .from(UserMfa::Table, UserMfa::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One-time recovery codes, stored hashed
+        manager
+            .create_table(
+                Table::create()
+                    .table(MfaRecoveryCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MfaRecoveryCodes::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MfaRecoveryCodes::UserId).uuid().not_null())
+                    .col(ColumnDef::new(MfaRecoveryCodes::CodeHash).string().not_null())
+                    .col(
+                        ColumnDef::new(MfaRecoveryCodes::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(MfaRecoveryCodes::UsedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_mfa_recovery_codes_user_id")
+                            .from(MfaRecoveryCodes::Table, MfaRecoveryCodes::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_mfa_recovery_codes_user_id")
+                    .table(MfaRecoveryCodes::Table)
+                    .col(MfaRecoveryCodes::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MfaRecoveryCodes::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UserMfa::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Roles::Table)
+                    .drop_column(Roles::MfaRequired)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserMfa {
+    Table,
+    UserId,
+    Secret,
+    EnabledAt,
+    LastUsedStep,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum MfaRecoveryCodes {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    CreatedAt,
+    UsedAt,
+}
+
+#[derive(DeriveIden)]
+enum Roles {
+    Table,
+    MfaRequired,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
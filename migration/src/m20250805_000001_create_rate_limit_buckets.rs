@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Token buckets for the SQLite rate limit store, keyed by "<policy>:<client>"
+        manager
+            .create_table(
+                Table::create()
+                    .table(RateLimitBuckets::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RateLimitBuckets::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RateLimitBuckets::Tokens).double().not_null())
+                    .col(
+                        ColumnDef::new(RateLimitBuckets::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RateLimitBuckets::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RateLimitBuckets {
+    Table,
+    Key,
+    Tokens,
+    UpdatedAt,
+}
@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Permissions granted to or denied from a single user on top of their roles
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserPermissions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserPermissions::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserPermissions::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(UserPermissions::Permission)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserPermissions::Effect)
+                            .string_len(10)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserPermissions::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(UserPermissions::Reason).text().null())
+                    .col(ColumnDef::new(UserPermissions::CreatedBy).uuid().null())
+                    .col(
+                        ColumnDef::new(UserPermissions::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_permissions_user_id")
+                            .from(UserPermissions::Table, UserPermissions::UserId)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_permissions_created_by")
+                            .from(UserPermissions::Table, UserPermissions::CreatedBy)
+                            .to(Users::Table, Users::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One record per user and permission, a new grant or deny replaces the old one
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_permissions_user_permission")
+                    .table(UserPermissions::Table)
+                    .col(UserPermissions::UserId)
+                    .col(UserPermissions::Permission)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_permissions_expires_at")
+                    .table(UserPermissions::Table)
+                    .col(UserPermissions::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserPermissions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserPermissions {
+    Table,
+    Id,
+    UserId,
+    Permission,
+    Effect,
+    ExpiresAt,
+    Reason,
+    CreatedBy,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+}
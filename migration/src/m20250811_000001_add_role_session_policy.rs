@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Per-role session policy, empty columns fall back to the server defaults.
+        // SQLite only adds one column per ALTER TABLE
+        for column in [
+            ColumnDef::new(Roles::MaxSessions).integer().null().to_owned(),
+            ColumnDef::new(Roles::SessionLimitAction)
+                .string_len(10)
+                .null()
+                .to_owned(),
+            ColumnDef::new(Roles::IdleTimeoutMinutes)
+                .integer()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Roles::SessionLifetimeHours)
+                .integer()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Roles::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Roles::MaxSessions,
+            Roles::SessionLimitAction,
+            Roles::IdleTimeoutMinutes,
+            Roles::SessionLifetimeHours,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Roles::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Roles {
+    Table,
+    MaxSessions,
+    SessionLimitAction,
+    IdleTimeoutMinutes,
+    SessionLifetimeHours,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Canonical address used for lookups, filled in by the application on startup since
+        // normalizing internationalized domains needs more than SQL can do
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::EmailNormalized).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_users_email_normalized")
+                    .table(Users::Table)
+                    .col(Users::EmailNormalized)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        report_duplicates(manager).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_users_email_normalized")
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::EmailNormalized)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// Prints the accounts whose addresses only differ in case. Only the oldest account of each
+/// group gets the normalized address, the others keep signing in with their exact address until
+/// an admin merges or removes them (see GET /api/v1/admin/users/duplicates)
+async fn report_duplicates(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+    let rows = db
+        .query_all(Statement::from_string(
+            manager.get_database_backend(),
+            "SELECT lower(trim(email)) AS normalized, group_concat(email, ', ') AS emails \
+             FROM users GROUP BY lower(trim(email)) HAVING count(*) > 1 ORDER BY normalized",
+        ))
+        .await?;
+
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    println!(
+        "{} email addresses are used by more than one account, only the oldest account of each keeps the address:",
+        rows.len()
+    );
+    for row in rows {
+        let normalized: String = row.try_get("", "normalized")?;
+        let emails: String = row.try_get("", "emails")?;
+        println!("  {}: {}", normalized, emails);
+    }
+
+    Ok(())
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    EmailNormalized,
+}
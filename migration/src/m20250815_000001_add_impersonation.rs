@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Admin a session was started for while impersonating its user
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserSessions::Table)
+                    .add_column(ColumnDef::new(UserSessions::ImpersonatorId).uuid().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Admin who acted on behalf of the logged user
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditLogs::Table)
+                    .add_column(ColumnDef::new(AuditLogs::ActorId).uuid().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AuditLogs::Table)
+                    .drop_column(AuditLogs::ActorId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserSessions::Table)
+                    .drop_column(UserSessions::ImpersonatorId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserSessions {
+    Table,
+    ImpersonatorId,
+}
+
+#[derive(DeriveIden)]
+enum AuditLogs {
+    Table,
+    ActorId,
+}